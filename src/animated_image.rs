@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::warn;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, Frame};
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+// Most players treat frames without a delay as lasting 100ms
+const DEFAULT_FRAME_DELAY: f64 = 0.1;
+
+pub struct AnimatedImageProvider {
+    name: String,
+    resolution: (usize, usize),
+
+    frames: Vec<Vec<u8>>,
+    delays: Vec<f64>,
+
+    current_frame: usize,
+    frame_start_time: f64,
+    fresh_frame: bool,
+
+    time: f64,
+    playing: bool,
+}
+
+impl AnimatedImageProvider {
+    pub fn new(
+        path: &str,
+        name: String,
+        resolution: (usize, usize),
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let frames = decode_frames(Path::new(path))?;
+        if frames.is_empty() {
+            return Err(anyhow::anyhow!("No frame found in animated image {:?}", path));
+        }
+
+        let mut delays = Vec::with_capacity(frames.len());
+        let mut frame_buffers = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay = if numerator == 0 || denominator == 0 {
+                DEFAULT_FRAME_DELAY
+            } else {
+                numerator as f64 / denominator as f64 / 1_000.0
+            };
+
+            let image = DynamicImage::ImageRgba8(frame.into_buffer());
+            let image = image.resize_exact(
+                resolution.0 as u32,
+                resolution.1 as u32,
+                FilterType::Triangle,
+            );
+
+            // Matches the vertical flip applied to decoded videos
+            delays.push(delay);
+            frame_buffers.push(image.flipv().into_rgb8().into_raw());
+        }
+
+        Ok(Self {
+            name,
+            resolution,
+            frames: frame_buffers,
+            delays,
+            current_frame: 0,
+            frame_start_time: start_time,
+            fresh_frame: true,
+            time: start_time,
+            playing: start_playing,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+fn decode_frames(path: &Path) -> Result<Vec<Frame>> {
+    let file = File::open(path).context(format!("Failed to open animated image {:?}", path))?;
+    let reader = BufReader::new(file);
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    let frames = match extension.as_deref() {
+        Some("gif") => GifDecoder::new(reader)?.into_frames().collect_frames(),
+        Some("png") | Some("apng") => PngDecoder::new(reader)?.apng().into_frames().collect_frames(),
+        _ => return Err(anyhow::anyhow!("Unsupported animated image format {:?}", path)),
+    };
+
+    frames.context(format!("Failed to decode animated image {:?}", path))
+}
+
+impl InputProvider for AnimatedImageProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn set_property(&mut self, property: &str, _value: &DataHolder) {
+        warn!("Set_property unimplemented for {:}", property);
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        if uniform_name == self.name {
+            if !self.fresh_frame {
+                return None;
+            }
+
+            if invalidate {
+                self.fresh_frame = false;
+            }
+
+            Some(DataHolder::Texture((
+                (self.resolution.0 as u32, self.resolution.1 as u32),
+                self.frames[self.current_frame].to_vec(),
+            )))
+        } else {
+            None
+        }
+    }
+
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {}
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        self.time = time;
+
+        if !self.playing {
+            return;
+        }
+
+        while self.time - self.frame_start_time >= self.delays[self.current_frame] {
+            self.frame_start_time += self.delays[self.current_frame];
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+            self.fresh_frame = true;
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.playing = false;
+        self.current_frame = 0;
+        self.fresh_frame = true;
+
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.playing = true;
+        self.frame_start_time = self.time;
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playing = false;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use log::error;
+
+use gst::prelude::*;
+
+use crate::video::stream_index;
+
+pub(crate) const SPECTRUM_BANDS: u32 = 64;
+
+// Magnitudes below this level in dB are reported as silence
+const SPECTRUM_THRESHOLD: f32 = -80.0;
+
+pub(crate) struct AudioSettings {
+    pub analysis: AtomicBool,
+    // Whether the soundtrack is played on the default audio device instead of being discarded
+    pub output: AtomicBool,
+    pub volume: Mutex<f64>,
+    pub mute: AtomicBool,
+    // Index of the soundtrack to use among the audio streams of the decoder
+    pub track: AtomicUsize,
+}
+
+impl AudioSettings {
+    pub fn new() -> Self {
+        Self {
+            analysis: AtomicBool::new(false),
+            output: AtomicBool::new(false),
+            volume: Mutex::new(1.0),
+            mute: AtomicBool::new(false),
+            track: AtomicUsize::new(0),
+        }
+    }
+}
+
+// Builds the audio branch once the decoder exposes the selected audio stream, any other audio stream is left unlinked
+pub(crate) fn watch_audio_pads(pipeline: &gst::Element, decoder: &gst::Element, settings: Arc<AudioSettings>) {
+    // The decoder lives inside the pipeline, holding a strong reference from its callback would leak both
+    let pipeline = pipeline.downgrade();
+
+    decoder.connect_pad_added(move |decoder, pad| {
+        let pipeline = if let Some(pipeline) = pipeline.upgrade() {
+            pipeline
+        } else {
+            return;
+        };
+
+        if stream_index(decoder, pad, "audio/") != Some(settings.track.load(Ordering::SeqCst)) {
+            return;
+        }
+
+        if let Err(e) = attach_audio_branch(&pipeline, pad, &settings) {
+            error!("{:?}", e);
+        }
+    });
+}
+
+fn attach_audio_branch(pipeline: &gst::Element, pad: &gst::Pad, settings: &AudioSettings) -> Result<()> {
+    if pad.is_linked() {
+        return Ok(());
+    }
+
+    let pipeline = pipeline
+        .clone()
+        .dynamic_cast::<gst::Bin>()
+        .expect("Failed to cast the gstreamer pipeline as a gst::Bin element");
+
+    // The branch is kept across restarts of the pipeline, only its link to the decoder has to be restored
+    let branch = match pipeline.get_by_name("audiobranch") {
+        Some(branch) => branch,
+        None => build_audio_branch(&pipeline, settings)?,
+    };
+
+    let sink_pad = branch
+        .get_static_pad("sink")
+        .context("Failed to find the input of the audio branch")?;
+
+    if sink_pad.is_linked() {
+        return Ok(());
+    }
+
+    pad.link(&sink_pad)
+        .map_err(|e| anyhow!("Failed to link the audio stream to its analysis branch: {:?}", e))?;
+
+    Ok(())
+}
+
+fn build_audio_branch(pipeline: &gst::Bin, settings: &AudioSettings) -> Result<gst::Element> {
+    let analysis = settings.analysis.load(Ordering::SeqCst);
+    let volume = settings.volume.lock().map(|volume| *volume).unwrap_or(1.0);
+    let mute = settings.mute.load(Ordering::SeqCst);
+
+    // Either sink follows the clock, so that the analysis matches the part of the soundtrack being played.
+    // A discarded soundtrack is not, it would otherwise pace the demuxer to the speed of the clock
+    let sink = if settings.output.load(Ordering::SeqCst) {
+        "autoaudiosink".to_owned()
+    } else {
+        format!("fakesink name=audiosink sync={} async=false", analysis)
+    };
+
+    let description = format!(
+        "queue ! audioconvert ! audioresample ! spectrum name=spectrum bands={} threshold={} post-messages={} ! level name=level post-messages={} ! volume name=volume volume={} mute={} ! {}",
+        SPECTRUM_BANDS, SPECTRUM_THRESHOLD as i32, analysis, analysis, volume, mute, sink,
+    );
+
+    let branch = gst::parse_bin_from_description(&description, true)
+        .context("Failed to build the audio branch of the video pipeline")?;
+    branch
+        .set_name("audiobranch")
+        .context("Failed to name the audio branch of the video pipeline")?;
+
+    pipeline
+        .add(&branch)
+        .context("Failed to add the audio branch to the video pipeline")?;
+    branch
+        .sync_state_with_parent()
+        .context("Failed to start the audio branch of the video pipeline")?;
+
+    Ok(branch.upcast())
+}
+
+// Spectrum magnitudes are mapped from [threshold, 0] dB to [0, 1]
+pub(crate) fn parse_spectrum(structure: &gst::StructureRef) -> Option<Vec<f32>> {
+    let magnitudes = structure.get::<gst::List>("magnitude").ok().flatten()?;
+
+    Some(
+        magnitudes
+            .as_slice()
+            .iter()
+            .filter_map(|magnitude| magnitude.get::<f32>().ok().flatten())
+            .map(|magnitude| ((magnitude - SPECTRUM_THRESHOLD) / -SPECTRUM_THRESHOLD).max(0.0).min(1.0))
+            .collect(),
+    )
+}
+
+// The level of each channel is reported in dB, their linear amplitudes are averaged
+pub(crate) fn parse_rms(structure: &gst::StructureRef) -> Option<f32> {
+    let channels = structure.get::<glib::ValueArray>("rms").ok().flatten()?;
+
+    let amplitudes: Vec<f64> = channels
+        .iter()
+        .filter_map(|channel| channel.get::<f64>().ok().flatten())
+        .map(|rms| 10f64.powf(rms / 20.0))
+        .collect();
+
+    if amplitudes.is_empty() {
+        return None;
+    }
+
+    Some((amplitudes.iter().sum::<f64>() / amplitudes.len() as f64) as f32)
+}
@@ -0,0 +1,453 @@
+use anyhow::{Context, Result};
+use log::{error, warn};
+
+use gst::prelude::*;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::config::CameraConfig;
+use crate::stats::DecodeStats;
+use crate::video::quote_launch_value;
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::PlaybackSpeed;
+use crate::video::VideoProvider;
+
+#[derive(Clone, Debug)]
+pub struct VideoDeviceInfo {
+    pub name: String,
+    // Can be passed as the device of a CameraProvider
+    pub id: String,
+    pub caps: Vec<String>,
+}
+
+// Lists the cameras and capture devices currently plugged in, along with the formats they support
+pub fn list_video_devices() -> Result<Vec<VideoDeviceInfo>> {
+    crate::init()?;
+
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Video/Source"), None);
+    monitor.start().context("Failed to start monitoring video devices")?;
+
+    let devices = monitor
+        .get_devices()
+        .into_iter()
+        .enumerate()
+        .map(|(index, device)| {
+            // avfvideosrc only selects devices by index, other sources by path
+            let path = device
+                .get_properties()
+                .and_then(|properties| properties.get::<String>("device.path").ok().flatten());
+            let id = match path {
+                Some(path) if !cfg!(target_os = "macos") => path,
+                _ => index.to_string(),
+            };
+
+            let caps = device
+                .get_caps()
+                .map(|caps| caps.iter().map(|structure| structure.to_string()).collect())
+                .unwrap_or_default();
+
+            VideoDeviceInfo {
+                name: device.get_display_name().to_string(),
+                id,
+                caps,
+            }
+        })
+        .collect();
+
+    monitor.stop();
+
+    Ok(devices)
+}
+
+const CAMERA_SOURCE_NAME: &str = "camera";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SensorMode {
+    pub width: usize,
+    pub height: usize,
+    pub framerate: f64,
+}
+
+// Recent kernels renamed some UVC controls, the name the driver does not know is ignored by v4l2src
+const AUTO_EXPOSURE_CONTROLS: &[&str] = &["exposure_auto", "auto_exposure"];
+const EXPOSURE_CONTROLS: &[&str] = &["exposure_absolute", "exposure_time_absolute"];
+const AUTO_WHITE_BALANCE_CONTROLS: &[&str] = &["white_balance_temperature_auto", "white_balance_automatic"];
+const WHITE_BALANCE_CONTROLS: &[&str] = &["white_balance_temperature"];
+const GAIN_CONTROLS: &[&str] = &["gain"];
+const AUTO_FOCUS_CONTROLS: &[&str] = &["focus_auto", "focus_automatic_continuous"];
+const FOCUS_CONTROLS: &[&str] = &["focus_absolute"];
+
+// Values of the exposure mode menu
+const EXPOSURE_MANUAL: i32 = 1;
+const EXPOSURE_APERTURE_PRIORITY: i32 = 3;
+
+pub struct CameraProvider {
+    video_provider: VideoProvider,
+    controls: gst::Structure,
+}
+
+impl CameraProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: Option<&str>,
+        name: String,
+        resolution: (usize, usize),
+        framerate: Option<f64>,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let mut source = format!("{} name={}", camera_source(device), CAMERA_SOURCE_NAME);
+
+        // Cameras often deliver compressed frames, decodebin takes care of both raw and mjpeg streams
+        source.push_str(" ! decodebin");
+        if let Some(framerate) = framerate {
+            source.push_str(&format!(
+                " ! videorate ! video/x-raw,framerate={}/1000",
+                (framerate * 1000.0) as i32
+            ));
+        }
+
+        let video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        Ok(Self {
+            video_provider,
+            controls: gst::Structure::new_empty("controls"),
+        })
+    }
+
+    pub fn from_config(name: String, config: &CameraConfig) -> Result<Self> {
+        Self::new(
+            config.device.as_deref(),
+            name,
+            config.resolution,
+            config.framerate,
+            config.speed,
+            config.start_beat,
+            config.start_time,
+            config.start_playing,
+        )
+    }
+
+    // Uses the CSI cameras of Raspberry Pi boards, and others supported by libcamera, without going through V4L2.
+    // The sensor mode is picked by libcamera from the requested size and frame rate
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_libcamera(
+        camera_name: Option<&str>,
+        sensor_mode: Option<SensorMode>,
+        name: String,
+        resolution: (usize, usize),
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let mut source = format!("libcamerasrc name={}", CAMERA_SOURCE_NAME);
+        if let Some(camera_name) = camera_name {
+            source.push_str(&format!(" camera-name=\"{}\"", quote_launch_value(camera_name)));
+        }
+        if let Some(sensor_mode) = sensor_mode {
+            source.push_str(&format!(
+                " ! video/x-raw,width={},height={},framerate={}/1000",
+                sensor_mode.width,
+                sensor_mode.height,
+                (sensor_mode.framerate * 1000.0) as i32
+            ));
+        }
+
+        let video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        Ok(Self {
+            video_provider,
+            controls: gst::Structure::new_empty("controls"),
+        })
+    }
+
+    // Exposure time in seconds, None lets the camera adjust it
+    pub fn set_exposure(&mut self, exposure: Option<f64>) -> Result<()> {
+        match exposure {
+            Some(exposure) => {
+                self.set_controls(AUTO_EXPOSURE_CONTROLS, EXPOSURE_MANUAL)?;
+                // UVC exposure times are counted in units of 100µs
+                self.set_controls(EXPOSURE_CONTROLS, (exposure * 10_000.0).round() as i32)
+            }
+            None => self.set_controls(AUTO_EXPOSURE_CONTROLS, EXPOSURE_APERTURE_PRIORITY),
+        }
+    }
+
+    // White balance temperature in Kelvin, None lets the camera adjust it
+    pub fn set_white_balance(&mut self, temperature: Option<f64>) -> Result<()> {
+        match temperature {
+            Some(temperature) => {
+                self.set_controls(AUTO_WHITE_BALANCE_CONTROLS, 0)?;
+                self.set_controls(WHITE_BALANCE_CONTROLS, temperature.round() as i32)
+            }
+            None => self.set_controls(AUTO_WHITE_BALANCE_CONTROLS, 1),
+        }
+    }
+
+    // Gain in the units of the device
+    pub fn set_gain(&mut self, gain: f64) -> Result<()> {
+        self.set_controls(GAIN_CONTROLS, gain.round() as i32)
+    }
+
+    // Focus distance in the units of the device, None enables auto focus
+    pub fn set_focus(&mut self, focus: Option<f64>) -> Result<()> {
+        match focus {
+            Some(focus) => {
+                self.set_controls(AUTO_FOCUS_CONTROLS, 0)?;
+                self.set_controls(FOCUS_CONTROLS, focus.round() as i32)
+            }
+            None => self.set_controls(AUTO_FOCUS_CONTROLS, 1),
+        }
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        self.video_provider.stats()
+    }
+
+    // Controls are applied right away when the device is open, or when it opens otherwise
+    fn set_controls(&mut self, names: &[&str], value: i32) -> Result<()> {
+        let source = self
+            .video_provider
+            .get_element(CAMERA_SOURCE_NAME)
+            .filter(|source| source.find_property("extra-controls").is_some())
+            .context("Camera controls are only available for V4L2 devices")?;
+
+        for name in names {
+            self.controls.set(name, &value);
+        }
+
+        source
+            .set_property("extra-controls", &self.controls)
+            .context("Failed to apply camera controls")?;
+
+        Ok(())
+    }
+}
+
+// Opens several cameras at once, exposed as <name>_0, <name>_1, ... and all following the same clock
+pub struct MultiCameraProvider {
+    name: String,
+    cameras: Vec<CameraProvider>,
+}
+
+impl MultiCameraProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        devices: &[&str],
+        name: String,
+        resolution: (usize, usize),
+        framerate: Option<f64>,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let speed = speed.into();
+        let cameras = devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| {
+                CameraProvider::new(Some(device), format!("{}_{}", name, index), resolution, framerate, speed, start_beat, start_time, false)
+                    .context(format!("Failed to open camera {:?}", device))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Cameras only start once they share the clock and the base time
+        let clock = gst::SystemClock::obtain();
+        let base_time = clock.get_time();
+        for camera in cameras.iter() {
+            camera.video_provider.use_clock(&clock, base_time);
+        }
+
+        let mut provider = Self { name, cameras };
+        if start_playing {
+            provider.play()?;
+        }
+
+        Ok(provider)
+    }
+
+    pub fn camera_count(&self) -> usize {
+        self.cameras.len()
+    }
+}
+
+impl InputProvider for MultiCameraProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+        for (index, camera) in self.cameras.iter_mut().enumerate() {
+            camera.set_name(&format!("{}_{}", name, index));
+        }
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.cameras.iter().flat_map(|camera| camera.provides()).collect()
+    }
+
+    // Properties apply to every camera, or to a single one when prefixed by its index, e.g. "1:gain"
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        if let Some((index, property)) = property.split_once(':') {
+            match index.parse::<usize>().ok().and_then(|index| self.cameras.get_mut(index)) {
+                Some(camera) => camera.set_property(property, value),
+                None => warn!("No camera {:} in {:}", index, self.name),
+            }
+            return;
+        }
+
+        for camera in self.cameras.iter_mut() {
+            camera.set_property(property, value);
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.cameras
+            .iter_mut()
+            .find(|camera| camera.provides().iter().any(|name| name == uniform_name))
+            .and_then(|camera| camera.get(uniform_name, invalidate))
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        for camera in self.cameras.iter_mut() {
+            camera.set_beat(beat, sync);
+        }
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        for camera in self.cameras.iter_mut() {
+            camera.set_time(time, sync);
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        for camera in self.cameras.iter_mut() {
+            camera.stop()?;
+        }
+
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        for camera in self.cameras.iter_mut() {
+            camera.play()?;
+        }
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        for camera in self.cameras.iter_mut() {
+            camera.pause()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn camera_source(device: Option<&str>) -> String {
+    if cfg!(target_os = "windows") {
+        match device {
+            Some(device) if device.parse::<u32>().is_ok() => {
+                format!("ksvideosrc device-index={}", device)
+            }
+            Some(device) => format!("ksvideosrc device-path=\"{}\"", device),
+            None => "ksvideosrc".to_owned(),
+        }
+    } else if cfg!(target_os = "macos") {
+        match device {
+            Some(device) => format!("avfvideosrc device-index={}", device),
+            None => "avfvideosrc".to_owned(),
+        }
+    } else {
+        match device {
+            Some(device) if device.parse::<u32>().is_ok() => {
+                format!("v4l2src device=/dev/video{}", device)
+            }
+            Some(device) => format!("v4l2src device=\"{}\"", device),
+            None => "v4l2src".to_owned(),
+        }
+    }
+}
+
+impl InputProvider for CameraProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides().into_iter().take(1).collect()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        let result = match (property, value) {
+            ("exposure", DataHolder::Float(exposure)) => self.set_exposure(Some(*exposure as f64)),
+            ("auto_exposure", DataHolder::Bool(true)) => self.set_exposure(None),
+            ("white_balance", DataHolder::Float(temperature)) => self.set_white_balance(Some(*temperature as f64)),
+            ("auto_white_balance", DataHolder::Bool(true)) => self.set_white_balance(None),
+            ("gain", DataHolder::Float(gain)) => self.set_gain(*gain as f64),
+            ("focus", DataHolder::Float(focus)) => self.set_focus(Some(*focus as f64)),
+            ("auto_focus", DataHolder::Bool(true)) => self.set_focus(None),
+            _ => {
+                self.video_provider.set_property(property, value);
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            error!("{:?}", e);
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
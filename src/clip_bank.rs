@@ -0,0 +1,240 @@
+use anyhow::Result;
+use log::error;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+use wvr_data::types::Speed;
+
+use crate::transition::{Crossfade, TransitionDuration};
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::VideoProvider;
+
+pub struct ClipBankProvider {
+    name: String,
+    clips: Vec<VideoProvider>,
+    selected: usize,
+    playing: bool,
+
+    beat: f64,
+    time: f64,
+
+    transition: Option<TransitionDuration>,
+    // Index of the previously selected clip, kept playing until the crossfade is over
+    crossfade: Option<(usize, Crossfade)>,
+}
+
+impl ClipBankProvider {
+    pub fn new(
+        paths: &[String],
+        name: String,
+        resolution: (usize, usize),
+        speed: Speed,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        if paths.is_empty() {
+            return Err(anyhow::anyhow!("Clip bank {:?} does not contain any video", name));
+        }
+
+        let mut clips = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut clip = VideoProvider::new(
+                path,
+                name.clone(),
+                resolution,
+                speed.clone(),
+                start_beat,
+                start_time,
+                false,
+                LoopMode::Loop,
+                None,
+                None,
+                OutputFormat::default(),
+            )?;
+
+            // Prerolling every clip makes switching between them instantaneous
+            clip.pause()?;
+            clips.push(clip);
+        }
+
+        let mut clip_bank = Self {
+            name,
+            clips,
+            selected: 0,
+            playing: start_playing,
+            beat: start_beat,
+            time: start_time,
+            transition: None,
+            crossfade: None,
+        };
+
+        if start_playing {
+            clip_bank.clips[0].play()?;
+        }
+
+        Ok(clip_bank)
+    }
+
+    pub fn len(&self) -> usize {
+        self.clips.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clips.is_empty()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set_transition(&mut self, transition: Option<TransitionDuration>) {
+        self.transition = transition;
+    }
+
+    pub fn select(&mut self, index: usize) -> Result<()> {
+        if index >= self.clips.len() {
+            return Err(anyhow::anyhow!(
+                "Clip index {} is out of range for clip bank {:?} of {} clips",
+                index,
+                self.name,
+                self.clips.len()
+            ));
+        }
+
+        if index == self.selected {
+            return Ok(());
+        }
+
+        // Only one crossfade runs at a time, an interrupted one is cut short
+        self.finish_crossfade()?;
+
+        let previous = self.selected;
+        match self.transition {
+            Some(transition) if self.playing => {
+                self.crossfade = Some((previous, Crossfade::new(transition, self.beat, self.time)));
+            }
+            _ => self.release_clip(previous)?,
+        }
+
+        self.selected = index;
+        if self.playing {
+            self.clips[index].play()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish_crossfade(&mut self) -> Result<()> {
+        if let Some((previous, _)) = self.crossfade.take() {
+            if previous != self.selected {
+                self.release_clip(previous)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The deselected clip is rewound right away so it is prerolled for its next trigger
+    fn release_clip(&mut self, index: usize) -> Result<()> {
+        let clip = &mut self.clips[index];
+        clip.pause()?;
+        clip.seek(0.0)?;
+
+        Ok(())
+    }
+}
+
+impl InputProvider for ClipBankProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+        for clip in self.clips.iter_mut() {
+            clip.set_name(name);
+        }
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.clips[self.selected].provides()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        match (property, value) {
+            ("select", DataHolder::Int(index)) => {
+                if let Err(e) = self.select((*index).max(0) as usize) {
+                    error!("{:?}", e);
+                }
+            }
+            ("transition_seconds", DataHolder::Float(duration)) => {
+                self.set_transition(Some(TransitionDuration::Seconds(*duration as f64)).filter(|_| *duration > 0.0));
+            }
+            ("transition_beats", DataHolder::Float(duration)) => {
+                self.set_transition(Some(TransitionDuration::Beats(*duration as f64)).filter(|_| *duration > 0.0));
+            }
+            ("speed_fpb", _) | ("speed_fps", _) => {
+                for clip in self.clips.iter_mut() {
+                    clip.set_property(property, value);
+                }
+            }
+            _ => self.clips[self.selected].set_property(property, value),
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        let crossfade_finished = self
+            .crossfade
+            .as_ref()
+            .map(|(_, crossfade)| crossfade.progress(self.beat, self.time) >= 1.0)
+            .unwrap_or(false);
+        if crossfade_finished {
+            if let Err(e) = self.finish_crossfade() {
+                error!("{:?}", e);
+            }
+        }
+
+        if uniform_name == self.name {
+            if let Some((previous, crossfade)) = self.crossfade.as_mut() {
+                let progress = crossfade.progress(self.beat, self.time);
+                let outgoing = self.clips[*previous].get(uniform_name, invalidate);
+                let incoming = self.clips[self.selected].get(uniform_name, invalidate);
+
+                return crossfade.blend(outgoing, incoming, progress);
+            }
+        }
+
+        self.clips[self.selected].get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.beat = beat;
+        for (index, clip) in self.clips.iter_mut().enumerate() {
+            clip.set_beat(beat, sync && index == self.selected);
+        }
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.time = time;
+        for (index, clip) in self.clips.iter_mut().enumerate() {
+            clip.set_time(time, sync && index == self.selected);
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.playing = false;
+        for clip in self.clips.iter_mut() {
+            clip.stop()?;
+        }
+
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.playing = true;
+        self.clips[self.selected].play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playing = false;
+        self.clips[self.selected].pause()
+    }
+}
@@ -0,0 +1,539 @@
+use serde::{Deserialize, Serialize};
+
+use crate::encoder::{AudioCodec, Container, HardwareEncoder, PixelFormat, RateControl, VideoCodec};
+use crate::transition::TransitionDuration;
+use crate::video::{DeinterlaceMethod, FitMode, LoopMode, Orientation, OutputFormat, PlaybackSpeed};
+
+// Frame size used when a project does not specify one for a live source
+const DEFAULT_LIVE_RESOLUTION: (usize, usize) = (1280, 720);
+
+// Everything a VideoProvider can be opened with, missing fields fall back to their defaults when deserializing
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoProviderConfig {
+    // Without a resolution frames keep the native size of the footage
+    pub resolution: Option<(usize, usize)>,
+    pub speed: PlaybackSpeed,
+    pub start_beat: f64,
+    pub start_time: f64,
+    pub start_playing: bool,
+    // Opens the source and prerolls on a background thread, see VideoProvider::new_async
+    pub async_start: bool,
+
+    pub loop_mode: LoopMode,
+    pub loop_start: Option<f64>,
+    pub loop_end: Option<f64>,
+
+    pub output_format: OutputFormat,
+    pub orientation: Orientation,
+    pub fit_mode: FitMode,
+    pub deinterlace: Option<DeinterlaceMethod>,
+    pub hardware_decode: bool,
+}
+
+impl Default for VideoProviderConfig {
+    fn default() -> Self {
+        Self {
+            resolution: None,
+            speed: PlaybackSpeed::Factor(1.0),
+            start_beat: 0.0,
+            start_time: 0.0,
+            start_playing: true,
+            async_start: false,
+            loop_mode: LoopMode::default(),
+            loop_start: None,
+            loop_end: None,
+            output_format: OutputFormat::default(),
+            orientation: Orientation::default(),
+            fit_mode: FitMode::default(),
+            deinterlace: None,
+            hardware_decode: true,
+        }
+    }
+}
+
+impl VideoProviderConfig {
+    pub fn resolution(mut self, resolution: (usize, usize)) -> Self {
+        self.resolution = Some(resolution);
+        self
+    }
+
+    pub fn speed(mut self, speed: impl Into<PlaybackSpeed>) -> Self {
+        self.speed = speed.into();
+        self
+    }
+
+    pub fn start_beat(mut self, start_beat: f64) -> Self {
+        self.start_beat = start_beat;
+        self
+    }
+
+    pub fn start_time(mut self, start_time: f64) -> Self {
+        self.start_time = start_time;
+        self
+    }
+
+    pub fn start_playing(mut self, start_playing: bool) -> Self {
+        self.start_playing = start_playing;
+        self
+    }
+
+    pub fn async_start(mut self, async_start: bool) -> Self {
+        self.async_start = async_start;
+        self
+    }
+
+    pub fn loop_mode(mut self, loop_mode: LoopMode) -> Self {
+        self.loop_mode = loop_mode;
+        self
+    }
+
+    pub fn loop_region(mut self, loop_start: Option<f64>, loop_end: Option<f64>) -> Self {
+        self.loop_start = loop_start;
+        self.loop_end = loop_end;
+        self
+    }
+
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn fit_mode(mut self, fit_mode: FitMode) -> Self {
+        self.fit_mode = fit_mode;
+        self
+    }
+
+    pub fn deinterlace(mut self, deinterlace: Option<DeinterlaceMethod>) -> Self {
+        self.deinterlace = deinterlace;
+        self
+    }
+
+    pub fn hardware_decode(mut self, hardware_decode: bool) -> Self {
+        self.hardware_decode = hardware_decode;
+        self
+    }
+}
+
+// A video file or URI as declared in a project file, the provider options sit next to the path
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VideoConfig {
+    pub path: String,
+    #[serde(flatten)]
+    pub provider: VideoProviderConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    // Index or path of the device, as listed by list_video_devices, the default camera otherwise
+    pub device: Option<String>,
+    pub resolution: (usize, usize),
+    pub framerate: Option<f64>,
+    pub speed: PlaybackSpeed,
+    pub start_beat: f64,
+    pub start_time: f64,
+    pub start_playing: bool,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            resolution: DEFAULT_LIVE_RESOLUTION,
+            framerate: None,
+            speed: PlaybackSpeed::Native,
+            start_beat: 0.0,
+            start_time: 0.0,
+            start_playing: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistConfig {
+    pub paths: Vec<String>,
+    pub resolution: (usize, usize),
+    pub speed: PlaybackSpeed,
+    pub start_beat: f64,
+    pub start_time: f64,
+    pub start_playing: bool,
+    pub transition: Option<TransitionDuration>,
+}
+
+impl Default for PlaylistConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            resolution: DEFAULT_LIVE_RESOLUTION,
+            speed: PlaybackSpeed::Factor(1.0),
+            start_beat: 0.0,
+            start_time: 0.0,
+            start_playing: true,
+            transition: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncoderConfig {
+    // The first output, its fields sit next to the ones of the frames
+    #[serde(flatten)]
+    pub output: OutputConfig,
+    pub width: usize,
+    pub height: usize,
+    pub framerate: f64,
+    #[serde(default)]
+    pub target_duration: Option<f64>,
+
+    // Layout of the frames given to encode_frame, the alpha channel of RGBA formats is only kept by codecs supporting it
+    #[serde(default)]
+    pub pixel_format: PixelFormat,
+    // Muxers wait for both tracks, pushed samples have to keep up for the recording to progress
+    #[serde(default)]
+    pub audio: Option<EncoderAudioConfig>,
+    // Encoded from the same frames as the first output, e.g. a stream next to a lossless recording
+    #[serde(default)]
+    pub extra_outputs: Vec<OutputConfig>,
+    // Keeps the last seconds encoded in memory for VideoEncoder::save_replay
+    #[serde(default)]
+    pub replay: Option<ReplayConfig>,
+    // Frames waiting to reach the encoders, encode_frame returns right away until it is full
+    #[serde(default)]
+    pub queue_size: Option<usize>,
+    // Waits for room in a full queue instead of dropping the frame
+    #[serde(default)]
+    pub block_when_full: bool,
+}
+
+// One file or stream written by a VideoEncoder, the path is either a file or an rtmp://, srt://, udp://, ndi://, v4l2:// or shm:// URL
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutputConfig {
+    pub path: String,
+
+    #[serde(default)]
+    pub codec: VideoCodec,
+    // Without a container the usual one of the codec is used, see VideoCodec::default_container
+    #[serde(default)]
+    pub container: Option<Container>,
+    // Encoder defaults apply when unset
+    #[serde(default)]
+    pub rate_control: Option<RateControl>,
+    // Speed / quality trade-off, named after the presets of the encoder
+    #[serde(default)]
+    pub preset: Option<String>,
+    // Falls back to software encoding when the requested backend is not available
+    #[serde(default)]
+    pub hardware: HardwareEncoder,
+    // Maximum number of frames between two keyframes, shorter intervals seek faster but weigh more
+    #[serde(default)]
+    pub keyframe_interval: Option<u32>,
+    // Moves the index of MP4 and MOV files to their start once recording ends, so that browsers can play them while downloading
+    #[serde(default)]
+    pub faststart: bool,
+    // Recordings to files are split in numbered segments once they last this many seconds or weigh this many bytes
+    #[serde(default)]
+    pub max_segment_duration: Option<f64>,
+    #[serde(default)]
+    pub max_segment_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncoderAudioConfig {
+    // Without a codec the usual one of the container is used, see Container::default_audio_codec
+    pub codec: Option<AudioCodec>,
+    pub sample_rate: u32,
+    pub channels: u32,
+    // In kbit/s, encoder defaults apply when unset
+    pub bitrate: Option<u32>,
+    pub source: AudioSource,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AudioSource {
+    // Samples are given through VideoEncoder::push_audio_samples
+    Pushed,
+    // Records an input device, the default one without a name
+    Device(Option<String>),
+    // Records what is played on an output device, the default one without a name
+    Loopback(Option<String>),
+}
+
+impl Default for AudioSource {
+    fn default() -> Self {
+        AudioSource::Pushed
+    }
+}
+
+impl Default for EncoderAudioConfig {
+    fn default() -> Self {
+        Self {
+            codec: None,
+            sample_rate: 48000,
+            channels: 2,
+            bitrate: None,
+            source: AudioSource::default(),
+        }
+    }
+}
+
+// Encodes to the lossless Huffyuv codec in Matroska unless told otherwise, e.g.
+// EncoderConfig::new("out.mp4", 1920, 1080, 60.0).codec(VideoCodec::H264).rate_control(RateControl::Crf(20)).
+// The output settings apply to the first output, the others are given through extra_output
+impl EncoderConfig {
+    pub fn new(path: &str, width: usize, height: usize, framerate: f64) -> Self {
+        Self {
+            output: OutputConfig::new(path),
+            width,
+            height,
+            framerate,
+            target_duration: None,
+            pixel_format: PixelFormat::default(),
+            audio: None,
+            extra_outputs: Vec::new(),
+            replay: None,
+            queue_size: None,
+            block_when_full: false,
+        }
+    }
+
+    // The first output followed by the extra ones
+    pub fn outputs(&self) -> impl Iterator<Item = &OutputConfig> {
+        std::iter::once(&self.output).chain(self.extra_outputs.iter())
+    }
+
+    pub fn target_duration(mut self, target_duration: f64) -> Self {
+        self.target_duration = Some(target_duration);
+        self
+    }
+
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.output = self.output.codec(codec);
+        self
+    }
+
+    pub fn container(mut self, container: Container) -> Self {
+        self.output = self.output.container(container);
+        self
+    }
+
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.output = self.output.rate_control(rate_control);
+        self
+    }
+
+    pub fn preset(mut self, preset: &str) -> Self {
+        self.output = self.output.preset(preset);
+        self
+    }
+
+    pub fn pixel_format(mut self, pixel_format: PixelFormat) -> Self {
+        self.pixel_format = pixel_format;
+        self
+    }
+
+    pub fn hardware(mut self, hardware: HardwareEncoder) -> Self {
+        self.output = self.output.hardware(hardware);
+        self
+    }
+
+    pub fn keyframe_interval(mut self, keyframe_interval: u32) -> Self {
+        self.output = self.output.keyframe_interval(keyframe_interval);
+        self
+    }
+
+    pub fn faststart(mut self, faststart: bool) -> Self {
+        self.output = self.output.faststart(faststart);
+        self
+    }
+
+    pub fn max_segment_duration(mut self, max_segment_duration: f64) -> Self {
+        self.output = self.output.max_segment_duration(max_segment_duration);
+        self
+    }
+
+    pub fn max_segment_size(mut self, max_segment_size: u64) -> Self {
+        self.output = self.output.max_segment_size(max_segment_size);
+        self
+    }
+
+    pub fn audio(mut self, audio: EncoderAudioConfig) -> Self {
+        self.audio = Some(audio);
+        self
+    }
+
+    pub fn extra_output(mut self, output: OutputConfig) -> Self {
+        self.extra_outputs.push(output);
+        self
+    }
+
+    pub fn replay(mut self, replay: ReplayConfig) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = Some(queue_size);
+        self
+    }
+
+    pub fn block_when_full(mut self, block_when_full: bool) -> Self {
+        self.block_when_full = block_when_full;
+        self
+    }
+}
+
+impl OutputConfig {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            codec: VideoCodec::default(),
+            container: None,
+            rate_control: None,
+            preset: None,
+            hardware: HardwareEncoder::default(),
+            keyframe_interval: None,
+            faststart: false,
+            max_segment_duration: None,
+            max_segment_size: None,
+        }
+    }
+
+    pub fn is_segmented(&self) -> bool {
+        self.max_segment_duration.is_some() || self.max_segment_size.is_some()
+    }
+
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = Some(rate_control);
+        self
+    }
+
+    pub fn preset(mut self, preset: &str) -> Self {
+        self.preset = Some(preset.to_owned());
+        self
+    }
+
+    pub fn hardware(mut self, hardware: HardwareEncoder) -> Self {
+        self.hardware = hardware;
+        self
+    }
+
+    pub fn keyframe_interval(mut self, keyframe_interval: u32) -> Self {
+        self.keyframe_interval = Some(keyframe_interval);
+        self
+    }
+
+    pub fn faststart(mut self, faststart: bool) -> Self {
+        self.faststart = faststart;
+        self
+    }
+
+    pub fn max_segment_duration(mut self, max_segment_duration: f64) -> Self {
+        self.max_segment_duration = Some(max_segment_duration);
+        self
+    }
+
+    pub fn max_segment_size(mut self, max_segment_size: u64) -> Self {
+        self.max_segment_size = Some(max_segment_size);
+        self
+    }
+}
+
+// Only the video is kept, replays are saved without the audio track
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayConfig {
+    // In seconds, saved replays start at the keyframe before, they can be a little longer
+    pub duration: f64,
+    pub codec: VideoCodec,
+    // Without a container the usual one of the codec is used, see VideoCodec::default_container
+    pub container: Option<Container>,
+    pub rate_control: Option<RateControl>,
+    pub preset: Option<String>,
+    pub hardware: HardwareEncoder,
+    pub keyframe_interval: Option<u32>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            duration: 30.0,
+            // Lossless frames would not fit in memory for long
+            codec: VideoCodec::H264,
+            container: None,
+            rate_control: None,
+            preset: None,
+            hardware: HardwareEncoder::default(),
+            keyframe_interval: None,
+        }
+    }
+}
+
+impl ReplayConfig {
+    pub fn new(duration: f64) -> Self {
+        Self {
+            duration,
+            ..Self::default()
+        }
+    }
+
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn container(mut self, container: Container) -> Self {
+        self.container = Some(container);
+        self
+    }
+
+    pub fn rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = Some(rate_control);
+        self
+    }
+
+    pub fn preset(mut self, preset: &str) -> Self {
+        self.preset = Some(preset.to_owned());
+        self
+    }
+
+    pub fn hardware(mut self, hardware: HardwareEncoder) -> Self {
+        self.hardware = hardware;
+        self
+    }
+
+    pub fn keyframe_interval(mut self, keyframe_interval: u32) -> Self {
+        self.keyframe_interval = Some(keyframe_interval);
+        self
+    }
+
+    // The replay is encoded like an output, without writing it anywhere
+    pub(crate) fn output_config(&self) -> OutputConfig {
+        OutputConfig {
+            codec: self.codec,
+            container: self.container,
+            rate_control: self.rate_control,
+            preset: self.preset.clone(),
+            hardware: self.hardware,
+            keyframe_interval: self.keyframe_interval,
+            ..OutputConfig::new("")
+        }
+    }
+}
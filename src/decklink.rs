@@ -0,0 +1,93 @@
+use anyhow::Result;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::video::quote_launch_value;
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::PlaybackSpeed;
+use crate::video::VideoProvider;
+
+// Captures SDI or HDMI input from a Blackmagic Decklink card
+pub struct DecklinkProvider {
+    video_provider: VideoProvider,
+}
+
+impl DecklinkProvider {
+    // `mode` and `connection` take the values of decklinkvideosrc, e.g. "1080p25" or "auto" and "sdi", "hdmi" or "auto"
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device_number: u32,
+        mode: Option<&str>,
+        connection: Option<&str>,
+        name: String,
+        resolution: impl Into<Option<(usize, usize)>>,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let mut source = format!("decklinkvideosrc device-number={}", device_number);
+        if let Some(mode) = mode {
+            source.push_str(&format!(" mode=\"{}\"", quote_launch_value(mode)));
+        }
+        if let Some(connection) = connection {
+            source.push_str(&format!(" connection=\"{}\"", quote_launch_value(connection)));
+        }
+
+        let video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        Ok(Self { video_provider })
+    }
+}
+
+impl InputProvider for DecklinkProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides().into_iter().take(1).collect()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        self.video_provider.set_property(property, value);
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
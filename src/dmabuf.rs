@@ -0,0 +1,134 @@
+use std::os::unix::io::RawFd;
+
+use anyhow::{Context, Result};
+use log::error;
+
+use gst::prelude::*;
+use gst::State;
+
+use crate::video::{path_to_uri, quote_launch_value};
+
+pub struct DmaBufFrame {
+    pub width: u32,
+    pub height: u32,
+    pub format: gst_video::VideoFormat,
+    pub offsets: Vec<usize>,
+    pub strides: Vec<i32>,
+    pub pts: Option<f64>,
+
+    // The file descriptors stay valid for as long as the buffer holding them is alive
+    fds: Vec<RawFd>,
+    _buffer: gst::Buffer,
+}
+
+impl DmaBufFrame {
+    pub fn fds(&self) -> &[RawFd] {
+        &self.fds
+    }
+}
+
+pub struct DmaBufSource {
+    pipeline: gst::Element,
+    appsink: gst_app::AppSink,
+}
+
+impl DmaBufSource {
+    pub fn new(path: &str, start_playing: bool) -> Result<Self> {
+        crate::init()?;
+
+        let uri = path_to_uri(path)?;
+
+        // Only hardware decoders producing DMA-buf memory can satisfy these caps
+        let pipeline_string = format!(
+            "uridecodebin uri=\"{}\" ! video/x-raw(memory:DMABuf) ! appsink name=appsink max-buffers=2 drop=true sync=true",
+            quote_launch_value(&uri)
+        );
+
+        let pipeline =
+            gst::parse_launch(&pipeline_string).context("Failed to build gstreamer pipeline")?;
+
+        let appsink = pipeline
+            .clone()
+            .dynamic_cast::<gst::Bin>()
+            .expect("Failed to cast the gstreamer pipeline as a gst::Bin element")
+            .get_by_name("appsink")
+            .expect("Failed to retrieve sink from gstreamer pipeline.")
+            .dynamic_cast::<gst_app::AppSink>()
+            .expect("The sink defined in the pipeline is not an appsink");
+
+        if start_playing {
+            pipeline.set_state(State::Playing).context(format!(
+                "Failed to start gstreamer pipeline for video {:?}",
+                uri
+            ))?;
+        }
+
+        Ok(Self { pipeline, appsink })
+    }
+
+    pub fn play(&mut self) -> Result<()> {
+        self.pipeline
+            .set_state(State::Playing)
+            .context("Failed to resume video playback")?;
+
+        Ok(())
+    }
+
+    pub fn pause(&mut self) -> Result<()> {
+        self.pipeline
+            .set_state(State::Paused)
+            .context("Failed to pause video playback")?;
+
+        Ok(())
+    }
+
+    // Returns the latest decoded frame if a new one is available
+    pub fn pull_frame(&mut self) -> Option<DmaBufFrame> {
+        let sample = self.appsink.try_pull_sample(gst::ClockTime::from_seconds(0))?;
+
+        let video_info = gst_video::VideoInfo::from_caps(sample.get_caps()?).ok()?;
+        let buffer = sample.get_buffer_owned()?;
+
+        let mut fds = Vec::with_capacity(buffer.n_memory() as usize);
+        for index in 0..buffer.n_memory() {
+            let memory = buffer.peek_memory(index);
+            let memory_ptr = memory.as_ptr() as *mut gst_sys::GstMemory;
+
+            unsafe {
+                if gst_allocators_sys::gst_is_dmabuf_memory(memory_ptr) == glib_sys::GFALSE {
+                    error!("Decoded frame is not backed by DMA-buf memory");
+                    return None;
+                }
+
+                fds.push(gst_allocators_sys::gst_dmabuf_memory_get_fd(memory_ptr));
+            }
+        }
+
+        let (offsets, strides) = match buffer.get_meta::<gst_video::VideoMeta>() {
+            Some(video_meta) => (video_meta.get_offset().to_vec(), video_meta.get_stride().to_vec()),
+            None => (video_info.offset().to_vec(), video_info.stride().to_vec()),
+        };
+
+        Some(DmaBufFrame {
+            width: video_info.width(),
+            height: video_info.height(),
+            format: video_info.format(),
+            offsets,
+            strides,
+            pts: buffer
+                .get_pts()
+                .nseconds()
+                .map(|pts| pts as f64 / 1_000_000_000.0),
+            fds,
+            _buffer: buffer,
+        })
+    }
+}
+
+impl Drop for DmaBufSource {
+    fn drop(&mut self) {
+        if let Err(e) = self.pipeline.set_state(State::Null) {
+            error!("Failed to stop video playback: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::error;
+
+use gst_app::AppSrc;
+use gst_video::{VideoFrameFlags, VideoMeta};
+
+use crate::encoder::{EncodeError, PixelFormat};
+use crate::pixel::float_rgba_to_argb64;
+use crate::stats::EncodeStats;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FrameLayout {
+    pub format: PixelFormat,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FrameLayout {
+    pub fn row_size(&self) -> usize {
+        self.width * self.format.bytes_per_pixel()
+    }
+
+    // Bytes read from a frame with rows `stride` bytes apart, the last row does not need its padding
+    pub fn frame_size(&self, stride: usize) -> Result<usize, EncodeError> {
+        if stride < self.row_size() {
+            return Err(EncodeError::InvalidFrame(format!(
+                "A stride of {} bytes is shorter than the {} bytes of a row",
+                stride,
+                self.row_size()
+            )));
+        }
+
+        Ok(stride * self.height.saturating_sub(1) + self.row_size())
+    }
+
+    // Padded rows are described by a video meta instead of being packed, gstreamer elements read them as they are
+    fn make_buffer(&self, frame: QueuedFrame) -> gst::Buffer {
+        let mut buffer = match frame.data {
+            FrameData::Buffer(buffer) => buffer,
+            FrameData::Pixels { data, stride } => self.pixels_buffer(data, stride),
+        };
+
+        {
+            let buffer = buffer.make_mut();
+            buffer.set_pts(frame.pts);
+            buffer.set_dts(frame.pts);
+        }
+
+        buffer
+    }
+
+    pub fn pixels_buffer(&self, data: Vec<u8>, stride: usize) -> gst::Buffer {
+        if self.format == PixelFormat::RgbaF32 {
+            let mut converted = Vec::with_capacity(self.width * self.height * 8);
+            float_rgba_to_argb64(&data, self.width, self.height, stride, &mut converted);
+            gst::Buffer::from_mut_slice(converted)
+        } else {
+            let padded = stride != self.row_size();
+            let mut buffer = gst::Buffer::from_mut_slice(data);
+            if padded {
+                VideoMeta::add_full(
+                    buffer.make_mut(),
+                    VideoFrameFlags::NONE,
+                    self.format.video_format(),
+                    self.width as u32,
+                    self.height as u32,
+                    &[0],
+                    &[stride as i32],
+                );
+            }
+            buffer
+        }
+    }
+}
+
+pub(crate) enum FrameData {
+    Pixels { data: Vec<u8>, stride: usize },
+    // Memory the pipeline reads as it is, e.g. wrapped GL textures
+    Buffer(gst::Buffer),
+}
+
+pub(crate) struct QueuedFrame {
+    pub pts: gst::ClockTime,
+    pub data: FrameData,
+}
+
+#[derive(Default)]
+struct Counters {
+    depth: AtomicUsize,
+    dropped: AtomicU64,
+    blocked: AtomicU64,
+    // Set once pushing failed, the worker stops there
+    error: Mutex<Option<String>>,
+}
+
+// Frames are converted and pushed to the appsrc of the encoder from a worker thread, so that the caller does not wait
+// whenever the appsrc blocks because a disk or a network falls behind
+pub(crate) struct EncodeQueue {
+    sender: Option<SyncSender<QueuedFrame>>,
+    worker: Option<JoinHandle<()>>,
+    // Disconnected once the worker returned
+    done: Receiver<()>,
+    counters: Arc<Counters>,
+    block_when_full: bool,
+}
+
+impl EncodeQueue {
+    pub fn new(app_src: AppSrc, layout: FrameLayout, size: usize, block_when_full: bool) -> Self {
+        let (sender, receiver) = sync_channel::<QueuedFrame>(size.max(1));
+        let counters = Arc::new(Counters::default());
+        let (done_sender, done) = channel::<()>();
+
+        let worker = {
+            let counters = counters.clone();
+            std::thread::spawn(move || {
+                let _done = done_sender;
+                for frame in receiver {
+                    let result = app_src.push_buffer(layout.make_buffer(frame));
+                    counters.depth.fetch_sub(1, Ordering::SeqCst);
+
+                    if let Err(e) = result {
+                        if let Ok(mut error) = counters.error.lock() {
+                            error.get_or_insert(format!("Failed to push a frame: {:?}", e));
+                        }
+                        return;
+                    }
+                }
+            })
+        };
+
+        Self {
+            sender: Some(sender),
+            worker: Some(worker),
+            done,
+            counters,
+            block_when_full,
+        }
+    }
+
+    pub fn push(&self, frame: QueuedFrame) -> Result<(), EncodeError> {
+        let sender = self.sender.as_ref().ok_or(EncodeError::Finished)?;
+
+        // Counted beforehand, the worker may take the frame before try_send returns
+        self.counters.depth.fetch_add(1, Ordering::SeqCst);
+        let sent = match sender.try_send(frame) {
+            Ok(()) => true,
+            Err(TrySendError::Full(frame)) if self.block_when_full => {
+                self.counters.blocked.fetch_add(1, Ordering::SeqCst);
+                sender.send(frame).is_ok()
+            }
+            Err(TrySendError::Full(_)) => {
+                self.counters.depth.fetch_sub(1, Ordering::SeqCst);
+                self.counters.dropped.fetch_add(1, Ordering::SeqCst);
+                return Err(EncodeError::QueueFull);
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        };
+
+        if sent {
+            return Ok(());
+        }
+
+        self.counters.depth.fetch_sub(1, Ordering::SeqCst);
+        Err(self
+            .error()
+            .unwrap_or_else(|| EncodeError::Pipeline("The encoding thread stopped".to_owned())))
+    }
+
+    pub fn error(&self) -> Option<EncodeError> {
+        self.counters
+            .error
+            .lock()
+            .ok()
+            .and_then(|error| error.clone())
+            .map(EncodeError::Pipeline)
+    }
+
+    // Waits for the queued frames to be pushed, no frame is taken afterwards
+    pub fn flush(&mut self) {
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            if worker.join().is_err() {
+                error!("The video encoding thread panicked");
+            }
+        }
+    }
+
+    // Same as flush, returns false without waiting any longer once `timeout` elapsed,
+    // the worker can stay blocked on the appsrc for as long as a stalled output holds the pipeline back
+    pub fn drain(&mut self, timeout: Duration) -> bool {
+        self.sender = None;
+        if let Err(RecvTimeoutError::Timeout) = self.done.recv_timeout(timeout) {
+            return false;
+        }
+
+        self.flush();
+        true
+    }
+
+    pub fn stats(&self) -> EncodeStats {
+        EncodeStats {
+            queue_depth: self.counters.depth.load(Ordering::SeqCst),
+            dropped_frames: self.counters.dropped.load(Ordering::SeqCst),
+            blocked_frames: self.counters.blocked.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for EncodeQueue {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -1,6 +1,12 @@
-use std::io::Write;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
 
 use gst::{self, Format, Fraction};
 use gst::{prelude::*, Buffer};
@@ -8,9 +14,648 @@ use gst::{Element, ElementFactory, Pipeline, State};
 use gst_app::{self, AppSrc};
 use gst_video::{self, VideoFormat, VideoInfo};
 
+use crate::config::{AudioSource, EncoderAudioConfig, EncoderConfig, OutputConfig};
+use crate::encode_queue::{EncodeQueue, FrameData, FrameLayout, QueuedFrame};
+use crate::logging::write_pipeline_dot;
+use crate::replay::{Replay, REPLAY_BIN_NAME};
+use crate::stats::EncodeStats;
+use crate::video::{Orientation, ReconnectPolicy};
+
+// Upper bound for the muxer to write its index once the last frame was pushed
+pub(crate) const FINALIZE_TIMEOUT: u64 = 5;
+
+// Frames waiting for the encoders when the config does not say
+const DEFAULT_QUEUE_SIZE: usize = 8;
+
+// Seconds of frames and samples a stream output holds while its connection falls behind, older ones are dropped
+// past that instead of holding the tee back, and with it the recordings and the other streams
+const STREAM_QUEUE_DURATION: u64 = 2;
+
+const GL_MEMORY_FEATURE: &str = "memory:GLMemory";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VideoCodec {
+    // Lossless, files are huge but frames are exact
+    Huffyuv,
+    H264,
+    // Roughly half the size of H.264 for the same quality, at a much higher encoding cost
+    H265,
+    // Royalty free codecs browsers play back in WebM
+    Vp9,
+    Av1,
+    // Intra-only intermediate codecs, meant to be imported in editing software
+    ProRes(ProResProfile),
+    DnxHr(DnxHrProfile),
+    // GPU decoded, the playback format of VJ software like Resolume or VDMX
+    Hap(HapVariant),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HapVariant {
+    Hap,
+    HapAlpha,
+    // Higher quality at twice the size of Hap
+    HapQ,
+}
+
+impl HapVariant {
+    fn property_value(self) -> &'static str {
+        match self {
+            HapVariant::Hap => "hap",
+            HapVariant::HapAlpha => "hap_alpha",
+            HapVariant::HapQ => "hap_q",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProResProfile {
+    Proxy,
+    Lt,
+    Standard,
+    Hq,
+    // 4:4:4, the only profiles keeping the alpha channel
+    P4444,
+    P4444Xq,
+}
+
+impl ProResProfile {
+    fn property_value(self) -> &'static str {
+        match self {
+            ProResProfile::Proxy => "proxy",
+            ProResProfile::Lt => "lt",
+            ProResProfile::Standard => "standard",
+            ProResProfile::Hq => "hq",
+            ProResProfile::P4444 => "4444",
+            ProResProfile::P4444Xq => "4444xq",
+        }
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(self, ProResProfile::P4444 | ProResProfile::P4444Xq)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DnxHrProfile {
+    Lb,
+    Sq,
+    Hq,
+    // 10 bit 4:2:2
+    Hqx,
+    // 10 bit 4:4:4
+    R444,
+}
+
+impl DnxHrProfile {
+    fn property_value(self) -> &'static str {
+        match self {
+            DnxHrProfile::Lb => "dnxhr_lb",
+            DnxHrProfile::Sq => "dnxhr_sq",
+            DnxHrProfile::Hq => "dnxhr_hq",
+            DnxHrProfile::Hqx => "dnxhr_hqx",
+            DnxHrProfile::R444 => "dnxhr_444",
+        }
+    }
+}
+
+impl VideoCodec {
+    pub fn default_container(self) -> Container {
+        match self {
+            VideoCodec::Huffyuv => Container::Matroska,
+            VideoCodec::H264 => Container::Mp4,
+            // Long captures stay readable up to the last written frame if the recording gets interrupted
+            VideoCodec::H265 => Container::Matroska,
+            VideoCodec::Vp9 | VideoCodec::Av1 => Container::WebM,
+            VideoCodec::ProRes(_) | VideoCodec::DnxHr(_) | VideoCodec::Hap(_) => Container::Mov,
+        }
+    }
+
+    pub(crate) fn encoder_element(self) -> &'static str {
+        match self {
+            VideoCodec::Huffyuv => "avenc_huffyuv",
+            VideoCodec::H264 => "x264enc",
+            VideoCodec::H265 => "x265enc",
+            VideoCodec::Vp9 => "vp9enc",
+            VideoCodec::Av1 => "av1enc",
+            VideoCodec::ProRes(_) => "avenc_prores_ks",
+            VideoCodec::DnxHr(_) => "avenc_dnxhd",
+            // Only available when ffmpeg was built with snappy
+            VideoCodec::Hap(_) => "avenc_hap",
+        }
+    }
+
+    pub(crate) fn parser_element(self) -> Option<&'static str> {
+        match self {
+            VideoCodec::Huffyuv => None,
+            VideoCodec::H264 => Some("h264parse"),
+            VideoCodec::H265 => Some("h265parse"),
+            VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::ProRes(_) | VideoCodec::DnxHr(_) | VideoCodec::Hap(_) => None,
+        }
+    }
+
+    // Pixel format the encoder is fed with, None lets it negotiate one with videoconvert
+    fn raw_format(self, alpha: bool) -> Option<&'static str> {
+        match self {
+            // 4:2:0 keeps the stream in a profile every player can decode
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1 => Some("I420"),
+            VideoCodec::ProRes(profile) if profile.has_alpha() && alpha => Some("A444_10LE"),
+            VideoCodec::ProRes(profile) if profile.has_alpha() => Some("Y444_10LE"),
+            VideoCodec::ProRes(_) => Some("I422_10LE"),
+            VideoCodec::DnxHr(DnxHrProfile::R444) => Some("Y444_10LE"),
+            VideoCodec::DnxHr(DnxHrProfile::Hqx) => Some("I422_10LE"),
+            VideoCodec::DnxHr(_) => Some("Y42B"),
+            VideoCodec::Hap(_) => Some("RGBA"),
+            VideoCodec::Huffyuv => None,
+        }
+    }
+}
+
+impl Default for VideoCodec {
+    fn default() -> Self {
+        VideoCodec::Huffyuv
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = anyhow::Error;
+
+    fn from_str(codec: &str) -> Result<Self> {
+        match codec.to_lowercase().as_str() {
+            "huffyuv" => Ok(VideoCodec::Huffyuv),
+            "h264" | "h.264" | "avc" => Ok(VideoCodec::H264),
+            "h265" | "h.265" | "hevc" => Ok(VideoCodec::H265),
+            "vp9" => Ok(VideoCodec::Vp9),
+            "av1" => Ok(VideoCodec::Av1),
+            "prores" => Ok(VideoCodec::ProRes(ProResProfile::Hq)),
+            "prores_proxy" => Ok(VideoCodec::ProRes(ProResProfile::Proxy)),
+            "prores_lt" => Ok(VideoCodec::ProRes(ProResProfile::Lt)),
+            "prores_standard" => Ok(VideoCodec::ProRes(ProResProfile::Standard)),
+            "prores_hq" => Ok(VideoCodec::ProRes(ProResProfile::Hq)),
+            "prores_4444" => Ok(VideoCodec::ProRes(ProResProfile::P4444)),
+            "prores_4444xq" => Ok(VideoCodec::ProRes(ProResProfile::P4444Xq)),
+            "dnxhr" => Ok(VideoCodec::DnxHr(DnxHrProfile::Hq)),
+            "dnxhr_lb" => Ok(VideoCodec::DnxHr(DnxHrProfile::Lb)),
+            "dnxhr_sq" => Ok(VideoCodec::DnxHr(DnxHrProfile::Sq)),
+            "dnxhr_hq" => Ok(VideoCodec::DnxHr(DnxHrProfile::Hq)),
+            "dnxhr_hqx" => Ok(VideoCodec::DnxHr(DnxHrProfile::Hqx)),
+            "dnxhr_444" => Ok(VideoCodec::DnxHr(DnxHrProfile::R444)),
+            "hap" => Ok(VideoCodec::Hap(HapVariant::Hap)),
+            "hap_alpha" => Ok(VideoCodec::Hap(HapVariant::HapAlpha)),
+            "hap_q" => Ok(VideoCodec::Hap(HapVariant::HapQ)),
+            _ => Err(anyhow!("Unknown video codec {:?}", codec)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Container {
+    Matroska,
+    Mp4,
+    // Only holds VP8, VP9 and AV1 video
+    WebM,
+    // QuickTime, what editing software expects ProRes, DNxHR and HAP in
+    Mov,
+    // What RTMP ingest servers expect
+    Flv,
+    // MPEG transport stream, for SRT and UDP contribution to other machines or hardware decoders
+    MpegTs,
+}
+
+impl Container {
+    pub(crate) fn muxer_element(self) -> &'static str {
+        match self {
+            Container::Matroska => "matroskamux",
+            Container::Mp4 => "mp4mux",
+            Container::WebM => "webmmux",
+            Container::Mov => "qtmux",
+            Container::Flv => "flvmux",
+            Container::MpegTs => "mpegtsmux",
+        }
+    }
+
+    // The codecs the sink pads of each muxer accept, the intermediate codecs are QuickTime ones
+    pub fn supports(self, codec: VideoCodec) -> bool {
+        match self {
+            Container::Matroska => matches!(
+                codec,
+                VideoCodec::Huffyuv | VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1 | VideoCodec::ProRes(_)
+            ),
+            Container::Mov => matches!(
+                codec,
+                VideoCodec::H264 | VideoCodec::H265 | VideoCodec::ProRes(_) | VideoCodec::DnxHr(_) | VideoCodec::Hap(_)
+            ),
+            Container::WebM => matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1),
+            Container::Mp4 => matches!(codec, VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1),
+            Container::Flv => codec == VideoCodec::H264,
+            Container::MpegTs => matches!(codec, VideoCodec::H264 | VideoCodec::H265),
+        }
+    }
+
+    pub fn supports_audio(self, codec: AudioCodec) -> bool {
+        match self {
+            Container::WebM => codec == AudioCodec::Opus,
+            Container::Mp4 | Container::Mov | Container::Flv => codec == AudioCodec::Aac,
+            Container::MpegTs => true,
+            Container::Matroska => true,
+        }
+    }
+
+    pub fn default_audio_codec(self) -> AudioCodec {
+        match self {
+            Container::WebM => AudioCodec::Opus,
+            _ => AudioCodec::Aac,
+        }
+    }
+}
+
+impl FromStr for Container {
+    type Err = anyhow::Error;
+
+    fn from_str(container: &str) -> Result<Self> {
+        match container.to_lowercase().as_str() {
+            "matroska" | "mkv" => Ok(Container::Matroska),
+            "mp4" => Ok(Container::Mp4),
+            "webm" => Ok(Container::WebM),
+            "mov" | "quicktime" => Ok(Container::Mov),
+            "flv" => Ok(Container::Flv),
+            "mpegts" | "ts" => Ok(Container::MpegTs),
+            _ => Err(anyhow!("Unknown container {:?}", container)),
+        }
+    }
+}
+
+// Where the encoded stream goes, told apart by the scheme of the output path
+pub(crate) enum Destination {
+    File(String),
+    // rtmp:// and rtmps:// URLs, usually the ingest URL followed by the stream key
+    Rtmp(String),
+    // srt://host:port connects to a listener, srt://:port?mode=listener waits for a caller.
+    // The query also takes the latency in ms and the passphrase, as understood by srtsink
+    Srt(String),
+    // udp://host:port
+    Udp(String, u16),
+    // ndi://name, frames are sent uncompressed to the NDI receivers of the network
+    Ndi(String),
+    // v4l2:///dev/videoN, a v4l2loopback device other applications open as a webcam
+    V4l2(String),
+    // shm:///path/to/socket, readers connect with `shmsrc socket-path=...` and the caps of the frames
+    Shm(String),
+}
+
+impl Destination {
+    pub fn parse(path: &str) -> Result<Self> {
+        let lowercase = path.to_lowercase();
+        if lowercase.starts_with("rtmp://") || lowercase.starts_with("rtmps://") {
+            return Ok(Destination::Rtmp(path.to_owned()));
+        }
+        if lowercase.starts_with("srt://") {
+            return Ok(Destination::Srt(path.to_owned()));
+        }
+        if lowercase.starts_with("ndi://") {
+            return Ok(Destination::Ndi(path["ndi://".len()..].to_owned()));
+        }
+        if lowercase.starts_with("v4l2://") {
+            return Ok(Destination::V4l2(path["v4l2://".len()..].to_owned()));
+        }
+        if lowercase.starts_with("shm://") {
+            return Ok(Destination::Shm(path["shm://".len()..].to_owned()));
+        }
+        // Not recorded to a local file named after the URL when it is mistyped
+        if let Some(address) = lowercase.strip_prefix("udp://") {
+            let (host, port) = address
+                .rsplit_once(':')
+                .context(format!("The UDP destination {:?} does not give a port, e.g. udp://host:5000", path))?;
+            let port = port
+                .trim_end_matches('/')
+                .parse()
+                .map_err(|_| anyhow!("The UDP destination {:?} does not give a valid port", path))?;
+            return Ok(Destination::Udp(host.to_owned(), port));
+        }
+
+        let path = if cfg!(target_os = "windows") {
+            path.replace('\\', "/")
+        } else {
+            path.to_owned()
+        };
+        Ok(Destination::File(path))
+    }
+
+    pub fn is_stream(&self) -> bool {
+        !matches!(self, Destination::File(_))
+    }
+
+    pub fn is_encoded(&self) -> bool {
+        !matches!(self, Destination::Ndi(_) | Destination::V4l2(_) | Destination::Shm(_))
+    }
+
+    // What uncompressed frames are converted to for the destinations taking them
+    fn raw_format(&self, alpha: bool) -> Option<&'static str> {
+        match self {
+            // What NDI transmits natively, the runtime would convert anything else itself
+            Destination::Ndi(_) => Some("UYVY"),
+            // Video call applications accept it from about any webcam
+            Destination::V4l2(_) => Some("YUY2"),
+            // Frames do not carry their caps over shared memory, readers have to expect these
+            Destination::Shm(_) if alpha => Some("RGBA"),
+            Destination::Shm(_) => Some("RGB"),
+            _ => None,
+        }
+    }
+
+    pub fn default_container(&self, codec: VideoCodec) -> Container {
+        match self {
+            Destination::File(_) | Destination::Ndi(_) | Destination::V4l2(_) | Destination::Shm(_) => codec.default_container(),
+            Destination::Rtmp(_) => Container::Flv,
+            Destination::Srt(_) | Destination::Udp(_, _) => Container::MpegTs,
+        }
+    }
+
+    pub fn sink_element(&self) -> &'static str {
+        match self {
+            Destination::File(_) => "filesink",
+            // rtmp2sink supports RTMPS, rtmpsink is the older librtmp based element
+            Destination::Rtmp(_) if ElementFactory::find("rtmp2sink").is_some() => "rtmp2sink",
+            Destination::Rtmp(_) => "rtmpsink",
+            Destination::Srt(_) => "srtsink",
+            Destination::Udp(_, _) => "udpsink",
+            Destination::Ndi(_) => "ndisink",
+            Destination::V4l2(_) => "v4l2sink",
+            Destination::Shm(_) => "shmsink",
+        }
+    }
+
+    fn make_sink(&self) -> Result<Element> {
+        let sink = make_element(self.sink_element())?;
+        match self {
+            Destination::File(path) | Destination::Rtmp(path) => sink.set_property("location", path).unwrap(),
+            Destination::Srt(uri) => sink.set_property("uri", uri).context(format!("Invalid SRT URI {:?}", uri))?,
+            Destination::Udp(host, port) => {
+                sink.set_property("host", host).unwrap();
+                sink.set_property("port", &(*port as i32)).unwrap();
+            }
+            Destination::Ndi(name) => sink.set_property("ndi-name", name).unwrap(),
+            Destination::V4l2(device) => sink.set_property("device", device).unwrap(),
+            // Frames are dropped while no reader is connected
+            Destination::Shm(socket_path) => {
+                sink.set_property("socket-path", socket_path).unwrap();
+                sink.set_property("wait-for-connection", &false).unwrap();
+            }
+        }
+
+        // Frames are sent as soon as they are encoded, timestamps follow the clock of the host
+        if self.is_stream() {
+            sink.set_property("sync", &false).unwrap();
+        }
+
+        Ok(sink)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+}
+
+impl AudioCodec {
+    pub(crate) fn encoder_element(self) -> &'static str {
+        match self {
+            AudioCodec::Aac => "avenc_aac",
+            AudioCodec::Opus => "opusenc",
+        }
+    }
+
+    pub(crate) fn parser_element(self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac => Some("aacparse"),
+            AudioCodec::Opus => None,
+        }
+    }
+}
+
+impl FromStr for AudioCodec {
+    type Err = anyhow::Error;
+
+    fn from_str(codec: &str) -> Result<Self> {
+        match codec.to_lowercase().as_str() {
+            "aac" => Ok(AudioCodec::Aac),
+            "opus" => Ok(AudioCodec::Opus),
+            _ => Err(anyhow!("Unknown audio codec {:?}", codec)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RateControl {
+    // Average bitrate in kbit/s
+    Bitrate(u32),
+    // Constant quality, lower values look better, around 18 to 28 for H.264 and H.265, 15 to 35 for VP9 and AV1
+    Crf(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum HardwareEncoder {
+    Software,
+    // The first backend found working for the codec, software encoding otherwise
+    Auto,
+    Nvenc,
+    Vaapi,
+    QuickSync,
+    VideoToolbox,
+}
+
+impl HardwareEncoder {
+    // Backends tried by Auto, in order of preference
+    fn candidates(self) -> &'static [HardwareEncoder] {
+        match self {
+            HardwareEncoder::Software => &[],
+            HardwareEncoder::Auto if cfg!(target_os = "macos") => &[HardwareEncoder::VideoToolbox],
+            HardwareEncoder::Auto => &[HardwareEncoder::Nvenc, HardwareEncoder::QuickSync, HardwareEncoder::Vaapi],
+            HardwareEncoder::Nvenc => &[HardwareEncoder::Nvenc],
+            HardwareEncoder::Vaapi => &[HardwareEncoder::Vaapi],
+            HardwareEncoder::QuickSync => &[HardwareEncoder::QuickSync],
+            HardwareEncoder::VideoToolbox => &[HardwareEncoder::VideoToolbox],
+        }
+    }
+
+    pub(crate) fn encoder_element(self, codec: VideoCodec) -> Option<&'static str> {
+        match (self, codec) {
+            (HardwareEncoder::Nvenc, VideoCodec::H264) => Some("nvh264enc"),
+            (HardwareEncoder::Nvenc, VideoCodec::H265) => Some("nvh265enc"),
+            (HardwareEncoder::Vaapi, VideoCodec::H264) => Some("vaapih264enc"),
+            (HardwareEncoder::Vaapi, VideoCodec::H265) => Some("vaapih265enc"),
+            (HardwareEncoder::Vaapi, VideoCodec::Vp9) => Some("vaapivp9enc"),
+            (HardwareEncoder::QuickSync, VideoCodec::H264) => Some("msdkh264enc"),
+            (HardwareEncoder::QuickSync, VideoCodec::H265) => Some("msdkh265enc"),
+            (HardwareEncoder::QuickSync, VideoCodec::Vp9) => Some("msdkvp9enc"),
+            (HardwareEncoder::QuickSync, VideoCodec::Av1) => Some("msdkav1enc"),
+            (HardwareEncoder::VideoToolbox, VideoCodec::H264) => Some("vtenc_h264"),
+            (HardwareEncoder::VideoToolbox, VideoCodec::H265) => Some("vtenc_h265"),
+            _ => None,
+        }
+    }
+}
+
+impl Default for HardwareEncoder {
+    fn default() -> Self {
+        HardwareEncoder::Software
+    }
+}
+
+impl FromStr for HardwareEncoder {
+    type Err = anyhow::Error;
+
+    fn from_str(hardware: &str) -> Result<Self> {
+        match hardware.to_lowercase().as_str() {
+            "software" | "none" => Ok(HardwareEncoder::Software),
+            "auto" => Ok(HardwareEncoder::Auto),
+            "nvenc" | "nvidia" => Ok(HardwareEncoder::Nvenc),
+            "vaapi" => Ok(HardwareEncoder::Vaapi),
+            "quicksync" | "qsv" | "msdk" => Ok(HardwareEncoder::QuickSync),
+            "videotoolbox" => Ok(HardwareEncoder::VideoToolbox),
+            _ => Err(anyhow!("Unknown hardware encoder {:?}", hardware)),
+        }
+    }
+}
+
+// Layout of the frames given to encode_frame, rows go bottom-up like the ones read back from the renderer
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PixelFormat {
+    Rgb8,
+    Rgba8,
+    Bgra8,
+    // 32 bits float per channel, e.g. HDR render targets. Values are clipped to 0..1 and kept at 16 bits per channel
+    RgbaF32,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+            PixelFormat::RgbaF32 => 16,
+        }
+    }
+
+    // Codecs supporting it keep the alpha channel
+    pub fn has_alpha(self) -> bool {
+        self != PixelFormat::Rgb8
+    }
+
+    // What the frames are given to gstreamer as, float frames are converted first
+    pub(crate) fn video_format(self) -> VideoFormat {
+        match self {
+            PixelFormat::Rgb8 => VideoFormat::Rgb,
+            PixelFormat::Rgba8 => VideoFormat::Rgba,
+            PixelFormat::Bgra8 => VideoFormat::Bgra,
+            PixelFormat::RgbaF32 => VideoFormat::Argb64,
+        }
+    }
+}
+
+impl Default for PixelFormat {
+    fn default() -> Self {
+        PixelFormat::Rgb8
+    }
+}
+
+impl FromStr for PixelFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format.to_lowercase().as_str() {
+            "rgb" | "rgb8" => Ok(PixelFormat::Rgb8),
+            "rgba" | "rgba8" => Ok(PixelFormat::Rgba8),
+            "bgra" | "bgra8" => Ok(PixelFormat::Bgra8),
+            "rgbaf32" | "float" => Ok(PixelFormat::RgbaF32),
+            _ => Err(anyhow!("Unknown pixel format {:?}", format)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncodeError {
+    // The encoder is not keeping up with the frames given to it, this one was dropped
+    QueueFull,
+    // Encoding stopped, e.g. because the disk is full, the frames given from now on are lost
+    Pipeline(String),
+    // finish or stop was already called
+    Finished,
+    // The frame is smaller than its size, pixel format and stride call for
+    InvalidFrame(String),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::QueueFull => write!(f, "The encoding queue is full, the frame was dropped"),
+            EncodeError::Pipeline(error) => write!(f, "Video encoding failed: {}", error),
+            EncodeError::Finished => write!(f, "The encoder was already finished"),
+            EncodeError::InvalidFrame(error) => write!(f, "Invalid frame: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
 pub struct VideoEncoder {
     pipeline: Pipeline,
     app_src: AppSrc,
+    queue: EncodeQueue,
+    videoflip: Element,
+    // Every output takes its frames and samples from these, they keep flowing while an output is being rebuilt
+    video_tee: Element,
+    audio_tee: Option<Element>,
+    input: BranchInput,
+    layout: FrameLayout,
+
+    audio_config: Option<EncoderAudioConfig>,
+    audio_src: Option<AppSrc>,
+    audio_capture: Option<Element>,
+    audio_channels: usize,
+    audio_sample_rate: u32,
+    // Captured audio is timestamped on the pipeline clock, frames are shifted onto it from the first one on
+    time_offset: Option<f64>,
+
+    outputs: Vec<Output>,
+    reconnect_policy: Option<ReconnectPolicy>,
+    replay: Option<Replay>,
+    // First error of the elements shared by every output
+    error: Option<String>,
+    finished: bool,
+}
+
+// Where the frames given to the encoder live, GL textures are wrapped by GlTextureEncoder
+pub(crate) enum FrameSource {
+    Pixels,
+    // The contexts sharing the GL display and context of the host with the GL elements
+    GlTextures(Vec<gst::Context>),
+}
+
+// What the outputs get from the tee
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BranchInput {
+    pub alpha: bool,
+    pub gl_memory: bool,
+}
+
+// The elements of an output live in their own bin, named after its index, so that a dropped stream can be
+// torn down and rebuilt while the other outputs keep recording
+struct Output {
+    config: OutputConfig,
+    destination: Destination,
+    bin: Option<gst::Bin>,
+    tee_pads: Vec<(Element, gst::Pad)>,
+    // Set from the streaming threads as soon as the output fails, its buffers are dropped at the tees from then on
+    // instead of the error flowing back upstream and stopping every output
+    failed: Arc<AtomicBool>,
+
+    video_encoder: Option<Element>,
+    hardware_encoded: bool,
+
+    reconnect_attempts: u32,
+    next_reconnect: Option<Instant>,
 }
 
 impl VideoEncoder {
@@ -21,108 +666,1162 @@ impl VideoEncoder {
         framerate: f64,
         target_duration: Option<f64>,
     ) -> Result<Self> {
-        gst::init().expect("Failed to initialize the gstreamer library");
-        let path = if cfg!(target_os = "windows") {
-            path.replace('\\', "/")
-        } else {
-            path.to_owned()
-        };
+        let mut config = EncoderConfig::new(path, width, height, framerate);
+        config.target_duration = target_duration;
+
+        Self::from_config(&config)
+    }
+
+    // Streams H.264 to an rtmp:// or rtmps:// ingest URL, reconnecting whenever the connection drops.
+    // An audio track can be added through from_config, most services expect one
+    pub fn new_rtmp(url: &str, width: usize, height: usize, framerate: f64, bitrate: u32) -> Result<Self> {
+        Self::new_live(url, width, height, framerate, bitrate)
+    }
+
+    // Sends H.264 in MPEG-TS over SRT, see Destination::Srt for the caller and listener URIs
+    pub fn new_srt(uri: &str, width: usize, height: usize, framerate: f64, bitrate: u32) -> Result<Self> {
+        Self::new_live(uri, width, height, framerate, bitrate)
+    }
+
+    // Sends H.264 in MPEG-TS as plain UDP datagrams to `host`, multicast groups included
+    pub fn new_udp(host: &str, port: u16, width: usize, height: usize, framerate: f64, bitrate: u32) -> Result<Self> {
+        Self::new_live(&format!("udp://{}:{}", host, port), width, height, framerate, bitrate)
+    }
+
+    fn new_live(url: &str, width: usize, height: usize, framerate: f64, bitrate: u32) -> Result<Self> {
+        // Ingest servers ask for a keyframe every two seconds
+        let config = EncoderConfig::new(url, width, height, framerate)
+            .codec(VideoCodec::H264)
+            .rate_control(RateControl::Bitrate(bitrate))
+            .preset("veryfast")
+            .keyframe_interval((framerate * 2.0).round().max(1.0) as u32);
+
+        let mut encoder = Self::from_config(&config)?;
+        encoder.set_reconnect_policy(Some(ReconnectPolicy::default()));
+
+        Ok(encoder)
+    }
+
+    // Frames are converted once and fanned out to every output of the config, each one encoding them on its own
+    pub fn from_config(config: &EncoderConfig) -> Result<Self> {
+        Self::with_source(config, FrameSource::Pixels)
+    }
+
+    pub(crate) fn with_source(config: &EncoderConfig, source: FrameSource) -> Result<Self> {
+        crate::init()?;
 
         let pipeline = Pipeline::new(None);
 
+        // Wrapped textures are always RGBA, whatever the pixel format of the config says
+        let gl_memory = matches!(source, FrameSource::GlTextures(_));
+        let input = BranchInput {
+            alpha: gl_memory || config.pixel_format.has_alpha(),
+            gl_memory,
+        };
+        // Set on the pipeline first, the bins of the outputs take them from it whenever they are added
+        if let FrameSource::GlTextures(contexts) = &source {
+            for context in contexts {
+                pipeline.set_context(context);
+            }
+        }
+
         let appsrc = ElementFactory::make("appsrc", None).unwrap();
 
-        let videoconvert = ElementFactory::make("videoconvert", None).unwrap();
+        let queue = ElementFactory::make("queue", None).unwrap();
 
-        let videoflip = ElementFactory::make("videoflip", None).unwrap();
-        videoflip.set_property_from_str("method", "vertical-flip");
+        let videoflip = make_element(if input.gl_memory { "glvideoflip" } else { "videoflip" })?;
+        videoflip.set_property_from_str("method", Orientation::default().videoflip_method());
 
-        let queue = ElementFactory::make("queue", None).unwrap();
+        // Outputs waiting to reconnect leave the tee without any pad for a while
+        let video_tee = make_element("tee")?;
+        video_tee.set_property("allow-not-linked", &true).unwrap();
 
-        let enc = ElementFactory::make("avenc_huffyuv", None).unwrap();
-
-        let mux = ElementFactory::make("matroskamux", None).unwrap();
-        let sink = ElementFactory::make("filesink", None).unwrap();
-        sink.set_property("location", &path).unwrap();
-
-        pipeline
-            .add_many(&[
-                &appsrc,
-                &queue,
-                &videoflip,
-                &videoconvert,
-                &enc,
-                &mux,
-                &sink,
-            ])
-            .unwrap();
+        // Each output converts the frames to what its encoder takes, this one is only there for the formats videoflip does not handle
+        let mut elements = vec![appsrc.clone(), queue];
+        if !input.gl_memory {
+            elements.push(make_element("videoconvert")?);
+        }
+        elements.push(videoflip.clone());
+        elements.push(video_tee.clone());
 
-        Element::link_many(&[
-            &appsrc,
-            &queue,
-            &videoflip,
-            &videoconvert,
-            &enc,
-            &mux,
-            &sink,
-        ])
-        .unwrap();
+        let elements: Vec<&Element> = elements.iter().collect();
+        pipeline.add_many(&elements).unwrap();
+        Element::link_many(&elements).context("Failed to link the video input of the encoder")?;
+
+        let (audio_source, audio_tee) = match &config.audio {
+            Some(audio) => {
+                let (source, tee) = add_audio_source(&pipeline, audio)?;
+                (Some(source), Some(tee))
+            }
+            None => (None, None),
+        };
+        let (audio_src, audio_capture) = match audio_source {
+            Some(source) => match source.dynamic_cast::<AppSrc>() {
+                Ok(appsrc) => (Some(appsrc), None),
+                Err(capture) => (None, Some(capture)),
+            },
+            None => (None, None),
+        };
+
+        let mut outputs = Vec::new();
+        for (index, output_config) in config.outputs().enumerate() {
+            let mut output = Output::new(output_config.clone())?;
+            output.build(index, &pipeline, &video_tee, audio_tee.as_ref(), input, config.audio.as_ref())?;
+            outputs.push(output);
+        }
+        let replay = match &config.replay {
+            Some(replay) => Some(Replay::new(&pipeline, &video_tee, replay, input)?),
+            None => None,
+        };
+        watch_output_errors(
+            &pipeline,
+            outputs.iter().map(|output| output.failed.clone()).collect(),
+            replay.as_ref().map(Replay::failed),
+        );
 
         let appsrc = appsrc.dynamic_cast::<AppSrc>().unwrap();
-        let info = VideoInfo::builder(VideoFormat::Rgb, width as u32, height as u32)
-            .fps(Fraction::new((framerate * 1000.0) as i32, 1000))
+        let input_format = if input.gl_memory { VideoFormat::Rgba } else { config.pixel_format.video_format() };
+        let info = VideoInfo::builder(input_format, config.width as u32, config.height as u32)
+            .fps(Fraction::new((config.framerate * 1000.0) as i32, 1000))
             .build()
             .unwrap();
-        appsrc.set_caps(Some(&info.to_caps().unwrap()));
+        let mut caps = info.to_caps().unwrap();
+        if input.gl_memory {
+            let caps = caps.get_mut().unwrap();
+            caps.set_features(0, Some(gst::CapsFeatures::new(&[GL_MEMORY_FEATURE])));
+            caps.get_mut_structure(0).unwrap().set("texture-target", &"2D");
+        }
+        appsrc.set_caps(Some(&caps));
         appsrc.set_property_format(Format::Time);
         appsrc.set_property_block(true);
-        if let Some(target_duration) = target_duration {
+        if let Some(target_duration) = config.target_duration {
             let target_duration = (target_duration * 1_000.0) as u64 * gst::MSECOND;
             appsrc.set_property("duration", &target_duration).unwrap();
         }
 
         pipeline.set_state(State::Playing).context(format!(
             "Failed to start gstreamer encoder for output {:?}",
-            path
+            config.output.path
         ))?;
 
+        let layout = FrameLayout {
+            format: config.pixel_format,
+            width: config.width,
+            height: config.height,
+        };
+        let queue = EncodeQueue::new(
+            appsrc.clone(),
+            layout,
+            config.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE),
+            config.block_when_full,
+        );
+
         Ok(Self {
             pipeline,
             app_src: appsrc,
+            queue,
+            videoflip,
+            video_tee,
+            audio_tee,
+            input,
+            layout,
+            audio_config: config.audio.clone(),
+            audio_src,
+            audio_capture,
+            audio_channels: config.audio.as_ref().map(|audio| audio.channels as usize).unwrap_or(0),
+            audio_sample_rate: config.audio.as_ref().map(|audio| audio.sample_rate).unwrap_or(0),
+            time_offset: None,
+            outputs,
+            reconnect_policy: None,
+            replay,
+            error: None,
+            finished: false,
         })
     }
 
+    pub fn dump_pipeline_dot(&self, path: &str) -> Result<()> {
+        write_pipeline_dot(self.pipeline.upcast_ref(), path)
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.videoflip.set_property_from_str("method", orientation.videoflip_method());
+    }
+
+    // Streams are rebuilt after an error, frames pushed in the meantime only reach the other outputs
+    pub fn set_reconnect_policy(&mut self, reconnect_policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = reconnect_policy;
+        if reconnect_policy.is_none() {
+            for output in self.outputs.iter_mut() {
+                output.next_reconnect = None;
+            }
+        }
+    }
+
+    // In kbit/s, applied while encoding so that a stream can adapt to the available bandwidth.
+    // Only the streamed outputs are changed when there are some, recordings keep their own rate
+    pub fn set_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        let streaming = self
+            .outputs
+            .iter()
+            .any(|output| output.destination.is_stream() && output.destination.is_encoded());
+
+        for output in self.outputs.iter_mut() {
+            if output.destination.is_encoded() && output.destination.is_stream() == streaming {
+                output.set_bitrate(bitrate)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Writes the last seconds kept by the replay of the config, recording goes on meanwhile
+    pub fn save_replay(&self, path: &str) -> Result<()> {
+        match &self.replay {
+            Some(replay) => replay.save(path),
+            None => bail!("The encoder was not configured with a replay"),
+        }
+    }
+
+    // Errors are only looked for between frames. Those of the elements shared by every output stop the encoder for good,
+    // pushing more frames would only fill up the input queue
+    fn check_bus(&mut self) -> Result<(), EncodeError> {
+        let now = Instant::now();
+        for index in 0..self.outputs.len() {
+            if matches!(self.outputs[index].next_reconnect, Some(next_reconnect) if now >= next_reconnect) {
+                self.reconnect(index);
+            }
+        }
+
+        if let Some(bus) = self.pipeline.get_bus() {
+            while let Some(message) = bus.timed_pop_filtered(gst::ClockTime::from_seconds(0), &[gst::MessageType::Error]) {
+                if let gst::MessageView::Error(err) = message.view() {
+                    match output_index(&self.pipeline, &message) {
+                        Some(index) if index < self.outputs.len() => self.output_failed(index, &err.get_error().to_string()),
+                        _ if is_replay_message(&self.pipeline, &message) => self.replay_failed(&err.get_error().to_string()),
+                        _ => {
+                            let description = format!("{} ({})", err.get_error(), err.get_debug().unwrap_or_default());
+                            error!("Video encoding error: {}", description);
+                            self.error.get_or_insert(description);
+                        }
+                    }
+                }
+            }
+        }
+
+        match &self.error {
+            Some(error) => Err(EncodeError::Pipeline(error.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn output_failed(&mut self, index: usize, error: &str) {
+        let output = &mut self.outputs[index];
+        // Elements of a failing output usually post more than one error
+        if output.bin.is_none() {
+            return;
+        }
+        output.teardown(&self.pipeline);
+
+        if output.destination.is_stream() && self.reconnect_policy.is_some() {
+            warn!("Video stream {:?} failed, reconnecting: {}", output.config.path, error);
+            self.schedule_reconnect(index);
+        } else {
+            error!("Video output {:?} stopped: {}", output.config.path, error);
+        }
+    }
+
+    // Recordings and streams keep going without it
+    fn replay_failed(&mut self, error: &str) {
+        if let Some(replay) = self.replay.as_mut().filter(|replay| replay.is_running()) {
+            replay.teardown(&self.pipeline);
+            error!("The replay encoder stopped: {}", error);
+        }
+    }
+
+    fn schedule_reconnect(&mut self, index: usize) {
+        let reconnect_policy = match self.reconnect_policy {
+            Some(reconnect_policy) => reconnect_policy,
+            None => return,
+        };
+
+        let output = &mut self.outputs[index];
+        if let Some(max_attempts) = reconnect_policy.max_attempts {
+            if output.reconnect_attempts >= max_attempts {
+                error!(
+                    "Giving up on video stream {:?} after {} failed attempts",
+                    output.config.path, output.reconnect_attempts
+                );
+                output.next_reconnect = None;
+                return;
+            }
+        }
+
+        let delay = reconnect_policy
+            .initial_delay
+            .checked_mul(2u32.saturating_pow(output.reconnect_attempts))
+            .unwrap_or(reconnect_policy.max_delay)
+            .min(reconnect_policy.max_delay);
+
+        output.reconnect_attempts = output.reconnect_attempts.saturating_add(1);
+        output.next_reconnect = Some(Instant::now() + delay);
+    }
+
+    fn reconnect(&mut self, index: usize) {
+        let output = &mut self.outputs[index];
+        output.next_reconnect = None;
+
+        let result = output.build(
+            index,
+            &self.pipeline,
+            &self.video_tee,
+            self.audio_tee.as_ref(),
+            self.input,
+            self.audio_config.as_ref(),
+        );
+        match result {
+            Ok(()) => output.reconnect_attempts = 0,
+            Err(e) => {
+                error!("Failed to reconnect video stream {:?}: {:?}", output.config.path, e);
+                output.teardown(&self.pipeline);
+                self.schedule_reconnect(index);
+            }
+        }
+    }
+
+    // Ends the recordings and waits for their muxers to write them out before tearing the pipeline down,
+    // frames given afterwards are ignored
+    pub fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            bail!("The encoder was already finished");
+        }
+        self.finished = true;
+
+        // Outputs which failed in the meantime are removed instead of being waited for
+        self.reconnect_policy = None;
+        let result = match self.check_bus() {
+            Ok(()) => {
+                if self.queue.drain(Duration::from_secs(FINALIZE_TIMEOUT)) {
+                    self.end_streams()
+                } else {
+                    Err(anyhow!("The queued frames were not encoded within {} seconds", FINALIZE_TIMEOUT))
+                }
+            }
+            Err(e) => Err(e.into()),
+        };
+        let teardown = self
+            .pipeline
+            .set_state(State::Null)
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to stop video encoding: {:?}", e));
+        // A failed or stalled pipeline leaves the worker blocked on the appsrc until then
+        self.queue.flush();
+
+        result.and(teardown)
+    }
+
+    // Same as finish, errors are logged
     pub fn stop(&mut self) {
-        if let Err(e) = self.pipeline.set_state(State::Null) {
-            eprintln!("Failed to stop video encoding: {:?}", e);
+        if self.finished {
+            return;
+        }
+
+        if let Err(e) = self.finish() {
+            error!("{:?}", e);
+        }
+    }
+
+    // Muxers like mp4mux only write a playable file once the end of stream reached them
+    fn end_streams(&self) -> Result<()> {
+        if let Some(audio_src) = &self.audio_src {
+            // The video track can still be finalized without it
+            if let Err(e) = audio_src.end_of_stream() {
+                error!("Failed to end audio stream: {:?}", e);
+            }
+        }
+        if let Some(audio_capture) = &self.audio_capture {
+            audio_capture.send_event(gst::event::Eos::new());
+        }
+
+        self.app_src
+            .end_of_stream()
+            .map_err(|e| anyhow!("Failed to end stream: {:?}", e))?;
+
+        let bus = self.pipeline.get_bus().context("Failed to find bus for video encoding pipeline")?;
+        let message = bus.timed_pop_filtered(
+            gst::ClockTime::from_seconds(FINALIZE_TIMEOUT),
+            &[gst::MessageType::Eos, gst::MessageType::Error],
+        );
+        match message.as_ref().map(|message| message.view()) {
+            Some(gst::MessageView::Eos(_)) => Ok(()),
+            Some(gst::MessageView::Error(err)) => bail!(
+                "Failed to finalize video encoding: {} ({})",
+                err.get_error(),
+                err.get_debug().unwrap_or_default()
+            ),
+            _ => bail!(
+                "Video encoding did not finish within {} seconds, the end of the recordings may be missing",
+                FINALIZE_TIMEOUT
+            ),
+        }
+    }
+
+    // Frames are given in the pixel format of the config, with tightly packed rows
+    pub fn encode_frame(&mut self, time: f64, frame: &[u8]) -> Result<(), EncodeError> {
+        self.encode_frame_with_stride(time, frame, self.layout.row_size())
+    }
+
+    // `stride` is the number of bytes from the start of a row to the start of the next one, padding included
+    pub fn encode_frame_with_stride(&mut self, time: f64, frame: &[u8], stride: usize) -> Result<(), EncodeError> {
+        if self.input.gl_memory {
+            return Err(EncodeError::InvalidFrame(
+                "This encoder takes GL textures, not pixels".to_owned(),
+            ));
+        }
+
+        let size = self.layout.frame_size(stride)?;
+        if frame.len() < size {
+            return Err(EncodeError::InvalidFrame(format!(
+                "{} bytes were given where {} are needed",
+                frame.len(),
+                size
+            )));
+        }
+
+        self.push_frame(time, FrameData::Pixels {
+            data: frame[..size].to_vec(),
+            stride,
+        })
+    }
+
+    // The buffer has to match the caps of the appsrc, GL memory for the encoders made by GlTextureEncoder
+    pub(crate) fn encode_buffer(&mut self, time: f64, buffer: Buffer) -> Result<(), EncodeError> {
+        self.push_frame(time, FrameData::Buffer(buffer))
+    }
+
+    fn push_frame(&mut self, time: f64, data: FrameData) -> Result<(), EncodeError> {
+        if self.finished {
+            return Err(EncodeError::Finished);
         }
-        if let Err(e) = self.app_src.end_of_stream() {
-            eprintln!("Failed to end stream: {:?}", e);
+        self.check_bus()?;
+        if let Some(error) = self.queue.error() {
+            return Err(error);
         }
+
+        let time = time + self.time_offset(time);
+        self.queue.push(QueuedFrame {
+            pts: (time * 1_000.0) as u64 * gst::MSECOND,
+            data,
+        })
     }
 
-    pub fn encode_frame(&mut self, time: f64, frame: &[u8]) {
-        let pts = (time * 1_000.0) as u64 * gst::MSECOND;
-        let mut buffer = Buffer::with_size(frame.len()).unwrap();
+    pub fn stats(&self) -> EncodeStats {
+        self.queue.stats()
+    }
+
+    fn time_offset(&mut self, time: f64) -> f64 {
+        if self.audio_capture.is_none() {
+            return 0.0;
+        }
+
+        let pipeline = &self.pipeline;
+        *self.time_offset.get_or_insert_with(|| {
+            let running_time = pipeline
+                .get_clock()
+                .map(|clock| clock.get_time())
+                .and_then(|now| (now - pipeline.get_base_time()).nseconds())
+                .unwrap_or(0);
+
+            running_time as f64 / 1_000_000_000.0 - time
+        })
+    }
+
+    // Samples are interleaved, starting at `time` seconds on the same clock as the frames given to encode_frame
+    pub fn push_audio_samples(&mut self, time: f64, samples: &[f32]) -> Result<()> {
+        if self.finished {
+            bail!("The encoder was already finished");
+        }
+
+        let audio_src = match &self.audio_src {
+            Some(audio_src) => audio_src,
+            None if self.audio_capture.is_some() => bail!("The audio track of the encoder is captured from a device"),
+            None => bail!("The encoder was not configured with an audio track"),
+        };
+
+        let frames = samples.len() / self.audio_channels.max(1);
+        let pts = (time * 1_000_000.0) as u64 * gst::USECOND;
+        let duration = gst::ClockTime::from_nseconds(frames as u64 * 1_000_000_000 / self.audio_sample_rate.max(1) as u64);
+
+        let mut buffer = Buffer::with_size(samples.len() * 4).context("Failed to allocate an audio buffer")?;
         {
             let buffer = buffer.get_mut().unwrap();
             buffer.set_pts(pts);
-            buffer.set_dts(pts);
+            buffer.set_duration(duration);
+
+            let mut data = buffer.map_writable().context("Failed to map the audio buffer")?;
+            for (bytes, sample) in data.as_mut_slice().chunks_exact_mut(4).zip(samples) {
+                bytes.copy_from_slice(&sample.to_le_bytes());
+            }
+        }
+
+        audio_src
+            .push_buffer(buffer)
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to push audio samples: {:?}", e))
+    }
+}
+
+impl Output {
+    fn new(config: OutputConfig) -> Result<Self> {
+        Ok(Self {
+            destination: Destination::parse(&config.path)?,
+            config,
+            bin: None,
+            tee_pads: Vec::new(),
+            failed: Arc::new(AtomicBool::new(false)),
+            video_encoder: None,
+            hardware_encoded: false,
+            reconnect_attempts: 0,
+            next_reconnect: None,
+        })
+    }
+
+    // Adds the bin of the output to the pipeline and links it to the tees, it starts along with a running pipeline
+    fn build(
+        &mut self,
+        index: usize,
+        pipeline: &Pipeline,
+        video_tee: &Element,
+        audio_tee: Option<&Element>,
+        input: BranchInput,
+        audio: Option<&EncoderAudioConfig>,
+    ) -> Result<()> {
+        let bin = gst::Bin::new(Some(&format!("output{}", index)));
+
+        let leaky = self.destination.is_stream();
+        let queue = make_output_queue(leaky)?;
+        let mut elements = vec![queue.clone()];
+        let mut mux = None;
+        let mut segment_sink = None;
+
+        if self.destination.is_encoded() {
+            let (encoder_elements, video_encoder, hardware_encoded) =
+                make_video_encoder(&self.config, input, self.destination.is_stream())?;
+            elements.extend(encoder_elements);
+            self.video_encoder = Some(video_encoder);
+            self.hardware_encoded = hardware_encoded;
+
+            let container = self
+                .config
+                .container
+                .unwrap_or_else(|| self.destination.default_container(self.config.codec));
+            if !container.supports(self.config.codec) {
+                bail!("A {:?} container can not hold {:?} video", container, self.config.codec);
+            }
+
+            let muxer = make_element(container.muxer_element())?;
+            if self.config.faststart {
+                if matches!(container, Container::Mp4 | Container::Mov) {
+                    muxer.set_property("faststart", &true).context("Failed to enable faststart on the muxer")?;
+                } else {
+                    warn!("Faststart only applies to MP4 and MOV files, it is ignored for {:?}", container);
+                }
+            }
+            // Players joining a live stream have to find the headers without seeking back to the start
+            if container == Container::Flv {
+                muxer.set_property("streamable", &true).unwrap();
+            }
+            // Seven transport packets fill a UDP datagram
+            if container == Container::MpegTs {
+                muxer.set_property("alignment", &7i32).unwrap();
+            }
+
+            match &self.destination {
+                Destination::File(path) if self.config.is_segmented() => {
+                    let splitmuxsink = make_segment_sink(path, &muxer, &self.config)?;
+                    segment_sink = Some(splitmuxsink.clone());
+                    mux = Some((splitmuxsink, container, Some("audio_%u")));
+                }
+                _ => {
+                    if self.config.is_segmented() {
+                        warn!("Only recordings to files are split in segments, {:?} is not", self.config.path);
+                    }
+                    elements.push(muxer.clone());
+                    elements.push(self.destination.make_sink()?);
+                    mux = Some((muxer, container, None));
+                }
+            }
+        } else if let Some(raw_format) = self.destination.raw_format(input.alpha) {
+            if input.gl_memory {
+                elements.push(make_element("gldownload")?);
+            }
 
-            let mut data = buffer.map_writable().unwrap();
-            let mut data = data.as_mut_slice();
+            let videoconvert = make_element("videoconvert")?;
+            let capsfilter = make_element("capsfilter")?;
+            let caps = gst::Caps::builder("video/x-raw").field("format", &raw_format).build();
+            capsfilter.set_property("caps", &caps).unwrap();
+            elements.push(videoconvert);
+            elements.push(capsfilter);
+            elements.push(self.destination.make_sink()?);
+        }
+
+        let elements: Vec<&Element> = elements.iter().collect();
+        bin.add_many(&elements).unwrap();
+        Element::link_many(&elements).context(format!(
+            "Failed to link the elements of video output {:?}",
+            self.config.path
+        ))?;
+        // The muxer sits inside splitmuxsink, streams are told apart by the pad they are linked to
+        if let Some(segment_sink) = &segment_sink {
+            bin.add(segment_sink).unwrap();
+            elements
+                .last()
+                .unwrap()
+                .link_pads(None, segment_sink, Some("video"))
+                .context(format!("Failed to link the segmented recording {:?}", self.config.path))?;
+        }
+        bin.add_pad(&ghost_pad("video", &queue)?).unwrap();
+
+        let mut tees = vec![(video_tee, "video")];
+        // Audio is only muxed, uncompressed outputs are left without it
+        if let (Some((mux, container, mux_pad)), Some(audio_tee), Some(audio)) = (mux, audio_tee, audio) {
+            let audio_queue = add_audio_encoder(&bin, &mux, mux_pad, container, audio, leaky)?;
+            bin.add_pad(&ghost_pad("audio", &audio_queue)?).unwrap();
+            tees.push((audio_tee, "audio"));
+        }
+
+        pipeline.add(&bin).unwrap();
+        self.bin = Some(bin.clone());
+        self.failed.store(false, Ordering::SeqCst);
+
+        for (tee, pad_name) in tees {
+            let tee_pad = tee.get_request_pad("src_%u").context("Failed to request a pad of the tee")?;
+            self.tee_pads.push((tee.clone(), tee_pad.clone()));
+
+            let failed = self.failed.clone();
+            tee_pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+                if failed.load(Ordering::SeqCst) {
+                    gst::PadProbeReturn::Drop
+                } else {
+                    gst::PadProbeReturn::Ok
+                }
+            });
+
+            tee_pad
+                .link(&bin.get_static_pad(pad_name).unwrap())
+                .map_err(|e| anyhow!("Failed to link video output {:?}: {:?}", self.config.path, e))?;
+        }
+
+        bin.sync_state_with_parent()
+            .context(format!("Failed to start video output {:?}", self.config.path))?;
+
+        Ok(())
+    }
 
-            data.write_all(frame).unwrap();
+    fn teardown(&mut self, pipeline: &Pipeline) {
+        self.failed.store(true, Ordering::SeqCst);
+        for (tee, tee_pad) in self.tee_pads.drain(..) {
+            tee.release_request_pad(&tee_pad);
         }
 
-        if let Err(error) = self.app_src.push_buffer(buffer) {
-            panic!("{:?}", error);
+        if let Some(bin) = self.bin.take() {
+            if let Err(e) = bin.set_state(State::Null) {
+                error!("Failed to stop video output {:?}: {:?}", self.config.path, e);
+            }
+            if let Err(e) = pipeline.remove(&bin) {
+                error!("Failed to remove video output {:?}: {:?}", self.config.path, e);
+            }
         }
+        self.video_encoder = None;
+    }
+
+    // Also kept for the encoder of a rebuilt output
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        match self.config.codec {
+            _ if self.hardware_encoded => (),
+            VideoCodec::H264 | VideoCodec::H265 | VideoCodec::Vp9 | VideoCodec::Av1 => (),
+            codec => bail!("The bitrate of {:?} video can not be changed", codec),
+        }
+        self.config.rate_control = Some(RateControl::Bitrate(bitrate));
+
+        let video_encoder = match &self.video_encoder {
+            Some(video_encoder) => video_encoder,
+            None => return Ok(()),
+        };
+        let result = match self.config.codec {
+            VideoCodec::Vp9 if !self.hardware_encoded => {
+                video_encoder.set_property("target-bitrate", &(bitrate.saturating_mul(1000) as i32))
+            }
+            VideoCodec::Av1 if !self.hardware_encoded => video_encoder.set_property("target-bitrate", &bitrate),
+            _ => video_encoder.set_property("bitrate", &bitrate),
+        };
+
+        result.context("Failed to set the encoder bitrate")
     }
 }
 
+fn ghost_pad(name: &str, element: &Element) -> Result<gst::GhostPad> {
+    let target = element.get_static_pad("sink").unwrap();
+    gst::GhostPad::with_target(Some(name), &target).context("Failed to expose the input of a video output")
+}
+
+// The output an error message comes from, None for the elements shared by every output and for bins already removed
+fn output_index(pipeline: &Pipeline, message: &gst::Message) -> Option<usize> {
+    pipeline_child(pipeline, message)?.get_name().strip_prefix("output")?.parse().ok()
+}
+
+fn is_replay_message(pipeline: &Pipeline, message: &gst::Message) -> bool {
+    pipeline_child(pipeline, message).map_or(false, |child| child.get_name() == REPLAY_BIN_NAME)
+}
+
+// The element of the pipeline holding the source of the message
+fn pipeline_child(pipeline: &Pipeline, message: &gst::Message) -> Option<gst::Object> {
+    let mut object = message.get_src()?;
+    while let Some(parent) = object.get_parent() {
+        if &parent == pipeline.upcast_ref::<gst::Object>() {
+            return Some(object);
+        }
+        object = parent;
+    }
+
+    None
+}
+
+// Errors are posted from the streaming thread of the failing output, which is flagged right away.
+// Messages are still passed on to the bus for check_bus to tear the output down
+fn watch_output_errors(pipeline: &Pipeline, failed: Vec<Arc<AtomicBool>>, replay_failed: Option<Arc<AtomicBool>>) {
+    let bus = pipeline.get_bus().expect("Failed to find bus for video encoding pipeline");
+    let pipeline = pipeline.downgrade();
+
+    bus.set_sync_handler(move |_, message| {
+        if let (gst::MessageView::Error(_), Some(pipeline)) = (message.view(), pipeline.upgrade()) {
+            if let Some(failed) = output_index(&pipeline, message).and_then(|index| failed.get(index)) {
+                failed.store(true, Ordering::SeqCst);
+            } else if is_replay_message(&pipeline, message) {
+                if let Some(replay_failed) = &replay_failed {
+                    replay_failed.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+
+        gst::BusSyncReply::Pass
+    });
+}
+
+fn audio_capture_element(source: &AudioSource) -> Result<Element> {
+    let (device, loopback) = match source {
+        AudioSource::Pushed => bail!("Pushed audio is not captured from a device"),
+        AudioSource::Device(device) => (device.as_deref(), false),
+        AudioSource::Loopback(device) => (device.as_deref(), true),
+    };
+
+    if cfg!(target_os = "windows") {
+        let capture = make_element("wasapisrc")?;
+        capture.set_property("loopback", &loopback).context("Failed to set the audio capture loopback")?;
+        if let Some(device) = device {
+            capture.set_property("device", &device).context("Failed to set the audio capture device")?;
+        }
+        Ok(capture)
+    } else if cfg!(target_os = "linux") {
+        // The monitor of a PulseAudio sink records what is played on it
+        let device = match (device, loopback) {
+            (Some(device), true) if !device.ends_with(".monitor") => Some(format!("{}.monitor", device)),
+            (None, true) => Some("@DEFAULT_MONITOR@".to_owned()),
+            (device, _) => device.map(str::to_owned),
+        };
+
+        let capture = make_element("pulsesrc")?;
+        if let Some(device) = device {
+            capture.set_property("device", &device).context("Failed to set the audio capture device")?;
+        }
+        Ok(capture)
+    } else {
+        if loopback || device.is_some() {
+            bail!("Only the default input device can be recorded on this platform");
+        }
+        make_element("autoaudiosrc")
+    }
+}
+
+// The audio track goes through its own appsrc or is captured from a device, then fanned out to the outputs.
+// Returns the appsrc samples are pushed to, or the capture element, along with the tee
+fn add_audio_source(pipeline: &Pipeline, config: &EncoderAudioConfig) -> Result<(Element, Element)> {
+    let source = match config.source {
+        AudioSource::Pushed => make_element("appsrc")?,
+        _ => audio_capture_element(&config.source)?,
+    };
+    let queue = make_element("queue")?;
+    let audioconvert = make_element("audioconvert")?;
+    let audioresample = make_element("audioresample")?;
+
+    // Devices are captured in whatever format they run at
+    let capsfilter = make_element("capsfilter")?;
+    let caps = gst::Caps::builder("audio/x-raw")
+        .field("rate", &(config.sample_rate as i32))
+        .field("channels", &(config.channels as i32))
+        .build();
+    capsfilter.set_property("caps", &caps).unwrap();
+
+    let tee = make_element("tee")?;
+    tee.set_property("allow-not-linked", &true).unwrap();
+
+    let elements = [&source, &queue, &audioconvert, &audioresample, &capsfilter, &tee];
+    pipeline.add_many(&elements).unwrap();
+    Element::link_many(&elements).context("Failed to link the audio input of the encoder")?;
+
+    if let Ok(appsrc) = source.clone().dynamic_cast::<AppSrc>() {
+        let caps = gst::Caps::builder("audio/x-raw")
+            .field("format", &"F32LE")
+            .field("layout", &"interleaved")
+            .field("rate", &(config.sample_rate as i32))
+            .field("channels", &(config.channels as i32))
+            .build();
+        appsrc.set_caps(Some(&caps));
+        appsrc.set_property_format(Format::Time);
+        appsrc.set_property_block(true);
+    }
+
+    Ok((source, tee))
+}
+
+// Encodes the audio track of one output next to its video, returns the queue the tee links to
+fn add_audio_encoder(
+    bin: &gst::Bin,
+    mux: &Element,
+    mux_pad: Option<&str>,
+    container: Container,
+    config: &EncoderAudioConfig,
+    leaky: bool,
+) -> Result<Element> {
+    let codec = config.codec.unwrap_or_else(|| container.default_audio_codec());
+    if !container.supports_audio(codec) {
+        bail!("A {:?} container can not hold {:?} audio", container, codec);
+    }
+
+    let queue = make_output_queue(leaky)?;
+    let encoder = make_element(codec.encoder_element())?;
+    if let Some(bitrate) = config.bitrate {
+        // Both encoders count in bit/s
+        encoder.set_property_from_str("bitrate", &bitrate.saturating_mul(1000).to_string());
+    }
+
+    let mut elements = vec![queue.clone(), encoder];
+    if let Some(parser) = codec.parser_element() {
+        elements.push(make_element(parser)?);
+    }
+
+    let elements: Vec<&Element> = elements.iter().collect();
+    bin.add_many(&elements).unwrap();
+    Element::link_many(&elements).context("Failed to link the audio encoder")?;
+    elements
+        .last()
+        .unwrap()
+        .link_pads(None, mux, mux_pad)
+        .context(format!("Failed to link a {:?} audio encoder to a {:?} container", codec, container))?;
+
+    Ok(queue)
+}
+
+// The raw format conversion, encoder and parser of an output. The encoder is also returned on its own,
+// along with whether it runs on the hardware
+pub(crate) fn make_video_encoder(config: &OutputConfig, input: BranchInput, streaming: bool) -> Result<(Vec<Element>, Element, bool)> {
+    let (encoder, raw_format, hardware_encoded) = match make_hardware_encoder(config) {
+        Some(encoder) => (encoder, Some("NV12"), true),
+        None => {
+            let encoder = make_element(config.codec.encoder_element())?;
+            configure_encoder(&encoder, config, input.alpha, streaming)?;
+            (encoder, config.codec.raw_format(input.alpha), false)
+        }
+    };
+
+    // Textures are converted on the GPU, then only downloaded for the encoders which can not read GL memory
+    let mut elements = Vec::new();
+    let mut gl_memory = false;
+    if input.gl_memory {
+        elements.push(make_element("glcolorconvert")?);
+        if accepts_gl_memory(&encoder) {
+            gl_memory = true;
+        } else {
+            elements.push(make_element("gldownload")?);
+        }
+    } else {
+        elements.push(make_element("videoconvert")?);
+    }
+
+    if let Some(format) = raw_format {
+        let capsfilter = make_element("capsfilter")?;
+        let mut caps = gst::Caps::builder("video/x-raw").field("format", &format).build();
+        if gl_memory {
+            caps.get_mut()
+                .unwrap()
+                .set_features(0, Some(gst::CapsFeatures::new(&[GL_MEMORY_FEATURE])));
+        }
+        capsfilter.set_property("caps", &caps).unwrap();
+        elements.push(capsfilter);
+    }
+    elements.push(encoder.clone());
+
+    if let Some(parser) = config.codec.parser_element() {
+        elements.push(make_element(parser)?);
+    }
+
+    Ok((elements, encoder, hardware_encoded))
+}
+
+fn accepts_gl_memory(encoder: &Element) -> bool {
+    encoder
+        .get_static_pad("sink")
+        .and_then(|pad| pad.get_pad_template_caps())
+        .map_or(false, |caps| {
+            caps.iter_with_features()
+                .any(|(_, features)| features.contains(GL_MEMORY_FEATURE))
+        })
+}
+
+// Writes numbered files with `muxer`, a new one is started at the first keyframe past either limit
+fn make_segment_sink(path: &str, muxer: &Element, config: &OutputConfig) -> Result<Element> {
+    let splitmuxsink = make_element("splitmuxsink")?;
+    splitmuxsink.set_property("muxer", muxer).context("Failed to set the muxer of the segmented recording")?;
+    splitmuxsink.set_property("location", &segment_location(path)).unwrap();
+
+    if let Some(max_segment_duration) = config.max_segment_duration {
+        let max_size_time = (max_segment_duration * 1_000_000_000.0) as u64;
+        splitmuxsink.set_property("max-size-time", &max_size_time).unwrap();
+        // Segments last as long as asked instead of waiting for the next keyframe the encoder happens to make
+        splitmuxsink.set_property("send-keyframe-requests", &true).unwrap();
+    }
+    if let Some(max_segment_size) = config.max_segment_size {
+        splitmuxsink.set_property("max-size-bytes", &max_segment_size).unwrap();
+    }
+
+    Ok(splitmuxsink)
+}
+
+// "night.mkv" is split into night_00000.mkv, night_00001.mkv and so on, unless it holds a printf pattern of its own
+fn segment_location(path: &str) -> String {
+    if path.contains('%') {
+        return path.to_owned();
+    }
+
+    match path.rfind('.') {
+        Some(dot) if !path[dot..].contains('/') => format!("{}_%05d{}", &path[..dot], &path[dot..]),
+        _ => format!("{}_%05d", path),
+    }
+}
+
+pub(crate) fn make_element(factory: &str) -> Result<Element> {
+    ElementFactory::make(factory, None)
+        .context(format!("Failed to create the gstreamer element {:?}, is its plugin installed?", factory))
+}
+
+fn make_output_queue(leaky: bool) -> Result<Element> {
+    let queue = make_element("queue")?;
+    if leaky {
+        queue.set_property_from_str("leaky", "downstream");
+        queue.set_property("max-size-buffers", &0u32).unwrap();
+        queue.set_property("max-size-bytes", &0u32).unwrap();
+        queue.set_property("max-size-time", &(STREAM_QUEUE_DURATION * 1_000_000_000)).unwrap();
+    }
+
+    Ok(queue)
+}
+
+// Returns None when software encoding has to be used, either by choice or because no backend works on this machine.
+// Hardware encoders are opened once to make sure a device backs them, their factory is also found without one
+fn make_hardware_encoder(config: &OutputConfig) -> Option<Element> {
+    for backend in config.hardware.candidates() {
+        let factory = match backend.encoder_element(config.codec) {
+            Some(factory) => factory,
+            None => continue,
+        };
+
+        let encoder = match ElementFactory::make(factory, None) {
+            Ok(encoder) => encoder,
+            Err(_) => continue,
+        };
+
+        let opened = encoder.set_state(State::Ready).is_ok();
+        let _ = encoder.set_state(State::Null);
+        if !opened {
+            warn!("Hardware encoder {:?} is installed but could not be opened", factory);
+            continue;
+        }
+
+        if let Err(e) = configure_hardware_encoder(&encoder, *backend, config) {
+            warn!("{:?}", e);
+            continue;
+        }
+
+        return Some(encoder);
+    }
+
+    if config.hardware != HardwareEncoder::Software {
+        warn!("No hardware encoder available for {:?}, falling back to software encoding", config.codec);
+    }
+
+    None
+}
+
+// Presets are named after the software encoders and do not apply here
+fn configure_hardware_encoder(encoder: &Element, backend: HardwareEncoder, config: &OutputConfig) -> Result<()> {
+    match config.rate_control {
+        Some(RateControl::Bitrate(bitrate)) => {
+            encoder.set_property("bitrate", &bitrate).context("Failed to set the encoder bitrate")?;
+        }
+        Some(RateControl::Crf(crf)) => match backend {
+            HardwareEncoder::Nvenc => {
+                encoder.set_property_from_str("rc-mode", "constqp");
+                encoder.set_property("qp-const", &(crf as i32)).context("Failed to set the encoder quality")?;
+            }
+            HardwareEncoder::Vaapi => {
+                encoder.set_property_from_str("rate-control", "cqp");
+                encoder.set_property("init-qp", &crf).context("Failed to set the encoder quality")?;
+            }
+            HardwareEncoder::QuickSync => {
+                encoder.set_property_from_str("rate-control", "cqp");
+                encoder.set_property("qpi", &crf).context("Failed to set the encoder quality")?;
+                encoder.set_property("qpp", &crf).context("Failed to set the encoder quality")?;
+            }
+            // Quality goes from 0 to 1, mapped from the 0 to 51 quantizer range of H.264
+            HardwareEncoder::VideoToolbox => {
+                let quality = 1.0 - (crf.min(51) as f64 / 51.0);
+                encoder.set_property("quality", &quality).context("Failed to set the encoder quality")?;
+            }
+            HardwareEncoder::Software | HardwareEncoder::Auto => (),
+        },
+        None => (),
+    }
+
+    if let Some(keyframe_interval) = config.keyframe_interval {
+        let property = match backend {
+            HardwareEncoder::Nvenc | HardwareEncoder::QuickSync => "gop-size",
+            HardwareEncoder::Vaapi => "keyframe-period",
+            HardwareEncoder::VideoToolbox => "max-keyframe-interval",
+            HardwareEncoder::Software | HardwareEncoder::Auto => return Ok(()),
+        };
+        encoder.set_property_from_str(property, &keyframe_interval.to_string());
+    }
+
+    Ok(())
+}
+
+fn configure_encoder(encoder: &Element, config: &OutputConfig, alpha: bool, streaming: bool) -> Result<()> {
+    match config.codec {
+        // Lossless, there is nothing to tune
+        VideoCodec::Huffyuv => (),
+        VideoCodec::H264 => {
+            // Frames go out as soon as they are encoded instead of being held for lookahead
+            if streaming {
+                encoder.set_property_from_str("tune", "zerolatency");
+            }
+
+            match config.rate_control {
+                Some(RateControl::Bitrate(bitrate)) => {
+                    encoder.set_property("bitrate", &bitrate).context("Failed to set the encoder bitrate")?;
+                }
+                Some(RateControl::Crf(crf)) => {
+                    encoder.set_property_from_str("pass", "qual");
+                    encoder.set_property("quantizer", &crf).context("Failed to set the encoder quality")?;
+                }
+                None => (),
+            }
+
+            // One of ultrafast, superfast, veryfast, faster, fast, medium, slow, slower, veryslow
+            if let Some(preset) = &config.preset {
+                encoder.set_property_from_str("speed-preset", preset);
+            }
+        }
+        VideoCodec::H265 => {
+            match config.rate_control {
+                Some(RateControl::Bitrate(bitrate)) => {
+                    encoder.set_property("bitrate", &bitrate).context("Failed to set the encoder bitrate")?;
+                }
+                // x265enc has no property of its own for constant quality
+                Some(RateControl::Crf(crf)) => {
+                    encoder
+                        .set_property("option-string", &format!("crf={}", crf))
+                        .context("Failed to set the encoder quality")?;
+                }
+                None => (),
+            }
+
+            // Same preset names as x264enc
+            if let Some(preset) = &config.preset {
+                encoder.set_property_from_str("speed-preset", preset);
+            }
+        }
+        VideoCodec::Vp9 | VideoCodec::Av1 => {
+            match config.rate_control {
+                Some(RateControl::Bitrate(bitrate)) => {
+                    // vp9enc counts in bit/s where av1enc counts in kbit/s
+                    let result = if config.codec == VideoCodec::Vp9 {
+                        encoder.set_property("target-bitrate", &(bitrate.saturating_mul(1000) as i32))
+                    } else {
+                        encoder.set_property("target-bitrate", &bitrate)
+                    };
+                    result.context("Failed to set the encoder bitrate")?;
+                }
+                Some(RateControl::Crf(crf)) => {
+                    // Not every AV1 encoder has these, e.g. rav1enc and svtav1enc
+                    let cq_level = match (encoder.find_property("end-usage"), encoder.find_property("cq-level")) {
+                        (Some(_), Some(cq_level)) => cq_level,
+                        _ => bail!("The {:?} encoder does not support a constant quality", config.codec),
+                    };
+                    encoder.set_property_from_str("end-usage", "q");
+                    let result = match cq_level.get_value_type() {
+                        glib::Type::I32 => encoder.set_property("cq-level", &(crf as i32)),
+                        glib::Type::U32 => encoder.set_property("cq-level", &crf),
+                        value_type => bail!("The quality level of the {:?} encoder is a {}", config.codec, value_type),
+                    };
+                    result.context("Failed to set the encoder quality")?;
+                }
+                None => (),
+            }
+
+            // These encoders have no named presets, the preset is their cpu-used speed level, higher is faster
+            if let Some(preset) = &config.preset {
+                encoder.set_property_from_str("cpu-used", preset);
+            }
+        }
+        // The profile sets the bitrate of these codecs, rate control and presets do not apply
+        VideoCodec::ProRes(profile) => {
+            encoder.set_property_from_str("profile", profile.property_value());
+            if profile.has_alpha() && alpha {
+                encoder.set_property_from_str("alpha-bits", "16");
+            }
+        }
+        VideoCodec::DnxHr(profile) => {
+            encoder.set_property_from_str("profile", profile.property_value());
+        }
+        VideoCodec::Hap(variant) => {
+            encoder.set_property_from_str("format", variant.property_value());
+        }
+    }
+
+    // Intra-only codecs only produce keyframes
+    let keyframe_property = match config.codec {
+        VideoCodec::H264 | VideoCodec::H265 => Some("key-int-max"),
+        VideoCodec::Vp9 | VideoCodec::Av1 => Some("keyframe-max-dist"),
+        _ => None,
+    };
+    if let (Some(property), Some(keyframe_interval)) = (keyframe_property, config.keyframe_interval) {
+        encoder.set_property_from_str(property, &keyframe_interval.to_string());
+    }
+
+    Ok(())
+}
+
 impl Drop for VideoEncoder {
+    // Does not wait for the muxers, recordings are only finalized by finish or stop
     fn drop(&mut self) {
-        self.stop();
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+
+        warn!("Video encoder dropped without being finished, the end of the recordings may be missing");
+        if let Err(e) = self.pipeline.set_state(State::Null) {
+            error!("Failed to stop video encoding: {:?}", e);
+        }
+        self.queue.flush();
     }
 }
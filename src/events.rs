@@ -0,0 +1,34 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProviderEvent {
+    Prerolled,
+    Buffering(i32),
+    // Playback reached the end of the clip and stopped there
+    Eos,
+    // Playback reached the end of the clip and started over
+    Looped,
+    Error(String),
+    // The pipeline stopped delivering frames while playing, see VideoProvider::set_stall_timeout
+    Stalled,
+    StreamStarted,
+}
+
+#[derive(Default)]
+pub(crate) struct EventSenders {
+    senders: Vec<Sender<ProviderEvent>>,
+}
+
+impl EventSenders {
+    pub fn subscribe(&mut self) -> Receiver<ProviderEvent> {
+        let (sender, receiver) = channel();
+        self.senders.push(sender);
+
+        receiver
+    }
+
+    // Receivers that were dropped are forgotten on the next event
+    pub fn emit(&mut self, event: ProviderEvent) {
+        self.senders.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
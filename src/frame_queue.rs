@@ -0,0 +1,233 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::stats::DecodeStats;
+
+// Allocations kept around for the streaming thread to decode into
+const SPARE_BUFFERS: usize = 2;
+
+// Upper bound between two checks of the release condition while waiting for the renderer
+const CONSUME_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+// Weight of the newest measure in the running averages of the statistics
+const STATS_SMOOTHING: f64 = 0.1;
+
+#[derive(Clone)]
+pub(crate) struct Frame {
+    pub dimensions: Vec<usize>,
+    pub data: Vec<u8>,
+    // Presentation timestamp in seconds of stream time
+    pub pts: Option<f64>,
+    pub index: u64,
+    // Frames left in GPU memory by providers decoding into GL textures, `data` is empty then
+    pub memory: Option<gst::Buffer>,
+}
+
+struct Slot {
+    frame: Option<Frame>,
+    consumed: bool,
+    pushed_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct Timing {
+    last_push: Option<Instant>,
+    // Running averages in seconds
+    frame_interval: Option<f64>,
+    latency: Option<f64>,
+}
+
+fn smooth(average: Option<f64>, value: f64) -> Option<f64> {
+    Some(match average {
+        Some(average) => average + (value - average) * STATS_SMOOTHING,
+        None => value,
+    })
+}
+
+// Triple buffering between the streaming thread and the render thread: the decoder fills a
+// spare buffer without holding any lock, then swaps it in as the latest frame, while the
+// renderer takes the latest complete frame, both locks only being held for a pointer swap
+pub(crate) struct FrameQueue {
+    latest: Mutex<Slot>,
+    consumed_signal: Condvar,
+    spare: Mutex<Vec<Vec<u8>>>,
+    hold_until_consumed: AtomicBool,
+    // Set while the streaming thread waits for the renderer to take the latest frame
+    waiting_consumer: AtomicBool,
+    pushed: AtomicU64,
+    // Frames replaced by a newer one before the render thread got them
+    overwritten: AtomicU64,
+    timing: Mutex<Timing>,
+}
+
+impl FrameQueue {
+    pub fn new() -> Self {
+        Self {
+            latest: Mutex::new(Slot {
+                frame: None,
+                consumed: false,
+                pushed_at: None,
+            }),
+            consumed_signal: Condvar::new(),
+            spare: Mutex::new(Vec::with_capacity(SPARE_BUFFERS)),
+            hold_until_consumed: AtomicBool::new(false),
+            waiting_consumer: AtomicBool::new(false),
+            pushed: AtomicU64::new(0),
+            overwritten: AtomicU64::new(0),
+            timing: Mutex::new(Timing::default()),
+        }
+    }
+
+    pub fn spare_buffer(&self) -> Vec<u8> {
+        self.spare
+            .lock()
+            .ok()
+            .and_then(|mut spare| spare.pop())
+            .unwrap_or_default()
+    }
+
+    pub fn set_hold_until_consumed(&self, hold_until_consumed: bool) {
+        self.hold_until_consumed.store(hold_until_consumed, Ordering::SeqCst);
+        self.consumed_signal.notify_all();
+    }
+
+    pub fn holds_until_consumed(&self) -> bool {
+        self.hold_until_consumed.load(Ordering::SeqCst)
+    }
+
+    // Blocks the streaming thread until the renderer got the latest frame, or until `release` holds.
+    // Returns false if the render thread most likely crashed.
+    pub fn wait_consumed<F: Fn() -> bool>(&self, release: F) -> bool {
+        let mut slot = match self.latest.lock() {
+            Ok(slot) => slot,
+            Err(_) => return false,
+        };
+
+        loop {
+            if slot.frame.is_none() || slot.consumed || !self.holds_until_consumed() || release() {
+                self.waiting_consumer.store(false, Ordering::Relaxed);
+                return true;
+            }
+
+            self.waiting_consumer.store(true, Ordering::Relaxed);
+            slot = match self.consumed_signal.wait_timeout(slot, CONSUME_POLL_INTERVAL) {
+                Ok((slot, _)) => slot,
+                Err(_) => return false,
+            };
+        }
+    }
+
+    pub fn is_waiting_consumer(&self) -> bool {
+        self.waiting_consumer.load(Ordering::Relaxed)
+    }
+
+    // Returns whether a frame that was never consumed got replaced, or None if the render thread most likely crashed
+    pub fn push(&self, frame: Frame) -> Option<bool> {
+        let (replaced, overwritten) = {
+            let mut slot = self.latest.lock().ok()?;
+            let overwritten = slot.frame.is_some() && !slot.consumed;
+            slot.consumed = false;
+            slot.pushed_at = Some(Instant::now());
+
+            (slot.frame.replace(frame), overwritten)
+        };
+        self.pushed.fetch_add(1, Ordering::Relaxed);
+
+        if let Ok(mut timing) = self.timing.lock() {
+            let now = Instant::now();
+            if let Some(last_push) = timing.last_push {
+                timing.frame_interval = smooth(timing.frame_interval, (now - last_push).as_secs_f64());
+            }
+            timing.last_push = Some(now);
+        }
+
+        if overwritten {
+            self.overwritten.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(replaced) = replaced {
+            self.recycle(replaced.data);
+        }
+
+        Some(overwritten)
+    }
+
+    pub fn take(&self) -> Option<Frame> {
+        let frame = {
+            let mut slot = self.latest.lock().ok()?;
+            if !slot.consumed && slot.frame.is_some() {
+                self.record_latency(slot.pushed_at);
+            }
+            slot.frame.take()
+        };
+        self.consumed_signal.notify_all();
+
+        frame
+    }
+
+    pub fn peek(&self) -> Option<Frame> {
+        let frame = {
+            let mut slot = self.latest.lock().ok()?;
+            if !slot.consumed && slot.frame.is_some() {
+                self.record_latency(slot.pushed_at);
+            }
+            slot.consumed = true;
+            slot.frame.clone()
+        };
+        self.consumed_signal.notify_all();
+
+        frame
+    }
+
+    pub fn pushed_count(&self) -> u64 {
+        self.pushed.load(Ordering::Relaxed)
+    }
+
+    pub fn overwritten_count(&self) -> u64 {
+        self.overwritten.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        let queue_depth = self
+            .latest
+            .lock()
+            .map(|slot| (slot.frame.is_some() && !slot.consumed) as usize)
+            .unwrap_or(0);
+
+        let (decode_fps, average_latency) = match self.timing.lock() {
+            Ok(timing) => {
+                // A stalled decoder lowers the rate instead of keeping the last measure
+                let interval = match (timing.frame_interval, timing.last_push) {
+                    (Some(interval), Some(last_push)) => Some(interval.max(last_push.elapsed().as_secs_f64())),
+                    _ => None,
+                };
+
+                (interval.filter(|interval| *interval > 0.0).map(|interval| 1.0 / interval), timing.latency)
+            }
+            Err(_) => (None, None),
+        };
+
+        DecodeStats {
+            decode_fps: decode_fps.unwrap_or(0.0),
+            average_latency: average_latency.unwrap_or(0.0),
+            decoded_frames: self.pushed_count(),
+            dropped_frames: self.overwritten_count(),
+            queue_depth,
+        }
+    }
+
+    // Time spent by a frame between its decoding and its first delivery to the renderer
+    fn record_latency(&self, pushed_at: Option<Instant>) {
+        if let (Some(pushed_at), Ok(mut timing)) = (pushed_at, self.timing.lock()) {
+            timing.latency = smooth(timing.latency, pushed_at.elapsed().as_secs_f64());
+        }
+    }
+
+    fn recycle(&self, buffer: Vec<u8>) {
+        if let Ok(mut spare) = self.spare.lock() {
+            if spare.len() < SPARE_BUFFERS {
+                spare.push(buffer);
+            }
+        }
+    }
+}
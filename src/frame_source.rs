@@ -0,0 +1,289 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use log::{error, log, Level};
+
+use gst::prelude::*;
+use gst::FlowError;
+
+use crate::frame_queue::{Frame, FrameQueue};
+use crate::pixel::{convert_frame, pack_rows};
+use crate::sync::FrameSync;
+use crate::video::{OutputFormat, PlaybackSpeed, TextureFormat};
+
+// The plumbing shared by every gstreamer based provider: decoded samples are gated by the clock given to
+// set_beat / set_time, converted to textures and handed over to the render thread, errors are recorded from the bus
+pub(crate) struct GstFrameSource {
+    pub pipeline: gst::Element,
+    pub frame_queue: Arc<FrameQueue>,
+    pub sync: Arc<FrameSync>,
+    pub last_error: Arc<Mutex<Option<String>>>,
+}
+
+impl GstFrameSource {
+    // Frames are taken from the appsink named `appsink_name`, the pipeline is left in the Null state
+    pub fn new(
+        pipeline_string: &str,
+        appsink_name: &str,
+        speed: PlaybackSpeed,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+        output_format: OutputFormat,
+    ) -> Result<Self> {
+        crate::init()?;
+
+        let frame_queue = Arc::new(FrameQueue::new());
+        let sync = Arc::new(FrameSync::new(speed, start_beat, start_time, start_playing));
+
+        let pipeline =
+            gst::parse_launch(pipeline_string).context("Failed to build gstreamer pipeline")?;
+
+        let appsink = pipeline
+            .clone()
+            .dynamic_cast::<gst::Bin>()
+            .expect("Failed to cast the gstreamer pipeline as a gst::Bin element")
+            .get_by_name(appsink_name)
+            .context(format!("No element named {:?} in the gstreamer pipeline", appsink_name))?
+            .dynamic_cast::<gst_app::AppSink>()
+            .map_err(|_| anyhow!("The element named {:?} in the gstreamer pipeline is not an appsink", appsink_name))?;
+
+        {
+            let sync = sync.clone();
+            let frame_queue = frame_queue.clone();
+
+            let preroll_sync = sync.clone();
+            let preroll_frame_queue = frame_queue.clone();
+
+            appsink.set_callbacks(
+                gst_app::AppSinkCallbacks::builder()
+                    .new_sample(move |appsink| {
+                        if !sync.wait_for_frame() {
+                            // The main thread most likely crashed
+                            return Err(gst::FlowError::Eos);
+                        }
+
+                        if frame_queue.holds_until_consumed() && !frame_queue.wait_consumed(|| sync.is_released()) {
+                            return Err(gst::FlowError::Eos);
+                        }
+
+                        let sample = match appsink.pull_sample() {
+                            Err(e) => {
+                                error!("{:}", e);
+                                return Err(gst::FlowError::Eos);
+                            }
+                            Ok(sample) => sample,
+                        };
+
+                        // Frames decoded while frozen are dropped, the last delivered one stays on screen
+                        if sync.is_frozen() {
+                            return Ok(gst::FlowSuccess::Ok);
+                        }
+
+                        store_sample(&sample, &frame_queue, output_format)
+                    })
+                    .new_preroll(move |appsink| {
+                        if !preroll_sync.shows_preroll() {
+                            return Ok(gst::FlowSuccess::Ok);
+                        }
+
+                        let sample = match appsink.pull_preroll() {
+                            Err(e) => {
+                                error!("{:}", e);
+                                return Err(gst::FlowError::Eos);
+                            }
+                            Ok(sample) => sample,
+                        };
+
+                        store_sample(&sample, &preroll_frame_queue, output_format)
+                    })
+                    .build(),
+            );
+        }
+
+        let last_error = Arc::new(Mutex::new(None));
+        watch_bus_errors(&pipeline, last_error.clone());
+
+        Ok(Self {
+            pipeline,
+            frame_queue,
+            sync,
+            last_error,
+        })
+    }
+}
+
+// Errors and warnings are recorded from the thread posting them, so that they are known even when the bus is not being drained.
+// Messages are still passed on to the bus for check_loop to handle.
+fn watch_bus_errors(pipeline: &gst::Element, last_error: Arc<Mutex<Option<String>>>) {
+    let bus = pipeline
+        .get_bus()
+        .expect("Failed to find bus for video playback pipeline");
+
+    bus.set_sync_handler(move |_, message| {
+        let description = match message.view() {
+            gst::MessageView::Error(err) => Some((Level::Error, format!(
+                "Error from {}: {} ({})",
+                err.get_src().map(|src| src.get_path_string().to_string()).unwrap_or_default(),
+                err.get_error(),
+                err.get_debug().unwrap_or_default(),
+            ))),
+            gst::MessageView::Warning(warning) => Some((Level::Warn, format!(
+                "Warning from {}: {} ({})",
+                warning.get_src().map(|src| src.get_path_string().to_string()).unwrap_or_default(),
+                warning.get_error(),
+                warning.get_debug().unwrap_or_default(),
+            ))),
+            _ => None,
+        };
+
+        if let Some((level, description)) = description {
+            log!(level, "{}", description);
+            if let Ok(mut last_error) = last_error.lock() {
+                *last_error = Some(description);
+            }
+        }
+
+        gst::BusSyncReply::Pass
+    });
+}
+
+// Stacks the planes of a YUV frame into a single luma-wide texture of 1.5 times the frame height:
+// the luma plane comes first, followed by either the U and V planes (I420) or the interleaved UV plane (NV12)
+fn pack_planes(video_info: &gst_video::VideoInfo, data: &[u8], output_format: OutputFormat, output: &mut Vec<u8>) {
+    let (width, height) = planar_size(video_info);
+
+    let planes = match output_format {
+        OutputFormat::Nv12 => vec![(width, height), (width, height / 2)],
+        _ => vec![(width, height), (width / 2, height / 2), (width / 2, height / 2)],
+    };
+
+    output.clear();
+    for (plane, (row_size, rows)) in planes.into_iter().enumerate() {
+        let offset = video_info.offset()[plane];
+        let stride = video_info.stride()[plane] as usize;
+
+        pack_rows(&data[offset..], row_size, rows, stride, output);
+    }
+}
+
+// The last column and row of odd sized frames are left out, the chroma planes then fit the luma-wide texture exactly
+fn planar_size(video_info: &gst_video::VideoInfo) -> (usize, usize) {
+    (video_info.width() as usize & !1, video_info.height() as usize & !1)
+}
+
+fn store_sample(sample: &gst::Sample, frame_queue: &FrameQueue, output_format: OutputFormat) -> Result<gst::FlowSuccess, gst::FlowError> {
+    let sample_caps = if let Some(sample_caps) = sample.get_caps() {
+        sample_caps
+    } else {
+        return Err(gst::FlowError::Error);
+    };
+
+    let video_info = if let Ok(video_info) = gst_video::VideoInfo::from_caps(sample_caps) {
+        video_info
+    } else {
+        return Err(gst::FlowError::Error);
+    };
+
+    let buffer = if let Some(buffer) = sample.get_buffer() {
+        buffer
+    } else {
+        return Err(gst::FlowError::Error);
+    };
+
+    let pts = stream_time(sample, buffer.get_pts());
+
+    // The source frame number follows from the timestamp, frames of variable rate streams are numbered as they are decoded
+    let fps = video_info.fps();
+    let index = match pts {
+        Some(pts) if *fps.numer() > 0 && *fps.denom() > 0 => (pts * *fps.numer() as f64 / *fps.denom() as f64).round() as u64,
+        _ => frame_queue.pushed_count(),
+    };
+
+    // Mapping GL memory would download it, the texture is handed over instead
+    if sample_caps.get_features(0).map_or(false, |features| features.contains("memory:GLMemory")) {
+        return store_frame(frame_queue, Frame {
+            dimensions: vec![video_info.width() as usize, video_info.height() as usize, 4],
+            data: Vec::new(),
+            pts,
+            index,
+            memory: Some(buffer.to_owned()),
+        });
+    }
+
+    let map = if let Ok(map) = buffer.map_readable() {
+        map
+    } else {
+        return Err(gst::FlowError::Error);
+    };
+
+    // Frames are decoded into a recycled allocation whenever one is available
+    let mut data = frame_queue.spare_buffer();
+
+    if output_format.is_planar() {
+        let (width, height) = planar_size(&video_info);
+        pack_planes(&video_info, map.as_slice(), output_format, &mut data);
+
+        return store_frame(frame_queue, Frame {
+            dimensions: vec![width, height + height / 2, 1],
+            data,
+            pts,
+            index,
+            memory: None,
+        });
+    }
+
+    let format = match video_info.format() {
+        gst_video::VideoFormat::Rgb => TextureFormat::RGBU8,
+        gst_video::VideoFormat::Rgba => TextureFormat::RGBAU8,
+        gst_video::VideoFormat::Bgr => TextureFormat::BGRU8,
+        gst_video::VideoFormat::Bgra => TextureFormat::BGRAU8,
+        gst_video::VideoFormat::Gray8 => TextureFormat::GRAYU8,
+        gst_video::VideoFormat::Gray16Le => TextureFormat::GRAYU16,
+        gst_video::VideoFormat::Argb64 => TextureFormat::ARGBU16,
+        unsupported_format => {
+            error!("Unsupported gstreamer format '{:?}'", unsupported_format);
+            return Err(gst::FlowError::Error);
+        }
+    };
+
+    convert_frame(
+        map.as_slice(),
+        video_info.width() as usize,
+        video_info.height() as usize,
+        video_info.stride()[0] as usize,
+        &format,
+        output_format,
+        &mut data,
+    );
+
+    store_frame(frame_queue, Frame {
+        dimensions: vec![video_info.width() as usize, video_info.height() as usize, output_format.channels()],
+        data,
+        pts,
+        index,
+        memory: None,
+    })
+}
+
+// Converts a buffer timestamp to the stream time reported by position queries
+pub(crate) fn stream_time(sample: &gst::Sample, time: gst::ClockTime) -> Option<f64> {
+    let time = match sample
+        .get_segment()
+        .and_then(|segment| segment.downcast_ref::<gst::ClockTime>())
+    {
+        Some(segment) => segment.to_stream_time(time),
+        None => time,
+    };
+
+    time.nseconds().map(|time| time as f64 / 1_000_000_000.0)
+}
+
+fn store_frame(frame_queue: &FrameQueue, frame: Frame) -> Result<gst::FlowSuccess, gst::FlowError> {
+    if frame_queue.push(frame).is_none() {
+        error!("Could not lock video frame queue, did the main thread panic?");
+        return Err(FlowError::Error);
+    }
+
+    Ok(gst::FlowSuccess::Ok)
+}
@@ -0,0 +1,69 @@
+use anyhow::{bail, Result};
+use log::error;
+
+use glib::translate::ToGlibPtr;
+
+use gst_gl::GLContext;
+
+use crate::frame_queue::Frame;
+
+const GL_ELEMENTS: &[&str] = &["glupload", "glcolorconvert", "glvideoflip"];
+
+// Ends the pipelines of providers decoding into GL textures, in place of the conversion to system memory.
+// Textures are flipped to be bottom-up like the ones of the other providers
+pub(crate) const GL_SINK: &str = "glupload ! glcolorconvert ! glvideoflip name=flip method=vertical-flip ! video/x-raw(memory:GLMemory),format=RGBA,texture-target=2D";
+
+pub struct GlTextureFrame {
+    pub width: u32,
+    pub height: u32,
+    // An RGBA GL_TEXTURE_2D in a context shared with the one of the host
+    pub texture: u32,
+    pub pts: Option<f64>,
+
+    // The texture stays valid for as long as the buffer holding it is alive
+    _buffer: gst::Buffer,
+}
+
+pub(crate) fn check_gl_elements() -> Result<()> {
+    for element in GL_ELEMENTS {
+        if gst::ElementFactory::find(element).is_none() {
+            bail!("Decoding to GL textures needs the gstreamer plugin providing the {:?} element", element);
+        }
+    }
+
+    Ok(())
+}
+
+// The GL commands producing the texture are waited for on `context`, which has to be current
+pub(crate) fn texture_frame(frame: Frame, context: &GLContext) -> Option<GlTextureFrame> {
+    let buffer = frame.memory?;
+
+    let memory = buffer.peek_memory(0);
+    let memory_ptr = memory.as_ptr() as *mut gst_sys::GstMemory;
+
+    let texture = unsafe {
+        if gst_gl_sys::gst_is_gl_memory(memory_ptr) == glib_sys::GFALSE {
+            error!("Decoded frame is not backed by GL memory");
+            return None;
+        }
+
+        let sync_meta = gst_sys::gst_buffer_get_meta(
+            buffer.as_ptr() as *mut gst_sys::GstBuffer,
+            gst_gl_sys::gst_gl_sync_meta_api_get_type(),
+        );
+        if !sync_meta.is_null() {
+            let context: *mut gst_gl_sys::GstGLContext = context.to_glib_none().0;
+            gst_gl_sys::gst_gl_sync_meta_wait(sync_meta as *mut gst_gl_sys::GstGLSyncMeta, context);
+        }
+
+        gst_gl_sys::gst_gl_memory_get_texture_id(memory_ptr as *mut gst_gl_sys::GstGLMemory)
+    };
+
+    Some(GlTextureFrame {
+        width: frame.dimensions[0] as u32,
+        height: frame.dimensions[1] as u32,
+        texture,
+        pts: frame.pts,
+        _buffer: buffer,
+    })
+}
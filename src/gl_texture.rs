@@ -0,0 +1,169 @@
+use std::ptr;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use glib::translate::{from_glib_full, ToGlibPtr};
+
+use gst::prelude::*;
+use gst_gl::prelude::*;
+use gst_gl::{GLContext, GLDisplay, GLAPI, GLPlatform};
+use gst_video::{VideoFormat, VideoInfo};
+
+use crate::config::EncoderConfig;
+use crate::encoder::{EncodeError, FrameSource, VideoEncoder};
+use crate::stats::EncodeStats;
+use crate::video::Orientation;
+
+// They all come with the opengl plugin of gst-plugins-base
+const GL_ELEMENTS: &[&str] = &["glvideoflip", "glcolorconvert", "gldownload"];
+
+type ReleaseTexture = Box<dyn FnOnce() + Send>;
+
+// Encodes RGBA textures rendered by the host without reading them back, the GL elements of the pipeline share
+// their context with the one of the host. Encoders taking GL memory (e.g. nvh264enc) get the converted textures
+// as they are, the other ones only get the frames downloaded after the color conversion
+pub struct GlTextureEncoder {
+    encoder: VideoEncoder,
+    context: GLContext,
+    info: VideoInfo,
+}
+
+impl GlTextureEncoder {
+    // Wraps the GL context current on the calling thread, textures are to be given from that same thread.
+    // Unsafe since gstreamer takes the context as it is, it has to stay alive for as long as the encoder
+    pub unsafe fn new(config: &EncoderConfig, platform: GLPlatform, api: GLAPI) -> Result<Self> {
+        crate::init()?;
+
+        for element in GL_ELEMENTS {
+            if gst::ElementFactory::find(element).is_none() {
+                bail!("Encoding GL textures needs the gstreamer plugin providing the {:?} element", element);
+            }
+        }
+
+        let (context, contexts) = wrap_current_context(platform, api)?;
+
+        let info = VideoInfo::builder(VideoFormat::Rgba, config.width as u32, config.height as u32)
+            .build()
+            .context("Failed to describe the encoded textures")?;
+
+        let encoder = VideoEncoder::with_source(config, FrameSource::GlTextures(contexts))?;
+
+        Ok(Self { encoder, context, info })
+    }
+
+    // The host has to be done rendering into `texture` (e.g. with glFinish) and leave it untouched until `release`
+    // is called, which happens on a gstreamer thread once the encoders are done with it
+    pub fn encode_texture(
+        &mut self,
+        time: f64,
+        texture: u32,
+        release: impl FnOnce() + Send + 'static,
+    ) -> Result<(), EncodeError> {
+        let release: Box<ReleaseTexture> = Box::new(Box::new(release));
+
+        let memory = unsafe {
+            let context: *mut gst_gl_sys::GstGLContext = self.context.to_glib_none().0;
+            let user_data = Box::into_raw(release);
+
+            let params = gst_gl_sys::gst_gl_video_allocation_params_new_wrapped_texture(
+                context,
+                ptr::null_mut(),
+                ToGlibPtr::<*const _>::to_glib_none(&self.info).0 as *mut _,
+                0,
+                ptr::null_mut(),
+                gst_gl_sys::GST_GL_TEXTURE_TARGET_2D,
+                gst_gl_sys::GST_GL_RGBA8,
+                texture,
+                user_data as glib_sys::gpointer,
+                Some(release_texture),
+            );
+            if params.is_null() {
+                release_texture(user_data as glib_sys::gpointer);
+                return Err(EncodeError::InvalidFrame(format!("Failed to wrap the GL texture {}", texture)));
+            }
+
+            let allocator = gst_gl_sys::gst_gl_memory_allocator_get_default(context);
+            let memory = gst_gl_sys::gst_gl_base_memory_alloc(allocator as *mut _, params as *mut _);
+            gst_gl_sys::gst_gl_allocation_params_free(params as *mut _);
+            gst_sys::gst_object_unref(allocator as *mut _);
+
+            // Only the memory calls the release function once it is freed
+            if memory.is_null() {
+                release_texture(user_data as glib_sys::gpointer);
+                return Err(EncodeError::InvalidFrame(format!("Failed to wrap the GL texture {}", texture)));
+            }
+
+            from_glib_full::<_, gst::Memory>(memory as *mut gst_sys::GstMemory)
+        };
+
+        let mut buffer = gst::Buffer::new();
+        buffer.get_mut().unwrap().append_memory(memory);
+
+        self.encoder.encode_buffer(time, buffer)
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        self.encoder.finish()
+    }
+
+    pub fn stop(&mut self) {
+        self.encoder.stop()
+    }
+
+    pub fn stats(&self) -> EncodeStats {
+        self.encoder.stats()
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.encoder.set_orientation(orientation)
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        self.encoder.set_bitrate(bitrate)
+    }
+
+    pub fn save_replay(&self, path: &str) -> Result<()> {
+        self.encoder.save_replay(path)
+    }
+
+    pub fn push_audio_samples(&mut self, time: f64, samples: &[f32]) -> Result<()> {
+        self.encoder.push_audio_samples(time, samples)
+    }
+
+    pub fn dump_pipeline_dot(&self, path: &str) -> Result<()> {
+        self.encoder.dump_pipeline_dot(path)
+    }
+}
+
+// The GL context current on the calling thread, along with the contexts sharing it with the GL elements of a pipeline
+pub(crate) unsafe fn wrap_current_context(platform: GLPlatform, api: GLAPI) -> Result<(GLContext, Vec<gst::Context>)> {
+    let handle = GLContext::get_current_gl_context(platform);
+    if handle == 0 {
+        bail!("No {:?} context is current on this thread", platform);
+    }
+
+    let display = GLDisplay::new();
+    let context = GLContext::new_wrapped(&display, handle, platform, api)
+        .context("Failed to wrap the GL context of the host")?;
+    context
+        .activate(true)
+        .map_err(|e| anyhow!("Failed to activate the wrapped GL context: {}", e))?;
+    context.fill_info().context("Failed to query the wrapped GL context")?;
+
+    let mut display_context = gst::Context::new("gst.gl.GLDisplay", true);
+    display_context.get_mut().unwrap().set_gl_display(&display);
+
+    let mut app_context = gst::Context::new("gst.gl.app_context", true);
+    app_context
+        .get_mut()
+        .unwrap()
+        .get_mut_structure()
+        .set("context", &context);
+
+    Ok((context, vec![display_context, app_context]))
+}
+
+unsafe extern "C" fn release_texture(user_data: glib_sys::gpointer) {
+    let release = Box::from_raw(user_data as *mut ReleaseTexture);
+    release();
+}
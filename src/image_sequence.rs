@@ -0,0 +1,303 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+
+use anyhow::{bail, Context, Result};
+use log::{error, warn};
+
+use image::imageops::FilterType;
+
+use wvr_data::types::Buffer;
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+use wvr_data::types::Speed;
+
+const SUPPORTED_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+type LoadedFrame = (usize, Result<Buffer>);
+
+pub struct ImageSequenceProvider {
+    name: String,
+    frames: Vec<PathBuf>,
+    resolution: (usize, usize),
+
+    current_frame: usize,
+    loaded_frame: Option<usize>,
+    requested_frame: Option<usize>,
+    video_buffer: Buffer,
+
+    // Images are decoded on a loader thread, the last loaded one is shown until the current one is ready
+    load_requests: Option<Sender<usize>>,
+    loaded_frames: Receiver<LoadedFrame>,
+    loader: Option<JoinHandle<()>>,
+
+    playing: bool,
+
+    beat: f64,
+    next_sync_beat: f64,
+
+    time: f64,
+    next_sync_time: f64,
+
+    speed: Speed,
+}
+
+impl ImageSequenceProvider {
+    pub fn new(
+        path: &str,
+        name: String,
+        resolution: (usize, usize),
+        speed: Speed,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        check_speed(&speed)?;
+
+        let frames = list_frames(Path::new(path))?;
+
+        let (load_requests, requests) = channel::<usize>();
+        let (loaded_sender, loaded_frames) = channel::<LoadedFrame>();
+        let loader = {
+            let frames = frames.clone();
+            std::thread::spawn(move || {
+                while let Ok(mut index) = requests.recv() {
+                    // Frames requested while the previous one was decoded are stale by now
+                    if let Some(latest) = requests.try_iter().last() {
+                        index = latest;
+                    }
+
+                    if loaded_sender.send((index, load_frame(&frames[index], resolution))).is_err() {
+                        return;
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            name,
+            frames,
+            resolution,
+            current_frame: 0,
+            loaded_frame: None,
+            requested_frame: None,
+            video_buffer: Buffer {
+                dimensions: vec![resolution.0, resolution.1, 3],
+                data: None,
+            },
+            load_requests: Some(load_requests),
+            loaded_frames,
+            loader: Some(loader),
+            playing: start_playing,
+            beat: start_beat,
+            next_sync_beat: start_beat,
+            time: start_time,
+            next_sync_time: start_time,
+            speed,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn advance(&mut self) {
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
+    }
+
+    fn update_frame(&mut self) {
+        for (index, result) in self.loaded_frames.try_iter() {
+            if self.requested_frame == Some(index) {
+                self.requested_frame = None;
+            }
+
+            match result {
+                Ok(buffer) => {
+                    self.video_buffer = buffer;
+                    self.loaded_frame = Some(index);
+                }
+                Err(e) => error!("{}: {:?}", self.name, e),
+            }
+        }
+
+        if self.loaded_frame != Some(self.current_frame) && self.requested_frame != Some(self.current_frame) {
+            if let Some(load_requests) = &self.load_requests {
+                if load_requests.send(self.current_frame).is_ok() {
+                    self.requested_frame = Some(self.current_frame);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ImageSequenceProvider {
+    fn drop(&mut self) {
+        // Disconnecting the requests ends the loader once it is done with the current image
+        self.load_requests.take();
+        if let Some(loader) = self.loader.take() {
+            let _ = loader.join();
+        }
+    }
+}
+
+fn check_speed(speed: &Speed) -> Result<()> {
+    match speed {
+        Speed::Fpb(speed) | Speed::Fps(speed) if *speed <= 0.0 || !speed.is_finite() => {
+            bail!("Image sequences only play forward, got a speed of {:?}", speed)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn load_frame(path: &Path, resolution: (usize, usize)) -> Result<Buffer> {
+    let image = image::open(path).context(format!("Failed to load image {:?}", path))?;
+
+    let image = if (image.width() as usize, image.height() as usize) != resolution {
+        image.resize_exact(resolution.0 as u32, resolution.1 as u32, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    // Matches the vertical flip applied to decoded videos
+    let image = image.flipv().into_rgb8();
+
+    Ok(Buffer {
+        dimensions: vec![image.width() as usize, image.height() as usize, 3],
+        data: Some(image.into_raw()),
+    })
+}
+
+fn list_frames(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut frames = Vec::new();
+    for entry in fs::read_dir(path).context(format!("Failed to read image sequence {:?}", path))? {
+        let entry_path = entry?.path();
+
+        let is_supported = entry_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| SUPPORTED_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+            .unwrap_or(false);
+
+        if is_supported {
+            frames.push(entry_path);
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(anyhow::anyhow!("No image found in sequence directory {:?}", path));
+    }
+
+    frames.sort_by_key(|frame| (frame_number(frame), frame.clone()));
+
+    Ok(frames)
+}
+
+// Extracts the trailing frame number of a file stem, so that "frame_9" comes before "frame_10"
+fn frame_number(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_str()?;
+    let digits_start = stem
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map(|index| index + 1)
+        .unwrap_or(0);
+
+    stem[digits_start..].parse().ok()
+}
+
+impl InputProvider for ImageSequenceProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        let speed = match (property, value) {
+            ("speed_fpb", DataHolder::Float(new_speed)) => Speed::Fpb(*new_speed),
+            ("speed_fps", DataHolder::Float(new_speed)) => Speed::Fps(*new_speed),
+            _ => {
+                warn!("Set_property unimplemented for {:}", property);
+                return;
+            }
+        };
+
+        match check_speed(&speed) {
+            Ok(()) => self.speed = speed,
+            Err(e) => error!("{}: {:?}", self.name, e),
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        if uniform_name == self.name {
+            self.update_frame();
+
+            let dimensions = (
+                self.video_buffer.dimensions[0] as u32,
+                self.video_buffer.dimensions[1] as u32,
+            );
+
+            let data = if invalidate {
+                self.video_buffer.data.take()
+            } else {
+                self.video_buffer.data.clone()
+            };
+
+            data.map(|data| DataHolder::Texture((dimensions, data)))
+        } else {
+            None
+        }
+    }
+
+    fn set_beat(&mut self, beat: f64, _sync: bool) {
+        self.beat = beat;
+
+        if let Speed::Fpb(frames_per_beat) = self.speed {
+            if !self.playing {
+                return;
+            }
+
+            while self.beat > self.next_sync_beat {
+                self.next_sync_beat += 1.0 / (frames_per_beat as f64);
+                self.advance();
+            }
+        }
+    }
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        self.time = time;
+
+        if let Speed::Fps(frame_rate) = self.speed {
+            if !self.playing {
+                return;
+            }
+
+            while self.time > self.next_sync_time {
+                self.next_sync_time += 1.0 / (frame_rate as f64);
+                self.advance();
+            }
+        }
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.playing = false;
+        self.current_frame = 0;
+
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.playing = true;
+        self.next_sync_beat = self.beat;
+        self.next_sync_time = self.time;
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playing = false;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+
+static GST_INIT: OnceCell<Result<(), String>> = OnceCell::new();
+
+// Initializes gstreamer once for the whole process, constructors call it as well but hosts can do it
+// early to report a broken installation before building any provider
+pub fn init() -> Result<()> {
+    GST_INIT
+        .get_or_init(|| gst::init().map_err(|e| e.to_string()))
+        .clone()
+        .map_err(|e| anyhow!("Failed to initialize the gstreamer library: {}", e))
+}
@@ -1,9 +1,66 @@
+extern crate glib;
 extern crate gstreamer as gst;
 extern crate gstreamer_app as gst_app;
 extern crate gstreamer_audio as gst_audio;
+extern crate gstreamer_pbutils as gst_pbutils;
 extern crate gstreamer_video as gst_video;
 extern crate image;
 extern crate wvr_data;
 
+#[cfg(any(all(feature = "dmabuf", target_os = "linux"), feature = "gl"))]
+extern crate glib_sys;
+#[cfg(all(feature = "dmabuf", target_os = "linux"))]
+extern crate gstreamer_allocators_sys as gst_allocators_sys;
+#[cfg(feature = "gl")]
+extern crate gstreamer_gl as gst_gl;
+#[cfg(feature = "gl")]
+extern crate gstreamer_gl_sys as gst_gl_sys;
+#[cfg(any(all(feature = "dmabuf", target_os = "linux"), feature = "gl"))]
+extern crate gstreamer_sys as gst_sys;
+#[cfg(feature = "webrtc")]
+extern crate gstreamer_sdp as gst_sdp;
+#[cfg(feature = "webrtc")]
+extern crate gstreamer_webrtc as gst_webrtc;
+
+pub mod animated_image;
+mod audio;
+pub mod camera;
+pub mod clip_bank;
+pub mod config;
+pub mod decklink;
+#[cfg(all(feature = "dmabuf", target_os = "linux"))]
+pub mod dmabuf;
+mod encode_queue;
 pub mod encoder;
+pub mod events;
+mod frame_queue;
+mod frame_source;
+#[cfg(feature = "gl")]
+pub mod gl_source;
+#[cfg(feature = "gl")]
+pub mod gl_texture;
+pub mod image_sequence;
+mod init;
+pub mod logging;
+pub mod media_info;
+pub mod ndi;
+pub mod pipeline_provider;
+mod pixel;
+pub mod playlist;
+pub mod ram_clip;
+mod replay;
+pub mod requirements;
+pub mod shm;
+pub mod stats;
+mod subtitle;
+mod sync;
+pub mod test_pattern;
+#[cfg(all(feature = "texture_share", any(target_os = "windows", target_os = "macos")))]
+pub mod texture_share;
+pub mod transition;
 pub mod video;
+pub mod virtual_camera;
+#[cfg(feature = "webrtc")]
+pub mod webrtc;
+
+pub use crate::init::init;
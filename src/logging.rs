@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use log::Level;
+
+use gst::prelude::*;
+
+// Forwards gstreamer's own debug output to the log crate, under the "gst::<category>" targets.
+// Messages below `threshold` are filtered out by gstreamer before they are formatted
+pub fn bridge_gst_debug(threshold: gst::DebugLevel) -> Result<()> {
+    crate::init()?;
+
+    gst::debug_remove_default_log_function();
+    gst::debug_set_default_threshold(threshold);
+    gst::debug_set_active(true);
+
+    gst::debug_add_log_function(|category, level, file, function, line, object, message| {
+        let level = match level {
+            gst::DebugLevel::Error => Level::Error,
+            gst::DebugLevel::Warning | gst::DebugLevel::Fixme => Level::Warn,
+            gst::DebugLevel::Info => Level::Info,
+            gst::DebugLevel::Debug => Level::Debug,
+            _ => Level::Trace,
+        };
+
+        let message = match message.get() {
+            Some(message) => message,
+            None => return,
+        };
+
+        let object = object
+            .map(|object| format!(" <{}>", object))
+            .unwrap_or_default();
+
+        log::log!(
+            target: &format!("gst::{}", category.get_name()),
+            level,
+            "{}:{}:{}{} {}",
+            file,
+            line,
+            function,
+            object,
+            message.to_string_lossy()
+        );
+    });
+
+    Ok(())
+}
+
+// Writes the graph of a pipeline in the DOT format, `dot -Tsvg` renders it along with the negotiated caps
+pub(crate) fn write_pipeline_dot(pipeline: &gst::Element, path: &str) -> Result<()> {
+    let bin = pipeline
+        .clone()
+        .dynamic_cast::<gst::Bin>()
+        .map_err(|_| anyhow::anyhow!("Only pipelines can be dumped as a graph"))?;
+
+    let graph = gst::debug_bin_to_dot_data(&bin, gst::DebugGraphDetails::ALL);
+    std::fs::write(path, graph.as_str()).context(format!("Failed to write pipeline graph to {:?}", path))?;
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+use gst_pbutils::prelude::*;
+
+use crate::video::path_to_uri;
+
+const PROBE_TIMEOUT: u64 = 10;
+
+#[derive(Clone, Debug)]
+pub struct VideoStreamInfo {
+    pub codec: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub framerate: Option<f64>,
+    // Clockwise rotation in degrees requested by the orientation tag of the stream
+    pub rotation: u32,
+    pub interlaced: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct AudioStreamInfo {
+    pub codec: Option<String>,
+    pub channels: u32,
+    pub sample_rate: u32,
+    pub language: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MediaInfo {
+    pub uri: String,
+    pub duration: Option<f64>,
+    pub seekable: bool,
+    pub container: Option<String>,
+    pub video_streams: Vec<VideoStreamInfo>,
+    pub audio_streams: Vec<AudioStreamInfo>,
+    pub tags: Vec<(String, String)>,
+}
+
+impl MediaInfo {
+    pub fn resolution(&self) -> Option<(u32, u32)> {
+        self.video_streams.first().map(|stream| (stream.width, stream.height))
+    }
+
+    pub fn framerate(&self) -> Option<f64> {
+        self.video_streams.first().and_then(|stream| stream.framerate)
+    }
+}
+
+// Reads the properties of a media file without building a provider for it
+pub fn probe_media(path: &str) -> Result<MediaInfo> {
+    let uri = path_to_uri(path)?;
+    let info = discover(&uri)?;
+
+    let duration = info
+        .get_duration()
+        .nseconds()
+        .map(|duration| duration as f64 / 1_000_000_000.0);
+
+    let container = info.get_stream_info().and_then(|stream_info| codec_name(&stream_info));
+
+    let tags = info
+        .get_tags()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|(name, value)| {
+                    value
+                        .transform::<String>()
+                        .and_then(|value| value.get::<String>().ok().flatten())
+                        .map(|value| (name.to_string(), value))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let video_streams = info
+        .get_video_streams()
+        .iter()
+        .map(|stream| {
+            let framerate = stream.get_framerate();
+            let framerate = if *framerate.denom() > 0 && *framerate.numer() > 0 {
+                Some(*framerate.numer() as f64 / *framerate.denom() as f64)
+            } else {
+                None
+            };
+
+            VideoStreamInfo {
+                codec: codec_name(stream),
+                width: stream.get_width(),
+                height: stream.get_height(),
+                framerate,
+                rotation: stream.get_tags().map(|tags| rotation(&tags)).unwrap_or(0),
+                interlaced: stream.is_interlaced(),
+            }
+        })
+        .collect();
+
+    let audio_streams = info
+        .get_audio_streams()
+        .iter()
+        .map(|stream| AudioStreamInfo {
+            codec: codec_name(stream),
+            channels: stream.get_channels(),
+            sample_rate: stream.get_sample_rate(),
+            language: stream.get_language().map(|language| language.to_string()),
+        })
+        .collect();
+
+    Ok(MediaInfo {
+        uri,
+        duration,
+        seekable: info.get_seekable(),
+        container,
+        video_streams,
+        audio_streams,
+        tags,
+    })
+}
+
+pub(crate) fn discover(uri: &str) -> Result<gst_pbutils::DiscovererInfo> {
+    crate::init()?;
+
+    let discoverer = gst_pbutils::Discoverer::new(gst::ClockTime::from_seconds(PROBE_TIMEOUT))
+        .context("Failed to create a gstreamer discoverer")?;
+
+    let info = discoverer
+        .discover_uri(uri)
+        .map_err(|e| anyhow!("Failed to probe {:?}: {}", uri, e))?;
+
+    match info.get_result() {
+        gst_pbutils::DiscovererResult::Ok => Ok(info),
+        gst_pbutils::DiscovererResult::MissingPlugins => bail!(
+            "Missing gstreamer plugins to decode {:?}: {}",
+            uri,
+            info.get_missing_elements_installer_details().join(", ")
+        ),
+        gst_pbutils::DiscovererResult::Timeout => bail!("Timed out while probing {:?}", uri),
+        result => bail!("Failed to probe {:?}: {:?}", uri, result),
+    }
+}
+
+fn codec_name<T: IsA<gst_pbutils::DiscovererStreamInfo>>(stream: &T) -> Option<String> {
+    stream
+        .get_caps()
+        .and_then(|caps| caps.get_structure(0).map(|structure| structure.get_name().to_string()))
+}
+
+fn rotation(tags: &gst::TagList) -> u32 {
+    tags.get::<gst::tags::ImageOrientation>()
+        .and_then(|orientation| orientation.get().map(|orientation| orientation.to_string()))
+        .and_then(|orientation| orientation.rsplit('-').next().and_then(|angle| angle.parse().ok()))
+        .unwrap_or(0)
+}
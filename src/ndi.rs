@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+
+use crate::config::EncoderConfig;
+use crate::encoder::{EncodeError, VideoEncoder};
+use crate::stats::EncodeStats;
+use crate::video::Orientation;
+
+// Comes from the gst-plugin-ndi plugin, along with the NDI runtime installed from ndi.video
+const NDI_SINK: &str = "ndisink";
+
+// Announces rendered frames as an NDI source on the network, for OBS, vMix or other machines to pick up.
+// Same as a VideoEncoder with an ndi:// output, which can also come next to recordings and streams
+pub struct NdiOutput {
+    encoder: VideoEncoder,
+}
+
+impl NdiOutput {
+    pub fn new(ndi_name: &str, width: usize, height: usize, framerate: f64) -> Result<Self> {
+        Self::from_config(&EncoderConfig::new(&format!("ndi://{}", ndi_name), width, height, framerate))
+    }
+
+    // The path of the config is the ndi:// URL, the codec settings do not apply
+    pub fn from_config(config: &EncoderConfig) -> Result<Self> {
+        crate::init()?;
+
+        if gst::ElementFactory::find(NDI_SINK).is_none() {
+            bail!("NDI output needs the gstreamer plugin providing the {:?} element", NDI_SINK);
+        }
+
+        Ok(Self {
+            encoder: VideoEncoder::from_config(config)?,
+        })
+    }
+
+    pub fn send_frame(&mut self, time: f64, frame: &[u8]) -> Result<(), EncodeError> {
+        self.encoder.encode_frame(time, frame)
+    }
+
+    pub fn send_frame_with_stride(&mut self, time: f64, frame: &[u8], stride: usize) -> Result<(), EncodeError> {
+        self.encoder.encode_frame_with_stride(time, frame, stride)
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.encoder.set_orientation(orientation)
+    }
+
+    pub fn stats(&self) -> EncodeStats {
+        self.encoder.stats()
+    }
+
+    pub fn stop(&mut self) {
+        self.encoder.stop()
+    }
+}
+
+impl Drop for NdiOutput {
+    fn drop(&mut self) {
+        self.encoder.stop();
+    }
+}
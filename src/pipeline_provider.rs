@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use log::error;
+
+use gst::prelude::*;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::stats::DecodeStats;
+use crate::video::{scale_caps, FitMode, LoopMode, OutputFormat, PlaybackSpeed, VideoProvider};
+
+// Wraps any gst-launch description ending in a named appsink, e.g.
+// "videotestsrc ! videoconvert ! videoflip method=vertical-flip ! appsink name=sink".
+// Textures are expected bottom-up like the ones of the other providers, hence the flip
+pub struct GstPipelineProvider {
+    video_provider: VideoProvider,
+}
+
+impl GstPipelineProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pipeline_description: &str,
+        appsink_name: &str,
+        name: String,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+        output_format: OutputFormat,
+    ) -> Result<Self> {
+        let speed = speed.into();
+
+        let mut video_provider = VideoProvider::from_launch(
+            pipeline_description,
+            appsink_name,
+            name,
+            None,
+            speed,
+            start_beat,
+            start_time,
+            false,
+            LoopMode::Hold,
+            None,
+            None,
+            output_format,
+        )?;
+
+        if let Some(appsink) = video_provider
+            .get_element(appsink_name)
+            .and_then(|element| element.dynamic_cast::<gst_app::AppSink>().ok())
+        {
+            // Without caps of its own the appsink would accept formats textures can not hold
+            if appsink.get_caps().is_none() {
+                let caps = gst::Caps::from_str(&scale_caps(output_format, None, FitMode::Stretch))
+                    .context("Failed to build the caps of the video sink")?;
+                appsink.set_caps(Some(&caps));
+            }
+
+            // Frames are gated by set_beat / set_time unless the pipeline clock paces them
+            if let Err(e) = appsink.set_property("sync", &(speed == PlaybackSpeed::Native)) {
+                error!("Failed to set the clock synchronization of the video sink: {:?}", e);
+            }
+        }
+
+        if start_playing {
+            video_provider.play()?;
+        }
+
+        Ok(Self { video_provider })
+    }
+
+    pub fn dump_pipeline_dot(&self, path: &str) -> Result<()> {
+        self.video_provider.dump_pipeline_dot(path)
+    }
+
+    pub fn stats(&self) -> DecodeStats {
+        self.video_provider.stats()
+    }
+
+    pub fn get_element(&self, element_name: &str) -> Option<gst::Element> {
+        self.video_provider.get_element(element_name)
+    }
+}
+
+impl InputProvider for GstPipelineProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        self.video_provider.set_property(property, value);
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
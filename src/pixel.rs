@@ -0,0 +1,156 @@
+use crate::video::{OutputFormat, TextureFormat};
+
+// Frames coming from gstreamer may have padding at the end of each row
+pub(crate) fn pack_rows(data: &[u8], row_size: usize, height: usize, stride: usize, output: &mut Vec<u8>) {
+    if stride == row_size {
+        output.extend_from_slice(&data[..row_size * height]);
+        return;
+    }
+
+    output.reserve(row_size * height);
+    for row in data.chunks(stride).take(height) {
+        output.extend_from_slice(&row[..row_size]);
+    }
+}
+
+// Float RGBA frames as the native endian 16 bits ARGB gstreamer takes, values outside of 0..1 are clipped
+pub(crate) fn float_rgba_to_argb64(data: &[u8], width: usize, height: usize, stride: usize, output: &mut Vec<u8>) {
+    let quantize = |bytes: &[u8]| {
+        let value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        (value.max(0.0).min(1.0) * u16::MAX as f32).round() as u16
+    };
+
+    output.reserve(width * height * 8);
+    for row in data.chunks(stride).take(height) {
+        for pixel in row[..width * 16].chunks_exact(16) {
+            for channel in [3, 0, 1, 2].iter() {
+                output.extend_from_slice(&quantize(&pixel[channel * 4..channel * 4 + 4]).to_ne_bytes());
+            }
+        }
+    }
+}
+
+// Converts a decoded frame to the requested output layout in a single pass over the mapped buffer
+pub(crate) fn convert_frame(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: &TextureFormat,
+    output_format: OutputFormat,
+    output: &mut Vec<u8>,
+) {
+    output.clear();
+
+    let row_size = width * format.bytes_per_pixel();
+
+    if same_layout(format, output_format) {
+        pack_rows(data, row_size, height, stride, output);
+        return;
+    }
+
+    output.reserve(width * height * output_format.bytes_per_pixel());
+    for row in data.chunks(stride).take(height) {
+        convert_row(&row[..row_size], format, output_format, output);
+    }
+}
+
+fn same_layout(format: &TextureFormat, output_format: OutputFormat) -> bool {
+    matches!(
+        (format, output_format),
+        (TextureFormat::RGBU8, OutputFormat::Rgb8)
+            | (TextureFormat::RGBAU8, OutputFormat::Rgba8)
+            | (TextureFormat::GRAYU8, OutputFormat::Gray8)
+            | (TextureFormat::GRAYU16, OutputFormat::Gray16)
+    )
+}
+
+// The common 8 bits swizzles are written as plain loops over fixed size chunks, which the compiler vectorizes
+fn convert_row(row: &[u8], format: &TextureFormat, output_format: OutputFormat, output: &mut Vec<u8>) {
+    match (format, output_format) {
+        (TextureFormat::BGRU8, OutputFormat::Rgb8) => {
+            for pixel in row.chunks_exact(3) {
+                output.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+        }
+        (TextureFormat::RGBAU8, OutputFormat::Rgb8) => {
+            for pixel in row.chunks_exact(4) {
+                output.extend_from_slice(&pixel[..3]);
+            }
+        }
+        (TextureFormat::BGRAU8, OutputFormat::Rgb8) => {
+            for pixel in row.chunks_exact(4) {
+                output.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+        }
+        (TextureFormat::RGBU8, OutputFormat::Rgba8) => {
+            for pixel in row.chunks_exact(3) {
+                output.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+        (TextureFormat::BGRU8, OutputFormat::Rgba8) => {
+            for pixel in row.chunks_exact(3) {
+                output.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+            }
+        }
+        (TextureFormat::BGRAU8, OutputFormat::Rgba8) => {
+            for pixel in row.chunks_exact(4) {
+                output.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            }
+        }
+        _ => {
+            for pixel in row.chunks_exact(format.bytes_per_pixel()) {
+                write_pixel(read_pixel(pixel, format), output_format, output);
+            }
+        }
+    }
+}
+
+// Reads a pixel as 16 bits RGBA
+fn read_pixel(pixel: &[u8], format: &TextureFormat) -> [u16; 4] {
+    let expand = |value: u8| value as u16 * 257;
+
+    match format {
+        TextureFormat::RGBU8 => [expand(pixel[0]), expand(pixel[1]), expand(pixel[2]), u16::MAX],
+        TextureFormat::RGBAU8 => [expand(pixel[0]), expand(pixel[1]), expand(pixel[2]), expand(pixel[3])],
+        TextureFormat::BGRU8 => [expand(pixel[2]), expand(pixel[1]), expand(pixel[0]), u16::MAX],
+        TextureFormat::BGRAU8 => [expand(pixel[2]), expand(pixel[1]), expand(pixel[0]), expand(pixel[3])],
+        TextureFormat::GRAYU8 => {
+            let luma = expand(pixel[0]);
+            [luma, luma, luma, u16::MAX]
+        }
+        TextureFormat::GRAYU16 => {
+            let luma = u16::from_le_bytes([pixel[0], pixel[1]]);
+            [luma, luma, luma, u16::MAX]
+        }
+        TextureFormat::ARGBU16 => {
+            // ARGB64 holds native endian values
+            let channel = |index: usize| u16::from_ne_bytes([pixel[index * 2], pixel[index * 2 + 1]]);
+            [channel(1), channel(2), channel(3), channel(0)]
+        }
+    }
+}
+
+fn write_pixel(pixel: [u16; 4], output_format: OutputFormat, output: &mut Vec<u8>) {
+    let narrow = |value: u16| (value >> 8) as u8;
+    let luma = || ((pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000) as u16;
+
+    match output_format {
+        OutputFormat::Rgb8 => output.extend_from_slice(&[narrow(pixel[0]), narrow(pixel[1]), narrow(pixel[2])]),
+        OutputFormat::Rgba8 => output.extend_from_slice(&[
+            narrow(pixel[0]),
+            narrow(pixel[1]),
+            narrow(pixel[2]),
+            narrow(pixel[3]),
+        ]),
+        OutputFormat::Gray8 => output.push(narrow(luma())),
+        OutputFormat::Gray16 => output.extend_from_slice(&luma().to_le_bytes()),
+        OutputFormat::Rgba16 => {
+            for channel in pixel.iter() {
+                output.extend_from_slice(&channel.to_le_bytes());
+            }
+        }
+        // Planar formats are copied plane by plane and never converted per pixel
+        OutputFormat::I420 | OutputFormat::Nv12 => (),
+    }
+}
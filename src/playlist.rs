@@ -0,0 +1,310 @@
+use anyhow::Result;
+use log::error;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::config::PlaylistConfig;
+use crate::stats::DecodeStats;
+use crate::transition::{Crossfade, TransitionDuration};
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::PlaybackSpeed;
+use crate::video::VideoProvider;
+
+pub struct PlaylistProvider {
+    name: String,
+    paths: Vec<String>,
+    resolution: (usize, usize),
+    speed: PlaybackSpeed,
+
+    beat: f64,
+    time: f64,
+    playing: bool,
+
+    current_index: usize,
+    current: VideoProvider,
+    // The following clip is prerolled in advance so that switching to it does not leave a gap
+    preloaded: Option<(usize, VideoProvider)>,
+
+    transition: Option<TransitionDuration>,
+    // The previous clip keeps playing until the crossfade towards the current one is over
+    crossfade: Option<(VideoProvider, Crossfade)>,
+}
+
+impl PlaylistProvider {
+    pub fn new(
+        paths: Vec<String>,
+        name: String,
+        resolution: (usize, usize),
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let speed = speed.into();
+        if paths.is_empty() {
+            return Err(anyhow::anyhow!("Playlist {:?} does not contain any video", name));
+        }
+
+        let current = create_provider(&paths[0], &name, resolution, speed, start_beat, start_time, start_playing)?;
+
+        let mut playlist = Self {
+            name,
+            paths,
+            resolution,
+            speed,
+            beat: start_beat,
+            time: start_time,
+            playing: start_playing,
+            current_index: 0,
+            current,
+            preloaded: None,
+            transition: None,
+            crossfade: None,
+        };
+
+        playlist.preload(1);
+
+        Ok(playlist)
+    }
+
+    pub fn from_config(name: String, config: &PlaylistConfig) -> Result<Self> {
+        let mut playlist = Self::new(
+            config.paths.clone(),
+            name,
+            config.resolution,
+            config.speed,
+            config.start_beat,
+            config.start_time,
+            config.start_playing,
+        )?;
+        playlist.set_transition(config.transition);
+
+        Ok(playlist)
+    }
+
+    pub fn set_transition(&mut self, transition: Option<TransitionDuration>) {
+        self.transition = transition;
+    }
+
+    pub fn dump_pipeline_dot(&self, path: &str) -> Result<()> {
+        self.current.dump_pipeline_dot(path)
+    }
+
+    // Statistics of the clip being shown
+    pub fn stats(&self) -> DecodeStats {
+        self.current.stats()
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current_index
+    }
+
+    pub fn next(&mut self) -> Result<()> {
+        self.jump(self.current_index + 1)
+    }
+
+    pub fn previous(&mut self) -> Result<()> {
+        self.jump(self.current_index + self.paths.len() - 1)
+    }
+
+    pub fn jump(&mut self, index: usize) -> Result<()> {
+        let index = index % self.paths.len();
+
+        let mut provider = match self.preloaded.take() {
+            Some((preloaded_index, provider)) if preloaded_index == index => provider,
+            _ => self.create_provider(index, false)?,
+        };
+
+        if self.playing {
+            provider.play()?;
+        }
+
+        let previous = std::mem::replace(&mut self.current, provider);
+        self.current_index = index;
+
+        self.crossfade = self
+            .transition
+            .map(|transition| (previous, Crossfade::new(transition, self.beat, self.time)));
+
+        self.preload(index + 1);
+
+        Ok(())
+    }
+
+    fn preload(&mut self, index: usize) {
+        let index = index % self.paths.len();
+        if index == self.current_index {
+            return;
+        }
+
+        match self.create_provider(index, false) {
+            Ok(mut provider) => {
+                if let Err(e) = provider.pause() {
+                    error!("{:?}", e);
+                }
+                self.preloaded = Some((index, provider));
+            }
+            Err(e) => error!("{:?}", e),
+        }
+    }
+
+    fn create_provider(&self, index: usize, start_playing: bool) -> Result<VideoProvider> {
+        create_provider(
+            &self.paths[index],
+            &self.name,
+            self.resolution,
+            self.speed,
+            self.beat,
+            self.time,
+            start_playing,
+        )
+    }
+
+    fn check_advance(&mut self) {
+        self.current.check_loop();
+
+        let crossfade_finished = self
+            .crossfade
+            .as_ref()
+            .map(|(_, crossfade)| crossfade.progress(self.beat, self.time) >= 1.0)
+            .unwrap_or(false);
+        if crossfade_finished {
+            self.crossfade = None;
+        }
+
+        if self.current.is_eos() {
+            if let Err(e) = self.next() {
+                error!("{:?}", e);
+            }
+        }
+    }
+}
+
+fn create_provider(
+    path: &str,
+    name: &str,
+    resolution: (usize, usize),
+    speed: PlaybackSpeed,
+    start_beat: f64,
+    start_time: f64,
+    start_playing: bool,
+) -> Result<VideoProvider> {
+    VideoProvider::new(
+        path,
+        name.to_owned(),
+        resolution,
+        speed,
+        start_beat,
+        start_time,
+        start_playing,
+        LoopMode::Hold,
+        None,
+        None,
+        OutputFormat::default(),
+    )
+}
+
+impl InputProvider for PlaylistProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+        self.current.set_name(name);
+        if let Some((_, preloaded)) = self.preloaded.as_mut() {
+            preloaded.set_name(name);
+        }
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.current.provides()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        let result = match (property, value) {
+            ("next", DataHolder::Bool(true)) => self.next(),
+            ("previous", DataHolder::Bool(true)) => self.previous(),
+            ("jump", DataHolder::Int(index)) => self.jump((*index).max(0) as usize),
+            ("transition_seconds", DataHolder::Float(duration)) => {
+                self.set_transition(Some(TransitionDuration::Seconds(*duration as f64)).filter(|_| *duration > 0.0));
+                Ok(())
+            }
+            ("transition_beats", DataHolder::Float(duration)) => {
+                self.set_transition(Some(TransitionDuration::Beats(*duration as f64)).filter(|_| *duration > 0.0));
+                Ok(())
+            }
+            ("speed_fpb", DataHolder::Float(new_speed)) => {
+                self.speed = PlaybackSpeed::Fpb(*new_speed);
+                self.current.set_property(property, value);
+                Ok(())
+            }
+            ("speed_fps", DataHolder::Float(new_speed)) => {
+                self.speed = PlaybackSpeed::Fps(*new_speed);
+                self.current.set_property(property, value);
+                Ok(())
+            }
+            _ => {
+                self.current.set_property(property, value);
+                Ok(())
+            }
+        };
+
+        if let Err(e) = result {
+            error!("{:?}", e);
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.check_advance();
+
+        if uniform_name == self.name {
+            if let Some((previous, crossfade)) = self.crossfade.as_mut() {
+                let progress = crossfade.progress(self.beat, self.time);
+                let outgoing = previous.get(uniform_name, invalidate);
+                let incoming = self.current.get(uniform_name, invalidate);
+
+                return crossfade.blend(outgoing, incoming, progress);
+            }
+        }
+
+        self.current.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.beat = beat;
+
+        if let Some((_, preloaded)) = self.preloaded.as_mut() {
+            preloaded.set_beat(beat, false);
+        }
+        if let Some((previous, _)) = self.crossfade.as_mut() {
+            previous.set_beat(beat, false);
+        }
+        self.current.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.time = time;
+
+        if let Some((_, preloaded)) = self.preloaded.as_mut() {
+            preloaded.set_time(time, false);
+        }
+        if let Some((previous, _)) = self.crossfade.as_mut() {
+            previous.set_time(time, false);
+        }
+        self.current.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.playing = false;
+        self.current.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.playing = true;
+        self.current.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playing = false;
+        self.current.pause()
+    }
+}
@@ -0,0 +1,345 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{error, warn};
+
+use gst::prelude::*;
+use gst::State;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::pixel::pack_rows;
+use crate::video::{path_to_uri, quote_launch_value, scale_caps, FitMode, LoopMode, OutputFormat};
+
+// Decoded frames are kept uncompressed, longer clips belong in a streaming provider
+const MAX_RAM_CLIP_BYTES: usize = 1 << 30;
+
+// Errors are only posted on the bus, it is checked between two waits for a frame
+const PULL_INTERVAL_MS: u64 = 100;
+// Without any frame for that long the decoder is considered stuck
+const DECODE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct RamFrame {
+    pts: f64,
+    data: Vec<u8>,
+}
+
+pub struct RamClipProvider {
+    name: String,
+    resolution: (usize, usize),
+
+    frames: Vec<RamFrame>,
+    duration: f64,
+
+    // Position in the clip before the loop mode is applied, it keeps growing while looping
+    playhead: f64,
+    rate: f64,
+    loop_mode: LoopMode,
+
+    current_frame: Option<usize>,
+    fresh_frame: bool,
+
+    time: f64,
+    playing: bool,
+}
+
+impl RamClipProvider {
+    pub fn new(
+        path: &str,
+        name: String,
+        resolution: impl Into<Option<(usize, usize)>>,
+        start_time: f64,
+        start_playing: bool,
+        loop_mode: LoopMode,
+    ) -> Result<Self> {
+        let (resolution, frames, duration) = decode_clip(path, resolution.into())?;
+
+        Ok(Self {
+            name,
+            resolution,
+            frames,
+            duration,
+            playhead: 0.0,
+            rate: 1.0,
+            loop_mode,
+            current_frame: None,
+            fresh_frame: true,
+            time: start_time,
+            playing: start_playing,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    pub fn position(&self) -> f64 {
+        let length = self.duration;
+        if length <= 0.0 {
+            return 0.0;
+        }
+
+        match self.loop_mode {
+            LoopMode::Loop => self.playhead.rem_euclid(length),
+            LoopMode::PingPong => {
+                let phase = self.playhead.rem_euclid(2.0 * length);
+                if phase < length {
+                    phase
+                } else {
+                    2.0 * length - phase
+                }
+            }
+            LoopMode::Once | LoopMode::Hold => self.playhead.max(0.0).min(length),
+        }
+    }
+
+    // Frames are already decoded, seeking only moves the playhead
+    pub fn seek(&mut self, position: f64) {
+        self.playhead = position;
+        self.update_frame();
+    }
+
+    // Negative rates play the clip in reverse
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) {
+        // Keeps showing the same position when switching modes
+        self.playhead = self.position();
+        self.loop_mode = loop_mode;
+    }
+
+    fn update_frame(&mut self) {
+        let position = self.position();
+
+        let frame_index = self
+            .frames
+            .partition_point(|frame| frame.pts <= position)
+            .saturating_sub(1);
+
+        if self.current_frame != Some(frame_index) {
+            self.current_frame = Some(frame_index);
+            self.fresh_frame = true;
+        }
+    }
+}
+
+// Decodes the whole clip as fast as possible, without any clock synchronization
+fn decode_clip(path: &str, resolution: Option<(usize, usize)>) -> Result<((usize, usize), Vec<RamFrame>, f64)> {
+    crate::init()?;
+
+    let uri = path_to_uri(path)?;
+
+    // Frames are flipped like the ones of streamed videos
+    let pipeline_string = format!(
+        "uridecodebin uri=\"{}\" ! videoconvert ! videoscale ! capsfilter caps=\"{}\" ! videoflip method=vertical-flip ! appsink name=appsink sync=false",
+        quote_launch_value(&uri),
+        scale_caps(OutputFormat::Rgb8, resolution, FitMode::Stretch),
+    );
+
+    let pipeline =
+        gst::parse_launch(&pipeline_string).context("Failed to build gstreamer pipeline")?;
+
+    let appsink = pipeline
+        .clone()
+        .dynamic_cast::<gst::Bin>()
+        .expect("Failed to cast the gstreamer pipeline as a gst::Bin element")
+        .get_by_name("appsink")
+        .expect("Failed to retrieve sink from gstreamer pipeline.")
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("The sink defined in the pipeline is not an appsink");
+
+    pipeline
+        .set_state(State::Playing)
+        .context(format!("Failed to start gstreamer pipeline for video {:?}", uri))?;
+
+    let result = pull_frames(&pipeline, &appsink);
+
+    let duration = pipeline
+        .query_duration::<gst::ClockTime>()
+        .and_then(|duration| duration.nseconds())
+        .map(|duration| duration as f64 / 1_000_000_000.0);
+
+    if let Err(e) = pipeline.set_state(State::Null) {
+        error!("Failed to stop video decoding: {:?}", e);
+    }
+
+    let (size, frames, frame_duration) = result.context(format!("Failed to preload video {:?}", path))?;
+    let last_pts = frames.last().map(|frame| frame.pts).unwrap_or(0.0);
+
+    Ok((size, frames, duration.unwrap_or(last_pts + frame_duration)))
+}
+
+fn pull_frames(pipeline: &gst::Element, appsink: &gst_app::AppSink) -> Result<((usize, usize), Vec<RamFrame>, f64)> {
+    let bus = pipeline.get_bus().context("Failed to find bus for video decoding pipeline")?;
+
+    let mut frames = Vec::new();
+    let mut size = (0, 0);
+    let mut frame_duration = 0.0;
+    let mut total_bytes = 0;
+    let mut last_frame = Instant::now();
+
+    loop {
+        let sample = match appsink.try_pull_sample(gst::ClockTime::from_mseconds(PULL_INTERVAL_MS)) {
+            Some(sample) => sample,
+            None if appsink.is_eos() => break,
+            None => {
+                if let Some(message) = bus.timed_pop_filtered(gst::ClockTime::from_seconds(0), &[gst::MessageType::Error]) {
+                    if let gst::MessageView::Error(err) = message.view() {
+                        bail!(
+                            "Decoding stopped before the end of the clip: {} ({})",
+                            err.get_error(),
+                            err.get_debug().unwrap_or_default()
+                        );
+                    }
+                }
+                if last_frame.elapsed() > DECODE_TIMEOUT {
+                    bail!("No frame was decoded for {} seconds", DECODE_TIMEOUT.as_secs());
+                }
+                continue;
+            }
+        };
+        last_frame = Instant::now();
+
+        let video_info = sample
+            .get_caps()
+            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+            .context("Decoded frame does not describe its format")?;
+        let buffer = sample.get_buffer().context("Decoded sample does not hold a frame")?;
+        let map = buffer.map_readable().context("Failed to map decoded frame")?;
+
+        let (width, height) = (video_info.width() as usize, video_info.height() as usize);
+        let fps = video_info.fps();
+        if *fps.numer() > 0 && *fps.denom() > 0 {
+            frame_duration = *fps.denom() as f64 / *fps.numer() as f64;
+        }
+
+        let mut data = Vec::new();
+        pack_rows(map.as_slice(), width * 3, height, video_info.stride()[0] as usize, &mut data);
+
+        total_bytes += data.len();
+        if total_bytes > MAX_RAM_CLIP_BYTES {
+            bail!("Clip does not fit in the {} bytes allowed for preloading", MAX_RAM_CLIP_BYTES);
+        }
+
+        // Timestamps are relative to the first frame, whatever the container starts at
+        let pts = buffer
+            .get_pts()
+            .nseconds()
+            .map(|pts| pts as f64 / 1_000_000_000.0)
+            .unwrap_or(frames.len() as f64 * frame_duration);
+
+        size = (width, height);
+        frames.push(RamFrame { pts, data });
+    }
+
+    if frames.is_empty() {
+        bail!("No frame could be decoded");
+    }
+
+    let first_pts = frames[0].pts;
+    for frame in frames.iter_mut() {
+        frame.pts -= first_pts;
+    }
+
+    Ok((size, frames, frame_duration))
+}
+
+impl InputProvider for RamClipProvider {
+    fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    fn provides(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            format!("{}_position", self.name),
+            format!("{}_duration", self.name),
+            format!("{}_progress", self.name),
+        ]
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        match (property, value) {
+            ("seek", DataHolder::Float(position)) => self.seek(*position as f64),
+            ("rate", DataHolder::Float(rate)) => self.set_rate(*rate as f64),
+            ("loop_mode", DataHolder::String(loop_mode)) => match LoopMode::from_str(loop_mode) {
+                Ok(loop_mode) => self.set_loop_mode(loop_mode),
+                Err(e) => error!("{:?}", e),
+            },
+            _ => warn!("Set_property unimplemented for {:}", property),
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        if uniform_name == self.name {
+            if !self.fresh_frame {
+                return None;
+            }
+
+            if invalidate {
+                self.fresh_frame = false;
+            }
+
+            let frame = &self.frames[self.current_frame.unwrap_or(0)];
+
+            Some(DataHolder::Texture((
+                (self.resolution.0 as u32, self.resolution.1 as u32),
+                frame.data.to_vec(),
+            )))
+        } else if uniform_name == format!("{}_position", self.name) {
+            Some(DataHolder::Float(self.position() as f32))
+        } else if uniform_name == format!("{}_duration", self.name) {
+            Some(DataHolder::Float(self.duration as f32))
+        } else if uniform_name == format!("{}_progress", self.name) {
+            if self.duration > 0.0 {
+                Some(DataHolder::Float((self.position() / self.duration) as f32))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    fn set_beat(&mut self, _beat: f64, _sync: bool) {}
+
+    fn set_time(&mut self, time: f64, _sync: bool) {
+        let elapsed = time - self.time;
+        self.time = time;
+
+        if !self.playing {
+            return;
+        }
+
+        self.playhead += elapsed * self.rate;
+        self.update_frame();
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.playing = false;
+        self.playhead = 0.0;
+        self.update_frame();
+
+        Ok(())
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.playing = true;
+
+        Ok(())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.playing = false;
+
+        Ok(())
+    }
+}
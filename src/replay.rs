@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::error;
+
+use gst::prelude::*;
+use gst::{Element, State};
+
+use crate::config::ReplayConfig;
+use crate::encoder::{make_element, make_video_encoder, BranchInput, FINALIZE_TIMEOUT};
+
+// Errors posted from within the bin only stop the replay, see VideoEncoder::check_bus
+pub(crate) const REPLAY_BIN_NAME: &str = "replay";
+
+// Encoded frames from the keyframe preceding the replay window on, so that they can be decoded on their own
+#[derive(Default)]
+struct ReplayBuffer {
+    caps: Option<gst::Caps>,
+    buffers: VecDeque<gst::Buffer>,
+}
+
+impl ReplayBuffer {
+    fn push(&mut self, caps: gst::Caps, buffer: gst::Buffer, duration: u64) {
+        self.caps = Some(caps);
+
+        let cutoff = buffer.get_pts().nseconds().map(|pts| pts.saturating_sub(duration));
+        self.buffers.push_back(buffer);
+
+        let cutoff = match cutoff {
+            Some(cutoff) => cutoff,
+            None => return,
+        };
+        if let Some(start) = self
+            .buffers
+            .iter()
+            .rposition(|buffer| is_keyframe(buffer) && buffer.get_pts().nseconds().map_or(false, |pts| pts <= cutoff))
+        {
+            self.buffers.drain(..start);
+        }
+    }
+}
+
+// Encodes the frames of the encoder tee next to its outputs, into memory
+pub(crate) struct Replay {
+    config: ReplayConfig,
+    buffer: Arc<Mutex<ReplayBuffer>>,
+    bin: Option<gst::Bin>,
+    tee_pad: Option<(Element, gst::Pad)>,
+    // Set from the streaming thread of a failing element, buffers stop reaching the bin from then on
+    failed: Arc<AtomicBool>,
+}
+
+impl Replay {
+    pub fn new(pipeline: &gst::Pipeline, video_tee: &Element, config: &ReplayConfig, input: BranchInput) -> Result<Self> {
+        let bin = gst::Bin::new(Some(REPLAY_BIN_NAME));
+
+        let queue = make_element("queue")?;
+        let (encoder_elements, _, _) = make_video_encoder(&config.output_config(), input, false)?;
+
+        let appsink = make_element("appsink")?;
+        appsink.set_property("sync", &false).unwrap();
+
+        let mut elements = vec![queue.clone()];
+        elements.extend(encoder_elements);
+        elements.push(appsink.clone());
+
+        let elements: Vec<&Element> = elements.iter().collect();
+        bin.add_many(&elements).unwrap();
+        Element::link_many(&elements).context("Failed to link the replay encoder")?;
+
+        let target = queue.get_static_pad("sink").unwrap();
+        let ghost_pad = gst::GhostPad::with_target(Some("video"), &target).context("Failed to expose the input of the replay")?;
+        bin.add_pad(&ghost_pad).unwrap();
+
+        let buffer = Arc::new(Mutex::new(ReplayBuffer::default()));
+        let duration = (config.duration.max(0.0) * 1_000_000_000.0) as u64;
+        {
+            let buffer = buffer.clone();
+            let appsink = appsink.dynamic_cast::<gst_app::AppSink>().unwrap();
+            appsink.set_callbacks(
+                gst_app::AppSinkCallbacks::builder()
+                    .new_sample(move |appsink| {
+                        let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                        if let (Some(caps), Some(encoded)) = (sample.get_caps(), sample.get_buffer()) {
+                            match buffer.lock() {
+                                Ok(mut buffer) => buffer.push(caps.to_owned(), encoded.to_owned(), duration),
+                                Err(_) => return Err(gst::FlowError::Error),
+                            }
+                        }
+
+                        Ok(gst::FlowSuccess::Ok)
+                    })
+                    .build(),
+            );
+        }
+
+        pipeline.add(&bin).unwrap();
+        let tee_pad = video_tee
+            .get_request_pad("src_%u")
+            .context("Failed to request a pad of the tee")?;
+
+        let failed = Arc::new(AtomicBool::new(false));
+        {
+            let failed = failed.clone();
+            tee_pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+                if failed.load(Ordering::SeqCst) {
+                    gst::PadProbeReturn::Drop
+                } else {
+                    gst::PadProbeReturn::Ok
+                }
+            });
+        }
+
+        tee_pad
+            .link(&ghost_pad)
+            .map_err(|e| anyhow!("Failed to link the replay encoder: {:?}", e))?;
+        bin.sync_state_with_parent().context("Failed to start the replay encoder")?;
+
+        Ok(Self {
+            config: config.clone(),
+            buffer,
+            bin: Some(bin),
+            tee_pad: Some((video_tee.clone(), tee_pad)),
+            failed,
+        })
+    }
+
+    pub fn failed(&self) -> Arc<AtomicBool> {
+        self.failed.clone()
+    }
+
+    // The frames buffered until then can still be saved
+    pub fn teardown(&mut self, pipeline: &gst::Pipeline) {
+        self.failed.store(true, Ordering::SeqCst);
+        if let Some((tee, tee_pad)) = self.tee_pad.take() {
+            tee.release_request_pad(&tee_pad);
+        }
+
+        if let Some(bin) = self.bin.take() {
+            if let Err(e) = bin.set_state(State::Null) {
+                error!("Failed to stop the replay encoder: {:?}", e);
+            }
+            if let Err(e) = pipeline.remove(&bin) {
+                error!("Failed to remove the replay encoder: {:?}", e);
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.bin.is_some()
+    }
+
+    // Muxes the buffered frames into `path`, the encoder keeps filling the buffer in the meantime
+    pub fn save(&self, path: &str) -> Result<()> {
+        let (caps, buffers) = {
+            let buffer = self.buffer.lock().map_err(|_| anyhow!("The replay buffer is poisoned"))?;
+            let start = buffer.buffers.iter().position(is_keyframe);
+            match (&buffer.caps, start) {
+                (Some(caps), Some(start)) => (caps.clone(), buffer.buffers.iter().skip(start).cloned().collect::<Vec<_>>()),
+                _ => bail!("No keyframe was encoded yet, there is nothing to replay"),
+            }
+        };
+
+        let container = self.config.container.unwrap_or_else(|| self.config.codec.default_container());
+        if !container.supports(self.config.codec) {
+            bail!("A {:?} container can not hold {:?} video", container, self.config.codec);
+        }
+
+        let pipeline = gst::Pipeline::new(None);
+        let appsrc = make_element("appsrc")?;
+        let mut elements = vec![appsrc.clone()];
+        // The parser converts the stream to the format the muxer expects, e.g. avc for MP4
+        if let Some(parser) = self.config.codec.parser_element() {
+            elements.push(make_element(parser)?);
+        }
+        elements.push(make_element(container.muxer_element())?);
+        let filesink = make_element("filesink")?;
+        filesink.set_property("location", &path).unwrap();
+        elements.push(filesink);
+
+        let elements: Vec<&Element> = elements.iter().collect();
+        pipeline.add_many(&elements).unwrap();
+        Element::link_many(&elements).context(format!("Failed to link the replay muxer for {:?}", path))?;
+
+        let appsrc = appsrc.dynamic_cast::<gst_app::AppSrc>().unwrap();
+        appsrc.set_caps(Some(&caps));
+        appsrc.set_property_format(gst::Format::Time);
+        appsrc.set_property_block(true);
+
+        pipeline
+            .set_state(State::Playing)
+            .context(format!("Failed to start saving the replay to {:?}", path))?;
+
+        let result = push_replay(&pipeline, &appsrc, buffers);
+        if let Err(e) = pipeline.set_state(State::Null) {
+            error!("Failed to stop saving the replay: {:?}", e);
+        }
+
+        result.context(format!("Failed to save the replay to {:?}", path))
+    }
+}
+
+// Replays start at the beginning of the file, whenever the frames were encoded
+fn push_replay(pipeline: &gst::Pipeline, appsrc: &gst_app::AppSrc, buffers: Vec<gst::Buffer>) -> Result<()> {
+    let start = buffers
+        .first()
+        .and_then(|buffer| buffer.get_dts().nseconds().or_else(|| buffer.get_pts().nseconds()))
+        .unwrap_or(0);
+
+    for mut buffer in buffers {
+        {
+            let buffer = buffer.make_mut();
+            let pts = shift_time(buffer.get_pts(), start);
+            let dts = shift_time(buffer.get_dts(), start);
+            buffer.set_pts(pts);
+            buffer.set_dts(dts);
+        }
+
+        appsrc
+            .push_buffer(buffer)
+            .map_err(|e| anyhow!("Failed to push a replayed frame: {:?}", e))?;
+    }
+    appsrc
+        .end_of_stream()
+        .map_err(|e| anyhow!("Failed to end the replay: {:?}", e))?;
+
+    let bus = pipeline.get_bus().context("Failed to find bus for the replay pipeline")?;
+    let message = bus.timed_pop_filtered(
+        gst::ClockTime::from_seconds(FINALIZE_TIMEOUT),
+        &[gst::MessageType::Eos, gst::MessageType::Error],
+    );
+    match message.as_ref().map(|message| message.view()) {
+        Some(gst::MessageView::Eos(_)) => Ok(()),
+        Some(gst::MessageView::Error(err)) => bail!("{} ({})", err.get_error(), err.get_debug().unwrap_or_default()),
+        _ => bail!("The muxer did not finish within {} seconds", FINALIZE_TIMEOUT),
+    }
+}
+
+fn shift_time(time: gst::ClockTime, start: u64) -> gst::ClockTime {
+    match time.nseconds() {
+        Some(time) => gst::ClockTime::from_nseconds(time.saturating_sub(start)),
+        None => time,
+    }
+}
+
+fn is_keyframe(buffer: &gst::Buffer) -> bool {
+    !buffer.get_flags().contains(gst::BufferFlags::DELTA_UNIT)
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::config::{AudioSource, EncoderConfig, VideoProviderConfig};
+use crate::encoder::{Container, Destination, VideoCodec};
+
+// Elements every VideoProvider pipeline is built from
+const PROVIDER_ELEMENTS: &[&str] = &[
+    "uridecodebin",
+    "decodebin",
+    "deinterlace",
+    "videoconvert",
+    "videocrop",
+    "aspectratiocrop",
+    "videoscale",
+    "capsfilter",
+    "videoflip",
+    "appsink",
+];
+
+// Only needed when the footage carries an audio track
+const AUDIO_ELEMENTS: &[&str] = &["audioconvert", "audioresample", "spectrum", "level", "volume"];
+
+// The encoder, parser and muxer of each output come on top, depending on the codec and container
+const ENCODER_ELEMENTS: &[&str] = &["appsrc", "queue", "videoflip", "videoconvert", "tee", "capsfilter"];
+
+// Distribution package shipping each element, as named by most Linux distributions and the gstreamer installers
+const ELEMENT_PACKAGES: &[(&str, &str)] = &[
+    ("uridecodebin", "gst-plugins-base"),
+    ("decodebin", "gst-plugins-base"),
+    ("videoconvert", "gst-plugins-base"),
+    ("videoscale", "gst-plugins-base"),
+    ("appsink", "gst-plugins-base"),
+    ("appsrc", "gst-plugins-base"),
+    ("audioconvert", "gst-plugins-base"),
+    ("audioresample", "gst-plugins-base"),
+    ("volume", "gst-plugins-base"),
+    ("deinterlace", "gst-plugins-good"),
+    ("videocrop", "gst-plugins-good"),
+    ("aspectratiocrop", "gst-plugins-good"),
+    ("videoflip", "gst-plugins-good"),
+    ("glvideoflip", "gst-plugins-base"),
+    ("glcolorconvert", "gst-plugins-base"),
+    ("gldownload", "gst-plugins-base"),
+    ("glupload", "gst-plugins-base"),
+    ("spectrum", "gst-plugins-good"),
+    ("level", "gst-plugins-good"),
+    ("matroskamux", "gst-plugins-good"),
+    ("mp4mux", "gst-plugins-good"),
+    ("webmmux", "gst-plugins-good"),
+    ("vp9enc", "gst-plugins-good"),
+    ("av1enc", "gst-plugins-bad"),
+    ("h264parse", "gst-plugins-bad"),
+    ("h265parse", "gst-plugins-bad"),
+    ("x264enc", "gst-plugins-ugly"),
+    ("x265enc", "gst-plugins-bad"),
+    ("qtmux", "gst-plugins-good"),
+    ("flvmux", "gst-plugins-good"),
+    ("rtmp2sink", "gst-plugins-bad"),
+    ("rtmpsink", "gst-plugins-bad"),
+    ("mpegtsmux", "gst-plugins-bad"),
+    ("srtsink", "gst-plugins-bad"),
+    ("ndisink", "gst-plugin-ndi"),
+    ("v4l2sink", "gst-plugins-good"),
+    ("shmsink", "gst-plugins-bad"),
+    ("splitmuxsink", "gst-plugins-good"),
+    ("udpsink", "gst-plugins-good"),
+    ("avenc_huffyuv", "gst-libav"),
+    ("avenc_prores_ks", "gst-libav"),
+    ("avenc_dnxhd", "gst-libav"),
+    ("avenc_hap", "gst-libav"),
+    ("avenc_aac", "gst-libav"),
+    ("aacparse", "gst-plugins-good"),
+    ("opusenc", "gst-plugins-base"),
+    ("pulsesrc", "gst-plugins-good"),
+    ("wasapisrc", "gst-plugins-bad"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissingElement {
+    pub element: String,
+    pub package: Option<&'static str>,
+}
+
+impl fmt::Display for MissingElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.package {
+            Some(package) => write!(f, "gstreamer element {:?} is missing, it is provided by {}", self.element, package),
+            None => write!(f, "gstreamer element {:?} is missing", self.element),
+        }
+    }
+}
+
+// Returns the elements a provider opened with `config` would need but that are not installed,
+// every option currently relies on the same elements
+pub fn missing_plugins_for(_config: &VideoProviderConfig) -> Result<Vec<MissingElement>> {
+    missing_elements(PROVIDER_ELEMENTS.iter().chain(AUDIO_ELEMENTS.iter()))
+}
+
+pub fn missing_encoder_plugins(config: &EncoderConfig) -> Result<Vec<MissingElement>> {
+    crate::init()?;
+
+    let mut elements = ENCODER_ELEMENTS.to_vec();
+    for output in config.outputs() {
+        let destination = Destination::parse(&output.path)?;
+        elements.push(destination.sink_element());
+        if output.is_segmented() {
+            elements.push("splitmuxsink");
+        }
+        if !destination.is_encoded() {
+            continue;
+        }
+
+        let container = output.container.unwrap_or_else(|| destination.default_container(output.codec));
+        elements.extend(encoder_elements(output.codec, container));
+        if let Some(audio) = &config.audio {
+            let codec = audio.codec.unwrap_or_else(|| container.default_audio_codec());
+            elements.push(codec.encoder_element());
+            elements.extend(codec.parser_element());
+        }
+    }
+
+    if let Some(replay) = &config.replay {
+        elements.extend(encoder_elements(replay.codec, replay.container.unwrap_or_else(|| replay.codec.default_container())));
+        elements.extend(&["appsink", "filesink"]);
+    }
+
+    if let Some(audio) = &config.audio {
+        elements.extend(&["audioconvert", "audioresample"]);
+
+        if audio.source != AudioSource::Pushed {
+            elements.push(if cfg!(target_os = "windows") {
+                "wasapisrc"
+            } else if cfg!(target_os = "linux") {
+                "pulsesrc"
+            } else {
+                "autoaudiosrc"
+            });
+        }
+    }
+
+    // Outputs share most of their elements
+    elements.sort_unstable();
+    elements.dedup();
+
+    missing_elements(elements.iter())
+}
+
+fn encoder_elements(codec: VideoCodec, container: Container) -> Vec<&'static str> {
+    let mut elements = ENCODER_ELEMENTS.to_vec();
+    elements.push(codec.encoder_element());
+    elements.extend(codec.parser_element());
+    elements.push(container.muxer_element());
+
+    elements
+}
+
+// Fails with a message naming every missing element and the package to install for it
+pub fn check_requirements() -> Result<()> {
+    let mut missing = missing_plugins_for(&VideoProviderConfig::default())?;
+    let codec = VideoCodec::default();
+    let mut elements = encoder_elements(codec, codec.default_container());
+    elements.push("filesink");
+    for element in missing_elements(elements.iter())? {
+        if !missing.contains(&element) {
+            missing.push(element);
+        }
+    }
+
+    if !missing.is_empty() {
+        let messages: Vec<String> = missing.iter().map(|element| element.to_string()).collect();
+        bail!("Missing gstreamer plugins:\n{}", messages.join("\n"));
+    }
+
+    Ok(())
+}
+
+fn missing_elements<'a>(elements: impl Iterator<Item = &'a &'static str>) -> Result<Vec<MissingElement>> {
+    crate::init()?;
+
+    Ok(elements
+        .filter(|element| gst::ElementFactory::find(element).is_none())
+        .map(|element| MissingElement {
+            element: (*element).to_owned(),
+            package: ELEMENT_PACKAGES
+                .iter()
+                .find(|(name, _)| name == element)
+                .map(|(_, package)| *package),
+        })
+        .collect())
+}
@@ -0,0 +1,144 @@
+use anyhow::Result;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::config::EncoderConfig;
+use crate::encoder::{EncodeError, VideoEncoder};
+use crate::stats::EncodeStats;
+use crate::video::quote_launch_value;
+use crate::video::LoopMode;
+use crate::video::Orientation;
+use crate::video::OutputFormat;
+use crate::video::PlaybackSpeed;
+use crate::video::RawVideoFormat;
+use crate::video::ReconnectPolicy;
+use crate::video::VideoProvider;
+
+// Receives raw frames written to a socket by shmsink, in another gstreamer process or any tool speaking its protocol
+pub struct ShmProvider {
+    video_provider: VideoProvider,
+}
+
+impl ShmProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        socket_path: &str,
+        format: &RawVideoFormat,
+        name: String,
+        resolution: impl Into<Option<(usize, usize)>>,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        // Frames do not carry their caps over shared memory, both sides have to agree on them
+        let source = format!(
+            "shmsrc socket-path=\"{}\" is-live=true do-timestamp=true ! {}",
+            quote_launch_value(socket_path),
+            format.caps()
+        );
+
+        let mut video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        // The writer may start after the provider or restart at any time
+        video_provider.set_reconnect_policy(Some(ReconnectPolicy::default()));
+
+        Ok(Self { video_provider })
+    }
+}
+
+impl InputProvider for ShmProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        self.video_provider.set_property(property, value);
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
+
+// Publishes rendered frames on a socket, readers connect with `shmsrc socket-path=...` and RGB caps of the same size,
+// RGBA ones for pixel formats with an alpha channel. Same as a VideoEncoder with a shm:// output
+pub struct ShmOutput {
+    encoder: VideoEncoder,
+}
+
+impl ShmOutput {
+    pub fn new(socket_path: &str, width: usize, height: usize, framerate: f64) -> Result<Self> {
+        Self::from_config(&EncoderConfig::new(&format!("shm://{}", socket_path), width, height, framerate))
+    }
+
+    // The path of the config is the shm:// URL of the socket, the codec settings do not apply
+    pub fn from_config(config: &EncoderConfig) -> Result<Self> {
+        Ok(Self {
+            encoder: VideoEncoder::from_config(config)?,
+        })
+    }
+
+    pub fn send_frame(&mut self, time: f64, frame: &[u8]) -> Result<(), EncodeError> {
+        self.encoder.encode_frame(time, frame)
+    }
+
+    pub fn send_frame_with_stride(&mut self, time: f64, frame: &[u8], stride: usize) -> Result<(), EncodeError> {
+        self.encoder.encode_frame_with_stride(time, frame, stride)
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.encoder.set_orientation(orientation)
+    }
+
+    pub fn stats(&self) -> EncodeStats {
+        self.encoder.stats()
+    }
+
+    pub fn stop(&mut self) {
+        self.encoder.stop()
+    }
+}
+
+impl Drop for ShmOutput {
+    fn drop(&mut self) {
+        self.encoder.stop();
+    }
+}
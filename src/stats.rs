@@ -0,0 +1,36 @@
+// Decoding statistics of a provider, to tell whether a stutter comes from the decoder or from the renderer
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DecodeStats {
+    // Frames delivered by the decoder per second, averaged over the last frames
+    pub decode_fps: f64,
+    // Seconds a decoded frame waits before being provided, averaged over the last frames
+    pub average_latency: f64,
+    pub decoded_frames: u64,
+    // Frames replaced by a newer one before being provided
+    pub dropped_frames: u64,
+    // Decoded frames waiting to be provided
+    pub queue_depth: usize,
+}
+
+impl DecodeStats {
+    // Layout of the <name>_stats uniform
+    pub fn to_vec(&self) -> Vec<f32> {
+        vec![
+            self.decode_fps as f32,
+            self.average_latency as f32,
+            self.dropped_frames as f32,
+            self.queue_depth as f32,
+        ]
+    }
+}
+
+// Frames given to a VideoEncoder, to tell whether the host has to lower its resolution or framerate
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EncodeStats {
+    // Frames waiting to reach the encoders
+    pub queue_depth: usize,
+    // Frames refused because the queue was full
+    pub dropped_frames: u64,
+    // Frames encode_frame had to wait for room in the queue for
+    pub blocked_frames: u64,
+}
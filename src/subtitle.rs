@@ -0,0 +1,182 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use log::error;
+
+use gst::prelude::*;
+
+use crate::frame_source::stream_time;
+use crate::video::{quote_launch_value, stream_index};
+
+const SIDECAR_EXTENSIONS: [&str; 4] = ["srt", "vtt", "ass", "ssa"];
+
+#[derive(Clone, Debug)]
+pub(crate) struct Cue {
+    pub text: String,
+    pub start: f64,
+    pub end: Option<f64>,
+}
+
+impl Cue {
+    pub fn is_active(&self, position: f64) -> bool {
+        position >= self.start && self.end.map(|end| position < end).unwrap_or(true)
+    }
+}
+
+// Embedded subtitles are only used when no sidecar file was found next to the video
+pub(crate) fn watch_subtitle_pads(pipeline: &gst::Element, decoder: &gst::Element, cue: Arc<Mutex<Option<Cue>>>) {
+    let pipeline = pipeline.downgrade();
+
+    decoder.connect_pad_added(move |decoder, pad| {
+        if stream_index(decoder, pad, "text/") != Some(0) {
+            return;
+        }
+
+        let pipeline = if let Some(pipeline) = pipeline.upgrade() {
+            pipeline
+        } else {
+            return;
+        };
+
+        let result = attach_subtitle_branch(&pipeline, "queue", cue.clone()).and_then(|branch| {
+            let sink_pad = branch
+                .get_static_pad("sink")
+                .context("Failed to find the input of the subtitle branch")?;
+
+            if sink_pad.is_linked() {
+                return Ok(());
+            }
+
+            pad.link(&sink_pad)
+                .map(|_| ())
+                .map_err(|e| anyhow!("Failed to link the subtitle stream: {:?}", e))
+        });
+
+        if let Err(e) = result {
+            error!("{:?}", e);
+        }
+    });
+}
+
+// Looks for a subtitle file sharing the name of the video, e.g. clip.srt for clip.mp4
+pub(crate) fn find_sidecar(path: &str) -> Option<String> {
+    let path = Path::new(path);
+
+    SIDECAR_EXTENSIONS
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|sidecar| sidecar.is_file())
+        .map(|sidecar| sidecar.to_string_lossy().into_owned())
+}
+
+pub(crate) fn attach_sidecar(pipeline: &gst::Element, sidecar: &str, cue: Arc<Mutex<Option<Cue>>>) -> Result<()> {
+    let source = format!("filesrc location=\"{}\" ! subparse ! queue", quote_launch_value(sidecar));
+
+    attach_subtitle_branch(pipeline, &source, cue)
+        .context(format!("Failed to load subtitles from {:?}", sidecar))?;
+
+    Ok(())
+}
+
+fn attach_subtitle_branch(pipeline: &gst::Element, source: &str, cue: Arc<Mutex<Option<Cue>>>) -> Result<gst::Element> {
+    let pipeline = pipeline
+        .clone()
+        .dynamic_cast::<gst::Bin>()
+        .expect("Failed to cast the gstreamer pipeline as a gst::Bin element");
+
+    // The branch is kept across restarts of the pipeline, only its link to the decoder has to be restored
+    if let Some(branch) = pipeline.get_by_name("subtitlebranch") {
+        return Ok(branch);
+    }
+
+    let description = format!(
+        "{} ! appsink name=subtitlesink caps=\"text/x-raw\" sync=true async=false",
+        source
+    );
+
+    let branch = gst::parse_bin_from_description(&description, true)
+        .context("Failed to build the subtitle branch of the video pipeline")?;
+    branch
+        .set_name("subtitlebranch")
+        .context("Failed to name the subtitle branch of the video pipeline")?;
+
+    let appsink = branch
+        .get_by_name("subtitlesink")
+        .context("Failed to retrieve the subtitle sink")?
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("The subtitle sink is not an appsink");
+
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+
+                if let Some(new_cue) = parse_cue(&sample) {
+                    if let Ok(mut cue) = cue.lock() {
+                        *cue = Some(new_cue);
+                    }
+                }
+
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+
+    pipeline
+        .add(&branch)
+        .context("Failed to add the subtitle branch to the video pipeline")?;
+    branch
+        .sync_state_with_parent()
+        .context("Failed to start the subtitle branch of the video pipeline")?;
+
+    Ok(branch.upcast())
+}
+
+fn parse_cue(sample: &gst::Sample) -> Option<Cue> {
+    let buffer = sample.get_buffer()?;
+    let map = buffer.map_readable().ok()?;
+
+    let is_markup = sample
+        .get_caps()
+        .and_then(|caps| {
+            caps.get_structure(0)
+                .and_then(|structure| structure.get::<String>("format").ok().flatten())
+        })
+        .map(|format| format == "pango-markup")
+        .unwrap_or(false);
+
+    let text = String::from_utf8_lossy(map.as_slice()).trim_end_matches('\0').to_string();
+    let text = if is_markup { strip_markup(&text) } else { text };
+
+    // Cues are timed against the position reported by the pipeline
+    let pts = buffer.get_pts();
+    let start = stream_time(sample, pts)?;
+    let end = buffer
+        .get_duration()
+        .nseconds()
+        .and_then(|_| stream_time(sample, pts + buffer.get_duration()));
+
+    Some(Cue { text, start, end })
+}
+
+fn strip_markup(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+
+    for character in text.chars() {
+        match character {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            character if !in_tag => stripped.push(character),
+            _ => (),
+        }
+    }
+
+    stripped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,185 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::video::PlaybackSpeed;
+
+// Used for relative speeds when the stream does not announce a frame rate
+const FALLBACK_NATIVE_FPS: f64 = 30.0;
+
+pub(crate) struct SyncState {
+    pub speed: PlaybackSpeed,
+    pub native_fps: Option<f64>,
+    // Length of the loop region, the whole clip without one
+    pub clip_duration: Option<f64>,
+    // Magnitude of the playback rate, gated frames come that much faster whatever their direction
+    pub rate: f64,
+    pub playing: bool,
+    pub stopped: bool,
+    pub frozen: bool,
+    interrupted: bool,
+    // The streaming thread holds back its next frame until it is due
+    waiting: bool,
+
+    pub beat: f64,
+    pub next_sync_beat: f64,
+
+    pub time: f64,
+    pub next_sync_time: f64,
+}
+
+impl SyncState {
+    fn frame_due(&self) -> bool {
+        match self.speed {
+            PlaybackSpeed::Fpb(_) | PlaybackSpeed::BeatsTotal(_) => self.beat > self.next_sync_beat,
+            PlaybackSpeed::Fps(_) | PlaybackSpeed::Factor(_) => self.time > self.next_sync_time,
+            // Frames are paced by the pipeline clock or by seeks instead
+            PlaybackSpeed::Native | PlaybackSpeed::RemapTime(_) | PlaybackSpeed::RemapBeats(_) => true,
+        }
+    }
+
+    fn advance(&mut self) {
+        match self.speed {
+            PlaybackSpeed::Fpb(frames_per_beat) => self.next_sync_beat += 1.0 / (frames_per_beat as f64 * self.rate),
+            PlaybackSpeed::Fps(frame_rate) => self.next_sync_time += 1.0 / (frame_rate as f64 * self.rate),
+            PlaybackSpeed::Factor(factor) => {
+                self.next_sync_time += 1.0 / (self.native_fps.unwrap_or(FALLBACK_NATIVE_FPS) * factor * self.rate)
+            }
+            // Clips locked to the beat grid keep to it whatever the rate
+            PlaybackSpeed::BeatsTotal(beats) => {
+                let native_fps = self.native_fps.unwrap_or(FALLBACK_NATIVE_FPS);
+
+                // Without a known duration frames advance at their native rate at 120 BPM
+                let frames_per_beat = match self.clip_duration {
+                    Some(clip_duration) if clip_duration > 0.0 && beats > 0.0 => native_fps * clip_duration / beats,
+                    _ => native_fps / 2.0,
+                };
+
+                self.next_sync_beat += 1.0 / frames_per_beat;
+            }
+            PlaybackSpeed::Native | PlaybackSpeed::RemapTime(_) | PlaybackSpeed::RemapBeats(_) => (),
+        }
+    }
+}
+
+// Shared between the render thread, which moves the clock forward, and the gstreamer
+// streaming thread, which sleeps until the next frame is due instead of polling
+pub(crate) struct FrameSync {
+    state: Mutex<SyncState>,
+    condvar: Condvar,
+}
+
+impl FrameSync {
+    pub fn new(speed: PlaybackSpeed, start_beat: f64, start_time: f64, start_playing: bool) -> Self {
+        Self {
+            state: Mutex::new(SyncState {
+                speed,
+                native_fps: None,
+                clip_duration: None,
+                rate: 1.0,
+                playing: start_playing,
+                stopped: false,
+                frozen: false,
+                interrupted: false,
+                waiting: false,
+                beat: start_beat,
+                next_sync_beat: start_beat,
+                time: start_time,
+                next_sync_time: start_time,
+            }),
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub fn lock(&self) -> Option<MutexGuard<SyncState>> {
+        self.state.lock().ok()
+    }
+
+    // Applies a change to the shared state and wakes up every waiting thread
+    pub fn update<F: FnOnce(&mut SyncState)>(&self, update: F) {
+        if let Ok(mut state) = self.state.lock() {
+            update(&mut state);
+            self.condvar.notify_all();
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.lock().map(|state| state.playing).unwrap_or(false)
+    }
+
+    // Prerolled frames are shown while paused, e.g. after a seek or a frame step, and for every seek of a remapped clip
+    pub fn shows_preroll(&self) -> bool {
+        self.lock()
+            .map(|state| !state.frozen && (!state.playing || state.speed.is_remap()))
+            .unwrap_or(false)
+    }
+
+    // Frames are not decoded meanwhile, without the decoder being stalled
+    pub fn is_waiting(&self) -> bool {
+        self.lock().map(|state| state.waiting).unwrap_or(false)
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.lock().map(|state| state.frozen).unwrap_or(false)
+    }
+
+    // Whether the streaming thread should stop waiting on the renderer
+    pub fn is_released(&self) -> bool {
+        self.lock()
+            .map(|state| state.stopped || state.interrupted || state.frozen || !state.playing)
+            .unwrap_or(true)
+    }
+
+    // Lets frames flow without gating while `action` runs, a flushing seek would otherwise wait on a blocked streaming thread
+    pub fn interrupt_waits<T, F: FnOnce() -> T>(&self, action: F) -> T {
+        self.update(|state| state.interrupted = true);
+        let result = action();
+        self.update(|state| state.interrupted = false);
+
+        result
+    }
+
+    // Blocks the streaming thread until the next frame is due, returns false if the main thread most likely crashed
+    pub fn wait_for_frame(&self) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+
+        loop {
+            if state.stopped || state.interrupted || state.frozen || !state.playing {
+                state.waiting = false;
+                return true;
+            }
+
+            if state.frame_due() {
+                state.waiting = false;
+                state.advance();
+                self.condvar.notify_all();
+                return true;
+            }
+
+            state.waiting = true;
+            state = match self.condvar.wait(state) {
+                Ok(state) => state,
+                Err(_) => return false,
+            };
+        }
+    }
+
+    // Waits at most `timeout` for the streaming thread to signal progress, returns false once `is_synced` holds
+    pub fn wait_for_sync<F: Fn(&SyncState) -> bool>(&self, is_synced: F, timeout: Duration) -> bool {
+        let state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+
+        if state.stopped || is_synced(&state) {
+            return false;
+        }
+
+        match self.condvar.wait_timeout(state, timeout) {
+            Ok((state, _)) => !(state.stopped || is_synced(&state)),
+            Err(_) => false,
+        }
+    }
+}
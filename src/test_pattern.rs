@@ -0,0 +1,97 @@
+use anyhow::Result;
+
+use gst::prelude::*;
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+use wvr_data::types::Speed;
+
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::VideoProvider;
+
+const SOURCE_NAME: &str = "testsrc";
+
+pub struct TestPatternProvider {
+    video_provider: VideoProvider,
+}
+
+impl TestPatternProvider {
+    pub fn new(
+        pattern: &str,
+        name: String,
+        resolution: (usize, usize),
+        speed: Speed,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        let source = format!(
+            "videotestsrc name={} pattern={} is-live=true",
+            SOURCE_NAME, pattern
+        );
+
+        let video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        Ok(Self { video_provider })
+    }
+
+    pub fn set_pattern(&mut self, pattern: &str) {
+        if let Some(source) = self.video_provider.get_element(SOURCE_NAME) {
+            source.set_property_from_str("pattern", pattern);
+        }
+    }
+}
+
+impl InputProvider for TestPatternProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides().into_iter().take(1).collect()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        match (property, value) {
+            ("pattern", DataHolder::String(pattern)) => self.set_pattern(pattern),
+            _ => self.video_provider.set_property(property, value),
+        }
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
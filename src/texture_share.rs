@@ -0,0 +1,105 @@
+use anyhow::{bail, Result};
+
+use wvr_data::types::DataHolder;
+use wvr_data::types::InputProvider;
+
+use crate::video::quote_launch_value;
+use crate::video::LoopMode;
+use crate::video::OutputFormat;
+use crate::video::PlaybackSpeed;
+use crate::video::ReconnectPolicy;
+use crate::video::VideoProvider;
+
+// Neither element ships with gstreamer, they come from the Spout and Syphon plugins installed alongside it
+#[cfg(target_os = "windows")]
+const SOURCE_ELEMENT: (&str, &str) = ("spoutsrc", "sender-name");
+#[cfg(target_os = "macos")]
+const SOURCE_ELEMENT: (&str, &str) = ("syphonsrc", "server-name");
+
+// Receives the frames another application shares over Spout (Windows) or Syphon (macOS)
+pub struct TextureShareProvider {
+    video_provider: VideoProvider,
+}
+
+impl TextureShareProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sender: Option<&str>,
+        name: String,
+        resolution: impl Into<Option<(usize, usize)>>,
+        speed: impl Into<PlaybackSpeed>,
+        start_beat: f64,
+        start_time: f64,
+        start_playing: bool,
+    ) -> Result<Self> {
+        crate::init()?;
+
+        let (element, sender_property) = SOURCE_ELEMENT;
+        if gst::ElementFactory::find(element).is_none() {
+            bail!("Texture sharing needs the gstreamer plugin providing the {:?} element", element);
+        }
+
+        // Without a sender name the first one found is received
+        let mut source = element.to_owned();
+        if let Some(sender) = sender {
+            source.push_str(&format!(" {}=\"{}\"", sender_property, quote_launch_value(sender)));
+        }
+
+        let mut video_provider = VideoProvider::from_source(
+            &source,
+            name,
+            resolution,
+            speed,
+            start_beat,
+            start_time,
+            start_playing,
+            LoopMode::Hold,
+            None,
+            None,
+            OutputFormat::default(),
+        )?;
+
+        // Senders come and go, e.g. when the other application reloads its composition
+        video_provider.set_reconnect_policy(Some(ReconnectPolicy::default()));
+
+        Ok(Self { video_provider })
+    }
+}
+
+impl InputProvider for TextureShareProvider {
+    fn set_name(&mut self, name: &str) {
+        self.video_provider.set_name(name);
+    }
+
+    fn provides(&self) -> Vec<String> {
+        self.video_provider.provides()
+    }
+
+    fn set_property(&mut self, property: &str, value: &DataHolder) {
+        self.video_provider.set_property(property, value);
+    }
+
+    fn get(&mut self, uniform_name: &str, invalidate: bool) -> Option<DataHolder> {
+        self.video_provider.get(uniform_name, invalidate)
+    }
+
+    fn set_beat(&mut self, beat: f64, sync: bool) {
+        self.video_provider.set_beat(beat, sync);
+    }
+
+    fn set_time(&mut self, time: f64, sync: bool) {
+        self.video_provider.set_time(time, sync);
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.video_provider.stop()
+    }
+
+    fn play(&mut self) -> Result<()> {
+        self.video_provider.play()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.video_provider.pause()
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use wvr_data::types::DataHolder;
+
+type Texture = ((u32, u32), Vec<u8>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TransitionDuration {
+    Seconds(f64),
+    Beats(f64),
+}
+
+pub(crate) struct Crossfade {
+    duration: TransitionDuration,
+    start: f64,
+
+    outgoing_frame: Option<Texture>,
+    incoming_frame: Option<Texture>,
+}
+
+impl Crossfade {
+    pub fn new(duration: TransitionDuration, beat: f64, time: f64) -> Self {
+        let start = match duration {
+            TransitionDuration::Seconds(_) => time,
+            TransitionDuration::Beats(_) => beat,
+        };
+
+        Self {
+            duration,
+            start,
+            outgoing_frame: None,
+            incoming_frame: None,
+        }
+    }
+
+    pub fn progress(&self, beat: f64, time: f64) -> f64 {
+        let (elapsed, duration) = match self.duration {
+            TransitionDuration::Seconds(duration) => (time - self.start, duration),
+            TransitionDuration::Beats(duration) => (beat - self.start, duration),
+        };
+
+        if duration <= 0.0 {
+            1.0
+        } else {
+            (elapsed / duration).max(0.0).min(1.0)
+        }
+    }
+
+    // Both clips only hand out new frames when they decode one, so the latest frame of each is kept around for blending
+    pub fn blend(
+        &mut self,
+        outgoing: Option<DataHolder>,
+        incoming: Option<DataHolder>,
+        progress: f64,
+    ) -> Option<DataHolder> {
+        if let Some(DataHolder::Texture(texture)) = outgoing {
+            self.outgoing_frame = Some(texture);
+        }
+        if let Some(DataHolder::Texture(texture)) = incoming {
+            self.incoming_frame = Some(texture);
+        }
+
+        match (&self.outgoing_frame, &self.incoming_frame) {
+            (Some((outgoing_size, outgoing_data)), Some((incoming_size, incoming_data)))
+                if outgoing_size == incoming_size && outgoing_data.len() == incoming_data.len() =>
+            {
+                let incoming_weight = progress as f32;
+                let outgoing_weight = 1.0 - incoming_weight;
+
+                let data = outgoing_data
+                    .iter()
+                    .zip(incoming_data.iter())
+                    .map(|(outgoing, incoming)| {
+                        (*outgoing as f32 * outgoing_weight + *incoming as f32 * incoming_weight)
+                            .round() as u8
+                    })
+                    .collect();
+
+                Some(DataHolder::Texture((*incoming_size, data)))
+            }
+            (_, Some(incoming)) => Some(DataHolder::Texture(incoming.clone())),
+            (Some(outgoing), None) => Some(DataHolder::Texture(outgoing.clone())),
+            (None, None) => None,
+        }
+    }
+}
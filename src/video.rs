@@ -1,293 +1,2386 @@
-use std::sync::Arc;
-use std::sync::Mutex;
-use std::thread;
-use std::time::Duration;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{error, warn};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
 use url::Url;
-use image::DynamicImage;
 
 use gst::prelude::*;
-use gst::FlowError;
 use gst::State;
 
 use wvr_data::types::Speed;
-use wvr_data::types::Buffer;
 use wvr_data::types::DataHolder;
 use wvr_data::types::InputProvider;
 
-type BgrImage = image::ImageBuffer<image::Bgr<u8>, Vec<u8>>;
-type BgraImage = image::ImageBuffer<image::Bgra<u8>, Vec<u8>>;
+use crate::audio::{parse_rms, parse_spectrum, watch_audio_pads, AudioSettings};
+use crate::config::{VideoConfig, VideoProviderConfig};
+use crate::events::{EventSenders, ProviderEvent};
+use crate::frame_queue::FrameQueue;
+use crate::frame_source::GstFrameSource;
+use crate::logging::write_pipeline_dot;
+use crate::media_info::discover;
+use crate::stats::DecodeStats;
+use crate::subtitle::{attach_sidecar, find_sidecar, watch_subtitle_pads, Cue};
+use crate::sync::{FrameSync, SyncState};
 
 pub enum TextureFormat {
     RGBU8,
     RGBAU8,
     BGRU8,
     BGRAU8,
+    GRAYU8,
+    GRAYU16,
+    ARGBU16,
+}
+
+impl TextureFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::RGBU8 | TextureFormat::BGRU8 => 3,
+            TextureFormat::RGBAU8 | TextureFormat::BGRAU8 => 4,
+            TextureFormat::GRAYU8 => 1,
+            TextureFormat::GRAYU16 => 2,
+            TextureFormat::ARGBU16 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OutputFormat {
+    Rgb8,
+    // Keeps the alpha channel of the source, the texture data then holds 4 bytes per pixel
+    Rgba8,
+    // Single channel luma, useful for masks and depth maps
+    Gray8,
+    // Single channel luma stored as little endian 16 bits values
+    Gray16,
+    // 16 bits per channel, stored as little endian values, for high bit depth sources such as P010
+    Rgba16,
+    // Planar YUV frames are left for the shaders to convert, see `pack_planes` for the texture layout
+    I420,
+    Nv12,
+}
+
+impl OutputFormat {
+    pub fn channels(&self) -> usize {
+        match self {
+            OutputFormat::Rgb8 => 3,
+            OutputFormat::Rgba8 | OutputFormat::Rgba16 => 4,
+            OutputFormat::Gray8 | OutputFormat::Gray16 => 1,
+            OutputFormat::I420 | OutputFormat::Nv12 => 1,
+        }
+    }
+
+    fn caps_format(&self) -> &'static str {
+        match self {
+            // Letting videoconvert produce the final layout avoids converting frames a second time on the CPU
+            OutputFormat::Rgb8 => "format=RGB",
+            OutputFormat::Rgba8 => "format=RGBA",
+            OutputFormat::Gray8 => "format=GRAY8",
+            OutputFormat::Gray16 => "format=GRAY16_LE",
+            OutputFormat::Rgba16 => "format=ARGB64",
+            OutputFormat::I420 => "format=I420",
+            OutputFormat::Nv12 => "format=NV12",
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            OutputFormat::Rgb8 => 3,
+            OutputFormat::Rgba8 => 4,
+            OutputFormat::Gray8 => 1,
+            OutputFormat::Gray16 => 2,
+            OutputFormat::Rgba16 => 8,
+            OutputFormat::I420 | OutputFormat::Nv12 => 1,
+        }
+    }
+
+    pub fn is_planar(&self) -> bool {
+        matches!(self, OutputFormat::I420 | OutputFormat::Nv12)
+    }
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Rgb8
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HdrTransfer {
+    Sdr,
+    // SMPTE ST 2084, also known as PQ
+    Pq,
+    // ARIB STD-B67, also known as HLG
+    Hlg,
+}
+
+impl HdrTransfer {
+    // Parses the colorimetry field of a caps structure, either as a named colorimetry or as "range:matrix:transfer:primaries"
+    fn from_colorimetry(colorimetry: &str) -> Self {
+        let colorimetry = colorimetry.to_lowercase();
+        let transfer = colorimetry.split(':').nth(2);
+
+        if colorimetry.contains("pq") || colorimetry.contains("smpte2084") || transfer == Some("14") {
+            HdrTransfer::Pq
+        } else if colorimetry.contains("hlg") || colorimetry.contains("arib-std-b67") || transfer == Some("15") {
+            HdrTransfer::Hlg
+        } else {
+            HdrTransfer::Sdr
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PlaybackSpeed {
+    // Frames per beat
+    Fpb(f32),
+    // Frames per second
+    Fps(f32),
+    // Multiple of the native frame rate of the footage
+    Factor(f64),
+    // Frames are delivered at the cadence of the footage against the gstreamer clock, without beat or time gating
+    Native,
+    // The whole clip lasts this many beats, whatever the tempo
+    BeatsTotal(f64),
+    // The position shown is a function of the time given to set_time, in seconds of footage per second
+    RemapTime(f64),
+    // The position shown is a function of the beat given to set_beat, in seconds of footage per beat
+    RemapBeats(f64),
+}
+
+impl PlaybackSpeed {
+    pub fn is_beat_based(&self) -> bool {
+        matches!(self, PlaybackSpeed::Fpb(_) | PlaybackSpeed::BeatsTotal(_))
+    }
+
+    pub fn is_time_based(&self) -> bool {
+        matches!(self, PlaybackSpeed::Fps(_) | PlaybackSpeed::Factor(_))
+    }
+
+    pub fn is_remap(&self) -> bool {
+        matches!(self, PlaybackSpeed::RemapTime(_) | PlaybackSpeed::RemapBeats(_))
+    }
+
+    // Gated speeds have to be positive, frames would never or always be due otherwise.
+    // Reverse playback goes through set_rate instead
+    pub fn check(&self) -> Result<()> {
+        let value = match *self {
+            PlaybackSpeed::Fpb(value) | PlaybackSpeed::Fps(value) => value as f64,
+            PlaybackSpeed::Factor(value) | PlaybackSpeed::BeatsTotal(value) => value,
+            PlaybackSpeed::Native | PlaybackSpeed::RemapTime(_) | PlaybackSpeed::RemapBeats(_) => return Ok(()),
+        };
+
+        if value <= 0.0 || !value.is_finite() {
+            bail!("Playback speed {:?} has to be positive", self);
+        }
+
+        Ok(())
+    }
+}
+
+impl From<Speed> for PlaybackSpeed {
+    fn from(speed: Speed) -> Self {
+        match speed {
+            Speed::Fpb(frames_per_beat) => PlaybackSpeed::Fpb(frames_per_beat),
+            Speed::Fps(frame_rate) => PlaybackSpeed::Fps(frame_rate),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LoopMode {
+    Loop,
+    PingPong,
+    Once,
+    Hold,
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::Loop
+    }
+}
+
+impl FromStr for LoopMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode.to_lowercase().as_str() {
+            "loop" => Ok(LoopMode::Loop),
+            "pingpong" | "ping_pong" | "ping-pong" => Ok(LoopMode::PingPong),
+            "once" => Ok(LoopMode::Once),
+            "hold" => Ok(LoopMode::Hold),
+            _ => Err(anyhow::anyhow!("Unknown loop mode {:?}", mode)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Orientation {
+    None,
+    VerticalFlip,
+    HorizontalFlip,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    // Follows the orientation tag of the footage, e.g. for phone recordings
+    Auto,
+}
+
+impl Orientation {
+    pub(crate) fn videoflip_method(self) -> &'static str {
+        match self {
+            Orientation::None => "none",
+            Orientation::VerticalFlip => "vertical-flip",
+            Orientation::HorizontalFlip => "horizontal-flip",
+            Orientation::Rotate90 => "clockwise",
+            Orientation::Rotate180 => "rotate-180",
+            Orientation::Rotate270 => "counterclockwise",
+            Orientation::Auto => "auto",
+        }
+    }
+}
+
+// Textures are uploaded bottom row first, hence the vertical flip
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::VerticalFlip
+    }
+}
+
+impl FromStr for Orientation {
+    type Err = anyhow::Error;
+
+    fn from_str(orientation: &str) -> Result<Self> {
+        match orientation.to_lowercase().as_str() {
+            "none" => Ok(Orientation::None),
+            "vflip" | "vertical-flip" => Ok(Orientation::VerticalFlip),
+            "hflip" | "horizontal-flip" => Ok(Orientation::HorizontalFlip),
+            "90" | "rotate90" => Ok(Orientation::Rotate90),
+            "180" | "rotate180" => Ok(Orientation::Rotate180),
+            "270" | "rotate270" => Ok(Orientation::Rotate270),
+            "auto" => Ok(Orientation::Auto),
+            _ => Err(anyhow::anyhow!("Unknown orientation {:?}", orientation)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FitMode {
+    // The frame is scaled to the requested resolution regardless of its aspect ratio
+    Stretch,
+    // The whole frame is kept, black borders fill the remaining space
+    Letterbox,
+    // The frame fills the requested resolution, what does not fit is cropped away
+    Crop,
+}
+
+impl Default for FitMode {
+    fn default() -> Self {
+        FitMode::Stretch
+    }
+}
+
+impl FromStr for FitMode {
+    type Err = anyhow::Error;
+
+    fn from_str(fit_mode: &str) -> Result<Self> {
+        match fit_mode.to_lowercase().as_str() {
+            "stretch" => Ok(FitMode::Stretch),
+            "letterbox" => Ok(FitMode::Letterbox),
+            "crop" | "fill" => Ok(FitMode::Crop),
+            _ => Err(anyhow::anyhow!("Unknown fit mode {:?}", fit_mode)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CropRegion {
+    // x, y, width and height in pixels of the decoded frame
+    Pixels(usize, usize, usize, usize),
+    // x, y, width and height relative to the size of the decoded frame
+    Normalized(f64, f64, f64, f64),
+}
+
+impl CropRegion {
+    // Margins to remove from the left, top, right and bottom of a frame of the given size
+    fn margins(self, frame_size: (usize, usize)) -> (i32, i32, i32, i32) {
+        let (x, y, width, height) = match self {
+            CropRegion::Pixels(x, y, width, height) => (x, y, width, height),
+            CropRegion::Normalized(x, y, width, height) => (
+                (x.max(0.0) * frame_size.0 as f64) as usize,
+                (y.max(0.0) * frame_size.1 as f64) as usize,
+                (width.max(0.0) * frame_size.0 as f64) as usize,
+                (height.max(0.0) * frame_size.1 as f64) as usize,
+            ),
+        };
+
+        let x = x.min(frame_size.0.saturating_sub(1));
+        let y = y.min(frame_size.1.saturating_sub(1));
+        let width = width.max(1).min(frame_size.0 - x);
+        let height = height.max(1).min(frame_size.1 - y);
+
+        (
+            x as i32,
+            y as i32,
+            (frame_size.0 - x - width) as i32,
+            (frame_size.1 - y - height) as i32,
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeinterlaceMethod {
+    Linear,
+    Vfir,
+    GreedyLow,
+    GreedyHigh,
+    Yadif,
+}
+
+impl DeinterlaceMethod {
+    fn property_value(self) -> &'static str {
+        match self {
+            DeinterlaceMethod::Linear => "linear",
+            DeinterlaceMethod::Vfir => "vfir",
+            DeinterlaceMethod::GreedyLow => "greedyl",
+            DeinterlaceMethod::GreedyHigh => "greedyh",
+            DeinterlaceMethod::Yadif => "yadif",
+        }
+    }
+}
+
+impl FromStr for DeinterlaceMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(method: &str) -> Result<Self> {
+        match method.to_lowercase().as_str() {
+            "linear" => Ok(DeinterlaceMethod::Linear),
+            "vfir" => Ok(DeinterlaceMethod::Vfir),
+            "greedyl" | "greedy_low" => Ok(DeinterlaceMethod::GreedyLow),
+            "greedyh" | "greedy_high" => Ok(DeinterlaceMethod::GreedyHigh),
+            "yadif" => Ok(DeinterlaceMethod::Yadif),
+            _ => Err(anyhow::anyhow!("Unknown deinterlacing method {:?}", method)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FramePacing {
+    // Decoded frames always replace the previous one, frames the renderer never got are counted as dropped
+    DropToLatest,
+    // The decoder holds each frame until the renderer got it
+    WaitForConsumer,
+}
+
+impl Default for FramePacing {
+    fn default() -> Self {
+        FramePacing::DropToLatest
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LatePolicy {
+    // Once the wait times out the decoder keeps delivering every frame it is late on
+    CatchUp,
+    // Once the wait times out the frames the decoder is late on are skipped
+    Skip,
+}
+
+impl Default for LatePolicy {
+    fn default() -> Self {
+        LatePolicy::CatchUp
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ReconnectPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    // Retries stop once this many consecutive attempts failed, None retries forever
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+pub const DEFAULT_RTSP_LATENCY: u32 = 200;
+pub const DEFAULT_SRT_LATENCY: u32 = 125;
+pub const DEFAULT_STREAM_BUFFER_DURATION: f64 = 5.0;
+
+// Messages name the provider and the state of its pipeline, hosts route and filter them through the log crate
+macro_rules! provider_log {
+    ($level:ident, $provider:expr, $($arg:tt)+) => {
+        log::$level!("{} ({:?}): {}", $provider.name, $provider.current_state(), format_args!($($arg)+))
+    };
+}
+
+// Upper bound between two bus checks while waiting for the decoder in set_beat / set_time
+const SYNC_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// Drift in frames from the beat grid after which a clip stretched over a number of beats is seeked back in place
+const BEAT_GRID_TOLERANCE: f64 = 2.0;
+
+// Minimum delay between two corrections of the drift from the beat grid
+const BEAT_GRID_CORRECTION_INTERVAL: Duration = Duration::from_secs(1);
+
+// Distance in seconds kept from the end of the seekable range when following a live stream
+const LIVE_EDGE_OFFSET: f64 = 1.0;
+
+const BYTE_SOURCE_NAME: &str = "bytesource";
+const BYTE_SOURCE_CHUNK_SIZE: usize = 64 * 1024;
+
+// Renderers write files in several steps, the clip is only reloaded once they are done
+const FILE_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Layout of uncompressed frames exchanged with other processes, `format` is a gstreamer format name such as "rgb" or "i420"
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawVideoFormat {
+    pub width: usize,
+    pub height: usize,
+    pub format: String,
+    pub framerate: (i32, i32),
+}
+
+impl RawVideoFormat {
+    pub(crate) fn caps(&self) -> String {
+        format!(
+            "video/x-raw,format={},width={},height={},framerate={}/{}",
+            self.format.to_uppercase(), self.width, self.height, self.framerate.0, self.framerate.1
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RandomSlice {
+    // Number of beats each slice plays for
+    pub every: f64,
+    // The same seed jumps to the same positions on the same beats
+    pub seed: u64,
 }
 
 pub struct VideoProvider {
     name: String,
-    video_buffer: Arc<Mutex<Buffer>>,
+    frame_queue: Arc<FrameQueue>,
     pipeline: gst::Element,
 
-    playing_lock: Arc<AtomicBool>,
-    stop_lock: Arc<AtomicBool>,
+    sync: Arc<FrameSync>,
+    max_sync_wait: Option<Duration>,
+    late_policy: LatePolicy,
+
+    rate: f64,
+    loop_mode: LoopMode,
+    loop_start: Option<f64>,
+    loop_end: Option<f64>,
+    pending_seek: Option<f64>,
+
+    reconnect_policy: Option<ReconnectPolicy>,
+    restart_policy: Option<ReconnectPolicy>,
+    reconnect_attempts: u32,
+    next_reconnect: Option<Instant>,
+    last_reconnect: Option<Instant>,
+    resume_position: Option<f64>,
+
+    buffering_percent: i32,
+    follow_live_edge: bool,
+    ended: bool,
+    resolution: Option<(usize, usize)>,
+    fit_mode: FitMode,
+    crop: Option<CropRegion>,
+    output_format: OutputFormat,
+    last_error: Arc<Mutex<Option<String>>>,
+
+    audio_settings: Arc<AudioSettings>,
+    video_track: Arc<AtomicUsize>,
+    audio_spectrum: Vec<f32>,
+    audio_rms: f32,
+    subtitle_cue: Arc<Mutex<Option<Cue>>>,
+    events: EventSenders,
+    remap_position: Option<f64>,
+    pending_retrigger: Option<f64>,
+    source_path: Option<String>,
+    // Along with the canonical path of the watched file
+    file_watcher: Option<(RecommendedWatcher, Receiver<DebouncedEvent>, PathBuf)>,
+    random_slice: Option<RandomSlice>,
+    slice_index: Option<i64>,
+    beat_origin: f64,
+    last_grid_correction: Option<Instant>,
+    frame_pts: Option<f64>,
+    frame_index: Option<u64>,
+    stats_uniform: bool,
+    stall_timeout: Option<Duration>,
+    // Frame count last seen moving and when, while the pipeline is expected to deliver frames
+    stall_watch: Option<(u64, Instant)>,
+    stalled: bool,
+    // The wrapped context of the host, for providers decoding into GL textures
+    #[cfg(feature = "gl")]
+    gl_context: Option<gst_gl::GLContext>,
+}
+
+impl VideoProvider {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(path: &str, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat) -> Result<Self> {
+        Self::open(path, name, resolution, speed, start_beat, start_time, start_playing, loop_mode, loop_start, loop_end, output_format, true)
+    }
+
+    // Probing a file reads it, the providers started in the background leave it to start_in_background
+    #[allow(clippy::too_many_arguments)]
+    fn open(path: &str, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat, probe: bool) -> Result<Self> {
+        // Without a resolution frames keep the native size of the footage
+        let resolution: Option<(usize, usize)> = resolution.into();
+        let speed: PlaybackSpeed = speed.into();
+
+        if path.starts_with("rtsp://") || path.starts_with("rtsps://") {
+            return Self::new_rtsp(path, DEFAULT_RTSP_LATENCY, name, resolution, speed, start_beat, start_time, start_playing, output_format);
+        }
+        if is_adaptive_stream(path) {
+            return Self::new_stream(path, DEFAULT_STREAM_BUFFER_DURATION, true, name, resolution, speed, start_beat, start_time, start_playing, output_format);
+        }
+        if path.starts_with("srt://") {
+            return Self::new_srt(path, DEFAULT_SRT_LATENCY, None, name, resolution, speed, start_beat, start_time, start_playing, output_format);
+        }
+
+        let uri = path_to_uri(path)?;
+
+        validate_uri(&uri, probe)?;
+
+        let mut provider = Self::from_source(&format!("uridecodebin name=decoder uri=\"{}\"", quote_launch_value(&uri)), name, resolution, speed, start_beat, start_time, start_playing, loop_mode, loop_start, loop_end, output_format)?;
+        provider.source_path = Some(path.to_owned());
+
+        if let Some(sidecar) = find_sidecar(path) {
+            if let Err(e) = attach_sidecar(&provider.pipeline, &sidecar, provider.subtitle_cue.clone()) {
+                provider_log!(error, provider, "{:?}", e);
+            }
+        }
+
+        Ok(provider)
+    }
+
+    pub fn from_config(name: String, config: &VideoConfig) -> Result<Self> {
+        Self::with_config(&config.path, name, &config.provider)
+    }
+
+    pub fn with_config(path: &str, name: String, config: &VideoProviderConfig) -> Result<Self> {
+        let mut provider = Self::open(path, name, config.resolution, config.speed, config.start_beat, config.start_time, false, config.loop_mode, config.loop_start, config.loop_end, config.output_format, !config.async_start)?;
+
+        // Decoders are picked while prerolling, the preference has to be known before the pipeline starts
+        if !config.hardware_decode {
+            if let Some(decoder) = provider.get_element("decoder") {
+                skip_hardware_decoders(&decoder)?;
+            }
+        }
+
+        if config.orientation != Orientation::default() {
+            provider.set_orientation(config.orientation)?;
+        }
+        if config.fit_mode != FitMode::default() {
+            provider.set_fit_mode(config.fit_mode)?;
+        }
+        if config.deinterlace.is_some() {
+            provider.set_deinterlace(config.deinterlace)?;
+        }
+
+        if config.async_start {
+            provider.start_in_background(config.start_playing);
+        } else if config.start_playing {
+            provider.play()?;
+        }
+
+        Ok(provider)
+    }
+
+    // Returns as soon as the pipeline is built, the slow part of opening the source and prerolling
+    // happens on a background thread and can be followed through is_ready or the <name>_ready uniform
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_async(path: &str, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat) -> Result<Self> {
+        let provider = Self::open(path, name, resolution, speed, start_beat, start_time, false, loop_mode, loop_start, loop_end, output_format, false)?;
+        provider.start_in_background(start_playing);
+
+        Ok(provider)
+    }
+
+    // Frames are decoded into GL textures shared with the context current on the calling thread and taken with
+    // take_gl_texture, they never go through system memory. Unsafe since gstreamer takes the context as it is,
+    // it has to stay alive for as long as the provider
+    #[cfg(feature = "gl")]
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn new_gl(path: &str, name: String, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, platform: gst_gl::GLPlatform, api: gst_gl::GLAPI) -> Result<Self> {
+        crate::init()?;
+        crate::gl_source::check_gl_elements()?;
+
+        let speed: PlaybackSpeed = speed.into();
+        let uri = path_to_uri(path)?;
+        validate_uri(&uri, true)?;
+
+        let pipeline_string = format!(
+            "uridecodebin name=decoder uri=\"{}\" ! {} ! appsink name=appsink async=false sync={}",
+            quote_launch_value(&uri), crate::gl_source::GL_SINK, speed == PlaybackSpeed::Native,
+        );
+        let mut provider = Self::from_launch(&pipeline_string, "appsink", name, None, speed, start_beat, start_time, false, loop_mode, loop_start, loop_end, OutputFormat::Rgba8)?;
+        provider.source_path = Some(path.to_owned());
+
+        // Set before the GL elements look for a context, they create ones sharing the textures with the host
+        let (context, contexts) = crate::gl_texture::wrap_current_context(platform, api)?;
+        for gst_context in &contexts {
+            provider.pipeline.set_context(gst_context);
+        }
+        provider.gl_context = Some(context);
+
+        if start_playing {
+            provider.play()?;
+        }
+
+        Ok(provider)
+    }
+
+    // The latest frame of a provider made with new_gl, its context has to be current on the calling thread
+    #[cfg(feature = "gl")]
+    pub fn take_gl_texture(&mut self) -> Option<crate::gl_source::GlTextureFrame> {
+        self.check_loop();
+
+        let context = self.gl_context.clone()?;
+        if self.sync.is_frozen() {
+            return None;
+        }
+
+        let frame = self.frame_queue.take()?;
+        self.frame_pts = frame.pts;
+        self.frame_index = Some(frame.index);
+
+        crate::gl_source::texture_frame(frame, &context)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_rtsp(url: &str, latency: u32, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, output_format: OutputFormat) -> Result<Self> {
+        let source = format!("rtspsrc name=source location=\"{}\" latency={} ! decodebin name=decoder", quote_launch_value(url), latency);
+
+        let mut provider = Self::from_source(&source, name, resolution, speed, start_beat, start_time, start_playing, LoopMode::Hold, None, None, output_format)?;
+        provider.set_reconnect_policy(Some(ReconnectPolicy::default()));
+
+        // rtspsrc exposes its streams again on every reconnection
+        if let (Some(rtspsrc), Some(decoder)) = (provider.get_element("source"), provider.get_element("decoder")) {
+            let decoder = decoder.downgrade();
+            rtspsrc.connect_pad_added(move |_, pad| {
+                let is_video = pad
+                    .get_current_caps()
+                    .unwrap_or_else(|| pad.query_caps(None))
+                    .get_structure(0)
+                    .and_then(|structure| structure.get::<String>("media").ok().flatten())
+                    .map(|media| media == "video")
+                    .unwrap_or(false);
+
+                let sink_pad = decoder.upgrade().and_then(|decoder| decoder.get_static_pad("sink"));
+                if let Some(sink_pad) = sink_pad {
+                    if is_video && !sink_pad.is_linked() {
+                        if let Err(e) = pad.link(&sink_pad) {
+                            error!("Failed to link the RTSP video stream: {:?}", e);
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(provider)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_srt(url: &str, latency: u32, passphrase: Option<&str>, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, output_format: OutputFormat) -> Result<Self> {
+        let mut source = format!("srtsrc uri=\"{}\" latency={}", quote_launch_value(url), latency);
+        if let Some(passphrase) = passphrase {
+            source.push_str(&format!(" passphrase=\"{}\"", quote_launch_value(passphrase)));
+        }
+        source.push_str(" ! decodebin name=decoder");
+
+        let mut provider = Self::from_source(&source, name, resolution, speed, start_beat, start_time, start_playing, LoopMode::Hold, None, None, output_format)?;
+        provider.set_reconnect_policy(Some(ReconnectPolicy::default()));
+
+        Ok(provider)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_stream(url: &str, buffer_duration: f64, follow_live_edge: bool, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, output_format: OutputFormat) -> Result<Self> {
+        let source = format!(
+            "uridecodebin name=decoder uri=\"{}\" use-buffering=true buffer-duration={}",
+            quote_launch_value(url),
+            (buffer_duration * 1_000_000_000.0) as i64
+        );
+
+        let mut provider = Self::from_source(&source, name, resolution, speed, start_beat, start_time, start_playing, LoopMode::Hold, None, None, output_format)?;
+        provider.follow_live_edge = follow_live_edge;
+
+        Ok(provider)
+    }
+
+    // Decodes media held in memory, the whole buffer is seekable so every loop mode is available
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_from_bytes(data: Vec<u8>, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat) -> Result<Self> {
+        let source = format!("appsrc name={} stream-type=random-access ! decodebin name=decoder", BYTE_SOURCE_NAME);
+
+        // Data can only be fed once the callbacks are installed, playback is started afterwards
+        let mut provider = Self::from_source(&source, name, resolution, speed, start_beat, start_time, false, loop_mode, loop_start, loop_end, output_format)?;
+        let appsrc = provider.byte_source()?;
+
+        let size = data.len();
+        let data = Arc::new(data);
+        let offset = Arc::new(AtomicUsize::new(0));
+        let seek_offset = offset.clone();
+
+        appsrc.set_size(size as i64);
+        appsrc.set_callbacks(
+            gst_app::AppSrcCallbacks::new()
+                .need_data(move |appsrc, length| {
+                    let start = offset.load(Ordering::SeqCst);
+                    if start >= size {
+                        let _ = appsrc.end_of_stream();
+                        return;
+                    }
+
+                    let end = (start + (length as usize).max(BYTE_SOURCE_CHUNK_SIZE)).min(size);
+                    let mut buffer = gst::Buffer::from_mut_slice(data[start..end].to_vec());
+                    if let Some(buffer) = buffer.get_mut() {
+                        buffer.set_offset(start as u64);
+                    }
+                    offset.store(end, Ordering::SeqCst);
+
+                    if let Err(e) = appsrc.push_buffer(buffer) {
+                        error!("Failed to feed video data: {:?}", e);
+                    }
+                })
+                .seek_data(move |_, position| {
+                    if position as usize > size {
+                        return false;
+                    }
+
+                    seek_offset.store(position as usize, Ordering::SeqCst);
+                    true
+                })
+                .build(),
+        );
+
+        if start_playing {
+            provider.play()?;
+        }
+
+        Ok(provider)
+    }
+
+    // Decodes media read from any byte stream, such as a download in progress, which can not be seeked in
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_from_reader<R: Read + Send + 'static>(mut reader: R, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, output_format: OutputFormat) -> Result<Self> {
+        let source = format!("appsrc name={} stream-type=stream block=true ! decodebin name=decoder", BYTE_SOURCE_NAME);
+
+        let mut provider = Self::from_source(&source, name, resolution, speed, start_beat, start_time, false, LoopMode::Hold, None, None, output_format)?;
+        let appsrc = provider.byte_source()?;
+
+        // Pushing blocks while the appsrc queue is full and fails once the pipeline is stopped
+        std::thread::spawn(move || {
+            let mut chunk = vec![0; BYTE_SOURCE_CHUNK_SIZE];
+
+            loop {
+                let length = match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(length) => length,
+                    Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        error!("Failed to read video data: {:?}", e);
+                        break;
+                    }
+                };
+
+                if appsrc.push_buffer(gst::Buffer::from_mut_slice(chunk[..length].to_vec())).is_err() {
+                    return;
+                }
+            }
+
+            let _ = appsrc.end_of_stream();
+        });
+
+        if start_playing {
+            provider.play()?;
+        }
+
+        Ok(provider)
+    }
+
+    // Reads stdin when `path` is "-", a named pipe otherwise. Without a raw format the stream is expected
+    // to be containerized, e.g. `ffmpeg ... -f matroska -`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_pipe(path: &str, raw_format: Option<&RawVideoFormat>, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, output_format: OutputFormat) -> Result<Self> {
+        let mut source = if path == "-" {
+            "fdsrc fd=0".to_owned()
+        } else {
+            if !Path::new(path).exists() {
+                bail!("Pipe {:?} does not exist", path);
+            }
+            format!("filesrc location=\"{}\"", quote_launch_value(path))
+        };
+
+        match raw_format {
+            Some(raw_format) => source.push_str(&format!(
+                " ! rawvideoparse width={} height={} format={} framerate={}/{}",
+                raw_format.width, raw_format.height, raw_format.format.to_lowercase(), raw_format.framerate.0, raw_format.framerate.1
+            )),
+            None => source.push_str(" ! decodebin name=decoder"),
+        }
+
+        // Pipes can not be seeked in, the last frame stays on screen once the writer closes it
+        Self::from_source(&source, name, resolution, speed, start_beat, start_time, start_playing, LoopMode::Hold, None, None, output_format)
+    }
+
+    fn byte_source(&self) -> Result<gst_app::AppSrc> {
+        self.get_element(BYTE_SOURCE_NAME)
+            .and_then(|element| element.dynamic_cast::<gst_app::AppSrc>().ok())
+            .context("Failed to retrieve the byte source of the video pipeline")
+    }
+
+    // Builds a provider from a gst-launch description of the elements producing the decoded video stream
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_source(source: &str, name: String, resolution: impl Into<Option<(usize, usize)>>, speed: impl Into<PlaybackSpeed>, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat) -> Result<Self> {
+        let resolution = resolution.into();
+        let speed: PlaybackSpeed = speed.into();
+
+        let pipeline_string = format!(
+            "{} ! deinterlace name=deinterlace mode=disabled ! videoconvert name=convert ! videocrop name=crop ! aspectratiocrop name=aspectcrop ! videoscale name=scale ! capsfilter name=scalecaps caps=\"{}\" ! videoflip name=flip method={} ! appsink name=appsink async=false sync={}",
+            source, scale_caps(output_format, resolution, FitMode::default()), Orientation::default().videoflip_method(), speed == PlaybackSpeed::Native,
+        );
+
+        Self::from_launch(&pipeline_string, "appsink", name, resolution, speed, start_beat, start_time, start_playing, loop_mode, loop_start, loop_end, output_format)
+    }
+
+    // Builds a provider around a complete gst-launch description, frames are taken from the appsink named `appsink_name`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_launch(pipeline_string: &str, appsink_name: &str, name: String, resolution: Option<(usize, usize)>, speed: PlaybackSpeed, start_beat: f64, start_time: f64, start_playing: bool, loop_mode: LoopMode, loop_start: Option<f64>, loop_end: Option<f64>, output_format: OutputFormat) -> Result<Self> {
+        speed.check()?;
+
+        let GstFrameSource { pipeline, frame_queue, sync, last_error } =
+            GstFrameSource::new(pipeline_string, appsink_name, speed, start_beat, start_time, start_playing, output_format)?;
+
+        // Sources are expected to name their decoder for their soundtrack to be picked up
+        let audio_settings = Arc::new(AudioSettings::new());
+        let video_track = Arc::new(AtomicUsize::new(0));
+        let subtitle_cue = Arc::new(Mutex::new(None));
+        if let Some(decoder) = pipeline
+            .clone()
+            .dynamic_cast::<gst::Bin>()
+            .ok()
+            .and_then(|bin| bin.get_by_name("decoder"))
+        {
+            watch_video_pads(&pipeline, &decoder, video_track.clone());
+            watch_audio_pads(&pipeline, &decoder, audio_settings.clone());
+            watch_subtitle_pads(&pipeline, &decoder, subtitle_cue.clone());
+        }
+
+        if start_playing {
+            pipeline.set_state(State::Playing).context(format!(
+                "Failed to start gstreamer pipeline {:?}",
+                pipeline_string
+            ))?;
+        }
+
+        let mut provider = Self {
+            name,
+            frame_queue,
+            pipeline,
+            sync,
+            max_sync_wait: None,
+            late_policy: LatePolicy::default(),
+            rate: 1.0,
+            loop_mode,
+            loop_start,
+            loop_end,
+            pending_seek: None,
+            reconnect_policy: None,
+            restart_policy: None,
+            reconnect_attempts: 0,
+            next_reconnect: None,
+            last_reconnect: None,
+            resume_position: None,
+            buffering_percent: 100,
+            follow_live_edge: false,
+            ended: false,
+            resolution,
+            fit_mode: FitMode::default(),
+            crop: None,
+            output_format,
+            last_error,
+            audio_settings,
+            video_track,
+            audio_spectrum: Vec::new(),
+            audio_rms: 0.0,
+            subtitle_cue,
+            events: EventSenders::default(),
+            remap_position: None,
+            pending_retrigger: None,
+            source_path: None,
+            file_watcher: None,
+            random_slice: None,
+            slice_index: None,
+            beat_origin: 0.0,
+            last_grid_correction: None,
+            frame_pts: None,
+            frame_index: None,
+            stats_uniform: false,
+            stall_timeout: None,
+            stall_watch: None,
+            stalled: false,
+            #[cfg(feature = "gl")]
+            gl_context: None,
+        };
+
+        // The loop region can only be applied once the pipeline has prerolled
+        provider.pending_seek = provider.region_start_seek();
+
+        Ok(provider)
+    }
+
+    fn start_in_background(&self, start_playing: bool) {
+        if start_playing {
+            self.sync.update(|state| {
+                state.playing = true;
+                state.next_sync_beat = state.beat;
+                state.next_sync_time = state.time;
+            });
+        }
+
+        // A provider that does not start playing is still prerolled so that its first frame is ready
+        let state = if start_playing { State::Playing } else { State::Paused };
+        let pipeline = self.pipeline.clone();
+        let sync = self.sync.clone();
+        let last_error = self.last_error.clone();
+        let uri = self.source_path.as_deref().and_then(|path| path_to_uri(path).ok());
+
+        std::thread::spawn(move || {
+            if sync.lock().map(|state| state.stopped).unwrap_or(true) {
+                return;
+            }
+
+            // The file is probed here instead of in the constructor, a broken one never leaves the Null state
+            if let Some(uri) = uri {
+                if let Err(e) = probe_uri(&uri) {
+                    error!("{:?}", e);
+                    if let Ok(mut last_error) = last_error.lock() {
+                        *last_error = Some(format!("{:?}", e));
+                    }
+                    return;
+                }
+            }
+
+            if let Err(e) = pipeline.set_state(state) {
+                error!("Failed to start gstreamer pipeline in the background: {:?}", e);
+            }
+        });
+    }
+
+    pub fn dump_pipeline_dot(&self, path: &str) -> Result<()> {
+        write_pipeline_dot(&self.pipeline, path)
+    }
+
+    pub fn is_ready(&self) -> bool {
+        let (result, current, pending) = self.pipeline.get_state(gst::ClockTime::from_seconds(0));
+
+        result.is_ok() && matches!(current, State::Paused | State::Playing) && pending == State::VoidPending
+    }
+
+    // Pipelines running on the same clock from the same base time render their frames at the same instants
+    pub(crate) fn use_clock(&self, clock: &gst::Clock, base_time: gst::ClockTime) {
+        if let Ok(pipeline) = self.pipeline.clone().dynamic_cast::<gst::Pipeline>() {
+            pipeline.use_clock(Some(clock));
+            pipeline.set_start_time(gst::ClockTime::none());
+            pipeline.set_base_time(base_time);
+        }
+    }
+
+    pub(crate) fn get_element(&self, element_name: &str) -> Option<gst::Element> {
+        self.pipeline
+            .clone()
+            .dynamic_cast::<gst::Bin>()
+            .ok()
+            .and_then(|bin| bin.get_by_name(element_name))
+    }
+
+    fn looped_duration(&self) -> Option<f64> {
+        let duration = self.duration()?;
+        let start = self.loop_start.unwrap_or(0.0).min(duration);
+        let end = self.loop_end.unwrap_or(duration).min(duration);
+
+        Some((end - start).max(0.0))
+    }
+
+    fn region_start_seek(&self) -> Option<f64> {
+        if self.loop_start.is_some() || self.loop_end.is_some() {
+            Some(self.loop_start.unwrap_or(0.0))
+        } else {
+            None
+        }
+    }
+
+    pub fn check_loop(&mut self) {
+        self.check_file_changes();
+        self.check_stall();
+
+        if let Some(next_reconnect) = self.next_reconnect {
+            if Instant::now() >= next_reconnect {
+                self.reconnect();
+            }
+        }
+
+        let bus = self
+            .pipeline
+            .get_bus()
+            .expect("Failed to find bus for video playback pipeline");
+
+        while let Some(message) = bus.timed_pop(gst::ClockTime::from_seconds(0)) {
+            if let gst::MessageView::Error(err) = message.view() {
+                self.events.emit(ProviderEvent::Error(err.get_error().to_string()));
+            }
+
+            match message.view() {
+                gst::MessageView::Error(err) if self.reconnect_policy.is_some() => {
+                    provider_log!(warn, self, "Video stream error, reconnecting: {}", err.get_error());
+                    self.schedule_reconnect();
+                }
+                gst::MessageView::Error(err) if self.restart_policy.is_some() => {
+                    provider_log!(warn, self, "Video pipeline error, restarting: {}", err.get_error());
+                    if self.next_reconnect.is_none() {
+                        self.resume_position = self.position();
+                    }
+                    self.schedule_reconnect();
+                }
+                gst::MessageView::Eos(_) if self.reconnect_policy.is_some() => self.schedule_reconnect(),
+                gst::MessageView::StreamStart(_) => {
+                    // A restarted file sends a new stream start right away, its attempts are only reset once it played long enough
+                    if self.reconnect_policy.is_some() {
+                        self.reconnect_attempts = 0;
+                    }
+                    self.clear_error();
+                    self.events.emit(ProviderEvent::StreamStarted);
+                }
+                gst::MessageView::Buffering(buffering) => {
+                    self.on_buffering(buffering.get_percent());
+                    self.events.emit(ProviderEvent::Buffering(buffering.get_percent()));
+                }
+                gst::MessageView::Eos(_) => self.on_eos(false),
+                gst::MessageView::SegmentDone(_) => self.on_eos(true),
+                gst::MessageView::Element(element) => {
+                    if let Some(structure) = element.get_structure() {
+                        match structure.get_name() {
+                            "spectrum" => if let Some(spectrum) = parse_spectrum(structure) {
+                                self.audio_spectrum = spectrum;
+                            }
+                            "level" => if let Some(rms) = parse_rms(structure) {
+                                self.audio_rms = rms;
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                gst::MessageView::AsyncDone(_) => {
+                    self.events.emit(ProviderEvent::Prerolled);
+
+                    let native_fps = self.native_framerate();
+                    let clip_duration = self.looped_duration();
+                    self.sync.update(|state| {
+                        state.native_fps = native_fps;
+                        state.clip_duration = clip_duration;
+                    });
+
+                    // Normalized crop regions can only be resolved once the size of the footage is known
+                    if let Err(e) = self.apply_crop() {
+                        provider_log!(error, self, "{:?}", e);
+                    }
+
+                    if let Some(position) = self.pending_seek.take() {
+                        if let Err(e) = self.seek(position) {
+                            provider_log!(error, self, "{:?}", e);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // Events are only gathered while the bus is serviced, i.e. from get(), set_beat() and set_time()
+    pub fn events(&mut self) -> Receiver<ProviderEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn set_reconnect_policy(&mut self, reconnect_policy: Option<ReconnectPolicy>) {
+        self.reconnect_policy = reconnect_policy;
+        if reconnect_policy.is_none() {
+            self.next_reconnect = None;
+        }
+    }
+
+    // Rebuilds the decoding chain of a non-live source after a fatal error, resuming where playback stood
+    pub fn set_restart_policy(&mut self, restart_policy: Option<ReconnectPolicy>) {
+        self.restart_policy = restart_policy;
+        if restart_policy.is_none() && self.reconnect_policy.is_none() {
+            self.next_reconnect = None;
+        }
+    }
+
+    // A pipeline that claims to be playing but delivered no frame for `stall_timeout` is restarted
+    // when a reconnect or restart policy is set, an error is raised otherwise. None disables the watchdog
+    pub fn set_stall_timeout(&mut self, stall_timeout: Option<Duration>) {
+        self.stall_timeout = stall_timeout;
+        self.stall_watch = None;
+        self.stalled = false;
+    }
+
+    fn check_stall(&mut self) {
+        let stall_timeout = match self.stall_timeout {
+            Some(stall_timeout) => stall_timeout,
+            None => return,
+        };
+
+        // Frames are not expected while buffering, reconnecting, or while the clock or the renderer holds the
+        // streaming thread back, e.g. a slow Fpb speed, a paused beat clock or WaitForConsumer without a consumer
+        let expects_frames = self.current_state() == State::Playing
+            && self.sync.is_playing()
+            && !self.sync.is_frozen()
+            && !self.sync.is_waiting()
+            && !self.frame_queue.is_waiting_consumer()
+            && !self.is_buffering()
+            && !self.ended
+            && self.next_reconnect.is_none();
+
+        let decoded_frames = self.frame_queue.pushed_count();
+        let since = match self.stall_watch {
+            Some((frames, since)) if expects_frames && frames == decoded_frames => since,
+            _ => {
+                self.stall_watch = if expects_frames { Some((decoded_frames, Instant::now())) } else { None };
+                self.stalled = false;
+                return;
+            }
+        };
+
+        // The watchdog only fires once per stall
+        if self.stalled || since.elapsed() < stall_timeout {
+            return;
+        }
+        self.stalled = true;
+
+        let message = format!("No frame received for {:.1}s while playing", stall_timeout.as_secs_f64());
+        self.events.emit(ProviderEvent::Stalled);
+
+        if self.reconnect_policy.is_some() || self.restart_policy.is_some() {
+            provider_log!(warn, self, "{}, restarting", message);
+            if self.reconnect_policy.is_none() {
+                self.resume_position = self.position();
+            }
+            self.schedule_reconnect();
+        } else {
+            provider_log!(error, self, "{}", message);
+            if let Ok(mut last_error) = self.last_error.lock() {
+                *last_error = Some(message.clone());
+            }
+            self.events.emit(ProviderEvent::Error(message));
+        }
+    }
+
+    fn schedule_reconnect(&mut self) {
+        let reconnect_policy = if let Some(reconnect_policy) = self.reconnect_policy.or(self.restart_policy) {
+            reconnect_policy
+        } else {
+            return;
+        };
+
+        if self.next_reconnect.is_some() {
+            return;
+        }
+
+        if let Some(last_reconnect) = self.last_reconnect {
+            if last_reconnect.elapsed() > reconnect_policy.max_delay {
+                self.reconnect_attempts = 0;
+            }
+        }
+
+        if let Some(max_attempts) = reconnect_policy.max_attempts {
+            if self.reconnect_attempts >= max_attempts {
+                provider_log!(error, self, "Giving up on video source after {} failed attempts", self.reconnect_attempts);
+                return;
+            }
+        }
+
+        if let Err(e) = self.pipeline.set_state(State::Null) {
+            provider_log!(error, self, "Failed to tear down video stream before reconnecting: {:?}", e);
+        }
+
+        let delay = reconnect_policy
+            .initial_delay
+            .checked_mul(2u32.saturating_pow(self.reconnect_attempts))
+            .unwrap_or(reconnect_policy.max_delay)
+            .min(reconnect_policy.max_delay);
+
+        self.reconnect_attempts = self.reconnect_attempts.saturating_add(1);
+        self.next_reconnect = Some(Instant::now() + delay);
+    }
+
+    fn reconnect(&mut self) {
+        self.next_reconnect = None;
+        self.last_reconnect = Some(Instant::now());
+
+        if self.is_stopped() {
+            return;
+        }
+
+        // The previous position can only be seeked back to once the rebuilt pipeline prerolled
+        if let Some(resume_position) = self.resume_position.take() {
+            self.pending_seek = Some(resume_position);
+        }
+
+        let state = if self.sync.is_playing() && !self.is_remapped() && !self.sync.is_frozen() {
+            State::Playing
+        } else {
+            State::Paused
+        };
+
+        if let Err(e) = self.pipeline.set_state(state) {
+            provider_log!(error, self, "Failed to reconnect video stream: {:?}", e);
+            self.schedule_reconnect();
+        }
+    }
+
+    // The transfer function is read from the decoded stream before its conversion to RGB, shaders can use it to tone-map HDR footage
+    pub fn hdr_transfer(&self) -> HdrTransfer {
+        self.get_element("convert")
+            .and_then(|convert| convert.get_static_pad("sink"))
+            .and_then(|pad| pad.get_current_caps())
+            .and_then(|caps| {
+                caps.get_structure(0)
+                    .and_then(|structure| structure.get::<String>("colorimetry").ok().flatten())
+            })
+            .map(|colorimetry| HdrTransfer::from_colorimetry(&colorimetry))
+            .unwrap_or(HdrTransfer::Sdr)
+    }
+
+    // Size of the decoded frames before cropping and scaling
+    pub fn native_size(&self) -> Option<(usize, usize)> {
+        self.get_element("convert")
+            .and_then(|convert| convert.get_static_pad("sink"))
+            .and_then(|pad| pad.get_current_caps())
+            .and_then(|caps| gst_video::VideoInfo::from_caps(&caps).ok())
+            .map(|video_info| (video_info.width() as usize, video_info.height() as usize))
+    }
+
+    pub fn native_framerate(&self) -> Option<f64> {
+        self.get_element("convert")
+            .and_then(|convert| convert.get_static_pad("sink"))
+            .and_then(|pad| pad.get_current_caps())
+            .and_then(|caps| gst_video::VideoInfo::from_caps(&caps).ok())
+            .map(|video_info| video_info.fps())
+            .filter(|fps| *fps.numer() > 0 && *fps.denom() > 0)
+            .map(|fps| *fps.numer() as f64 / *fps.denom() as f64)
+    }
+
+    pub fn set_crop(&mut self, crop: Option<CropRegion>) -> Result<()> {
+        self.crop = crop;
+        self.apply_crop()
+    }
+
+    fn apply_crop(&self) -> Result<()> {
+        let crop = self
+            .get_element("crop")
+            .context("Failed to find the crop element of the video pipeline")?;
+
+        let (left, top, right, bottom) = match (self.crop, self.native_size()) {
+            (Some(region), Some(native_size)) => region.margins(native_size),
+            (Some(_), None) => return Ok(()),
+            (None, _) => (0, 0, 0, 0),
+        };
+
+        for (property, margin) in &[("left", left), ("top", top), ("right", right), ("bottom", bottom)] {
+            crop.set_property(property, margin)
+                .context(format!("Failed to set the {} crop margin", property))?;
+        }
+
+        Ok(())
+    }
+
+    // Also applies to a soundtrack that is only found later, e.g. once the pipeline prerolls
+    pub fn set_audio_analysis(&mut self, enabled: bool) {
+        self.audio_settings.analysis.store(enabled, Ordering::SeqCst);
+
+        // Only the discarding sink is named, see build_audio_branch
+        if let Some(sink) = self.get_element("audiosink") {
+            if let Err(e) = sink.set_property("sync", &enabled) {
+                provider_log!(error, self, "Failed to toggle the clock synchronization of the audio: {:?}", e);
+            }
+        }
+
+        for element_name in &["spectrum", "level"] {
+            if let Some(element) = self.get_element(element_name) {
+                if let Err(e) = element.set_property("post-messages", &enabled) {
+                    provider_log!(error, self, "Failed to toggle the audio analysis: {:?}", e);
+                }
+            }
+        }
+
+        if !enabled {
+            self.audio_spectrum.clear();
+            self.audio_rms = 0.0;
+        }
+    }
+
+    // The audio sink can only be replaced while the pipeline is stopped
+    pub fn set_audio_output(&mut self, enabled: bool) -> Result<()> {
+        if self.audio_settings.output.swap(enabled, Ordering::SeqCst) == enabled {
+            return Ok(());
+        }
+
+        self.rebuild_streams(true)
+    }
+
+    pub fn set_audio_track(&mut self, track: usize) -> Result<()> {
+        if self.audio_settings.track.swap(track, Ordering::SeqCst) == track {
+            return Ok(());
+        }
+
+        self.rebuild_streams(false)
+    }
+
+    pub fn set_video_track(&mut self, track: usize) -> Result<()> {
+        if self.video_track.swap(track, Ordering::SeqCst) == track {
+            return Ok(());
+        }
+
+        self.rebuild_streams(false)
+    }
+
+    // Streams are picked as the decoder exposes them, changing the selection means restarting the pipeline,
+    // playback then resumes where it stood
+    fn rebuild_streams(&mut self, replace_audio_branch: bool) -> Result<()> {
+        let position = self.position();
+        let (_, current, pending) = self.pipeline.get_state(gst::ClockTime::from_seconds(0));
+        let state = if pending == State::VoidPending { current } else { pending };
+
+        self.pipeline
+            .set_state(State::Null)
+            .context("Failed to stop video playback to change its streams")?;
+
+        if replace_audio_branch {
+            if let Some(branch) = self.get_element("audiobranch") {
+                if let Ok(bin) = self.pipeline.clone().dynamic_cast::<gst::Bin>() {
+                    bin.remove(&branch)
+                        .context("Failed to remove the previous audio output")?;
+                }
+                branch
+                    .set_state(State::Null)
+                    .context("Failed to stop the previous audio output")?;
+            }
+        }
+
+        if state != State::Null {
+            self.pipeline
+                .set_state(state)
+                .context("Failed to resume video playback after changing its streams")?;
+            self.pending_seek = position;
+        }
+
+        Ok(())
+    }
+
+    // Switches to another file or URI without recreating the provider, the last frame of the previous clip stays
+    // available until the new one prerolled
+    pub fn set_path(&mut self, path: &str) -> Result<()> {
+        let decoder = self
+            .get_element("decoder")
+            .filter(|decoder| decoder.find_property("uri").is_some())
+            .context("Only videos opened from a path or an URI can switch to another one")?;
+
+        // Called from the render thread, the new file is probed along with the restart of the pipeline
+        let uri = path_to_uri(path)?;
+        validate_uri(&uri, false)?;
+
+        let state = self.current_state();
+
+        self.pipeline
+            .set_state(State::Null)
+            .context("Failed to stop video playback to switch to another clip")?;
+
+        // Subtitles belong to the previous clip, embedded ones get a new branch once the decoder exposes them
+        if let Some(branch) = self.get_element("subtitlebranch") {
+            if let Ok(bin) = self.pipeline.clone().dynamic_cast::<gst::Bin>() {
+                bin.remove(&branch)
+                    .context("Failed to remove the subtitles of the previous clip")?;
+            }
+            branch
+                .set_state(State::Null)
+                .context("Failed to stop the subtitles of the previous clip")?;
+        }
+        if let Ok(mut cue) = self.subtitle_cue.lock() {
+            *cue = None;
+        }
+
+        decoder
+            .set_property("uri", &uri)
+            .context("Failed to switch the video decoder to another clip")?;
+
+        if let Some(sidecar) = find_sidecar(path) {
+            if let Err(e) = attach_sidecar(&self.pipeline, &sidecar, self.subtitle_cue.clone()) {
+                provider_log!(error, self, "{:?}", e);
+            }
+        }
+
+        let path_changed = self.source_path.as_deref() != Some(path);
+        self.source_path = Some(path.to_owned());
+        self.pending_seek = None;
+        self.resume_position = None;
+        self.remap_position = None;
+        self.slice_index = None;
+        self.ended = false;
+        self.clear_error();
+        self.sync.update(|state| {
+            state.native_fps = None;
+            state.clip_duration = None;
+        });
+
+        // Failures end up in the last error of the provider, as for providers started asynchronously
+        if state != State::Null {
+            self.start_in_background(state == State::Playing);
+        }
+
+        // Loop points of the previous clip are applied once the new one prerolled
+        if let Some(loop_start) = self.loop_start {
+            self.pending_seek = Some(loop_start);
+        }
+
+        if path_changed && self.file_watcher.is_some() {
+            self.set_watch_file(true)?;
+        }
+
+        Ok(())
+    }
+
+    // Reloads the clip whenever its file is written to or replaced, e.g. by a render job overwriting it
+    pub fn set_watch_file(&mut self, watch: bool) -> Result<()> {
+        if !watch {
+            self.file_watcher = None;
+            return Ok(());
+        }
+
+        let path = match &self.source_path {
+            Some(path) if !path.contains("://") => Path::new(path).to_path_buf(),
+            _ => bail!("Only videos opened from a local file can be watched for changes"),
+        };
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut watcher = notify::watcher(sender, FILE_WATCH_DEBOUNCE).context("Failed to create a file watcher")?;
+
+        // Files replaced through a rename are only noticed by watching the directory holding them
+        let directory = path.parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .context(format!("Failed to watch {:?} for changes", path))?;
+
+        let watched_path = std::fs::canonicalize(&path).unwrap_or(path);
+        self.file_watcher = Some((watcher, receiver, watched_path));
+
+        Ok(())
+    }
+
+    // Called on every check of the bus, the file system is only looked at once the watcher reported events
+    fn check_file_changes(&mut self) {
+        let (receiver, watched_path) = match &self.file_watcher {
+            Some((_, receiver, watched_path)) => (receiver, watched_path),
+            None => return,
+        };
+
+        let mut changed = false;
+        while let Ok(event) = receiver.try_recv() {
+            let event_path = match event {
+                DebouncedEvent::Create(event_path) | DebouncedEvent::Write(event_path) | DebouncedEvent::Rename(_, event_path) => event_path,
+                _ => continue,
+            };
+
+            if event_path == *watched_path || std::fs::canonicalize(&event_path).map(|event_path| event_path == *watched_path).unwrap_or(false) {
+                changed = true;
+            }
+        }
+
+        let path = match &self.source_path {
+            Some(path) if changed => path.clone(),
+            _ => return,
+        };
+
+        // Only the decoder is switched to the rewritten file, it is probed in the background
+        if let Err(e) = self.set_path(&path) {
+            provider_log!(error, self, "Failed to reload modified video {:?}: {:?}", path, e);
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        if let Ok(mut current_volume) = self.audio_settings.volume.lock() {
+            *current_volume = volume.max(0.0);
+        }
+
+        if let Some(element) = self.get_element("volume") {
+            if let Err(e) = element.set_property("volume", &volume.max(0.0)) {
+                provider_log!(error, self, "Failed to set the audio volume: {:?}", e);
+            }
+        }
+    }
+
+    pub fn set_mute(&mut self, mute: bool) {
+        self.audio_settings.mute.store(mute, Ordering::SeqCst);
+
+        if let Some(element) = self.get_element("volume") {
+            if let Err(e) = element.set_property("mute", &mute) {
+                provider_log!(error, self, "Failed to mute the audio output: {:?}", e);
+            }
+        }
+    }
+
+    // Text of the subtitle cue active at the current position, if any
+    pub fn subtitle(&self) -> Option<String> {
+        let position = self.position()?;
+
+        self.subtitle_cue
+            .lock()
+            .ok()?
+            .as_ref()
+            .filter(|cue| cue.is_active(position))
+            .map(|cue| cue.text.clone())
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().ok().and_then(|last_error| last_error.clone())
+    }
+
+    pub fn clear_error(&self) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = None;
+        }
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) -> Result<()> {
+        let flip = self
+            .get_element("flip")
+            .context("Failed to find the orientation element of the video pipeline")?;
+
+        flip.set_property_from_str("method", orientation.videoflip_method());
+
+        Ok(())
+    }
+
+    // Only interlaced content goes through the deinterlacer, progressive footage is passed through untouched
+    pub fn set_deinterlace(&mut self, method: Option<DeinterlaceMethod>) -> Result<()> {
+        let deinterlace = self
+            .get_element("deinterlace")
+            .context("Failed to find the deinterlacing element of the video pipeline")?;
+
+        match method {
+            Some(method) => {
+                deinterlace.set_property_from_str("method", method.property_value());
+                deinterlace.set_property_from_str("mode", "auto");
+            }
+            None => deinterlace.set_property_from_str("mode", "disabled"),
+        }
+
+        Ok(())
+    }
+
+    pub fn set_fit_mode(&mut self, fit_mode: FitMode) -> Result<()> {
+        let aspectcrop = self
+            .get_element("aspectcrop")
+            .context("Failed to find the aspect ratio crop element of the video pipeline")?;
+        let scale = self
+            .get_element("scale")
+            .context("Failed to find the scaling element of the video pipeline")?;
+        let scalecaps = self
+            .get_element("scalecaps")
+            .context("Failed to find the scaling caps of the video pipeline")?;
+
+        // A null aspect ratio disables the crop
+        let aspect_ratio = match (fit_mode, self.resolution) {
+            (FitMode::Crop, Some(resolution)) => gst::Fraction::new(resolution.0 as i32, resolution.1 as i32),
+            _ => gst::Fraction::new(0, 1),
+        };
+        aspectcrop
+            .set_property("aspect-ratio", &aspect_ratio)
+            .context("Failed to set the crop aspect ratio")?;
+        scale
+            .set_property("add-borders", &(fit_mode == FitMode::Letterbox))
+            .context("Failed to set the letterboxing of the video pipeline")?;
+
+        let caps = gst::Caps::from_str(&scale_caps(self.output_format, self.resolution, fit_mode))
+            .context("Failed to build the scaling caps of the video pipeline")?;
+        scalecaps
+            .set_property("caps", &caps)
+            .context("Failed to set the scaling caps of the video pipeline")?;
+
+        self.fit_mode = fit_mode;
+
+        Ok(())
+    }
+
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    // Keeps servicing the bus while waiting for the streaming thread to catch up with the clock
+    fn wait_for_sync<F: Fn(&SyncState) -> bool>(&mut self, is_synced: F) {
+        let sync = self.sync.clone();
+        let deadline = self.max_sync_wait.map(|max_sync_wait| Instant::now() + max_sync_wait);
+
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        if self.late_policy == LatePolicy::Skip {
+                            sync.update(|state| {
+                                state.next_sync_beat = state.beat;
+                                state.next_sync_time = state.time;
+                            });
+                        }
+                        return;
+                    }
+
+                    (deadline - now).min(SYNC_POLL_INTERVAL)
+                }
+                None => SYNC_POLL_INTERVAL,
+            };
+
+            if !sync.wait_for_sync(&is_synced, timeout) {
+                return;
+            }
+
+            self.check_loop();
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: PlaybackSpeed) {
+        if let Err(e) = speed.check() {
+            provider_log!(error, self, "{:?}", e);
+            return;
+        }
+
+        // Only the native mode lets the sink follow the pipeline clock, other modes are gated by set_beat / set_time
+        if let Some(appsink) = self.get_element("appsink") {
+            if let Err(e) = appsink.set_property("sync", &(speed == PlaybackSpeed::Native)) {
+                provider_log!(error, self, "Failed to switch the clock synchronization of the video sink: {:?}", e);
+            }
+        }
+
+        // Remapped clips never free-run, their frames are prerolled by seeks
+        if speed.is_remap() != self.is_remapped() && self.current_state() != State::Null {
+            let state = if speed.is_remap() || !self.sync.is_playing() {
+                State::Paused
+            } else {
+                State::Playing
+            };
+            if let Err(e) = self.pipeline.set_state(state) {
+                provider_log!(error, self, "Failed to switch video playback mode: {:?}", e);
+            }
+        }
+        self.remap_position = None;
+
+        self.sync.update(|state| {
+            // The clock the next frame was due on is meaningless for another kind of speed
+            if std::mem::discriminant(&speed) != std::mem::discriminant(&state.speed) {
+                state.next_sync_beat = state.beat;
+                state.next_sync_time = state.time;
+            }
+            state.speed = speed;
+        });
+    }
+
+    // Holds the last delivered frame while the pipeline pauses underneath, unlike pause it keeps the playback state
+    pub fn set_freeze(&mut self, freeze: bool) {
+        if freeze == self.sync.is_frozen() {
+            return;
+        }
+
+        // Unfreezing resumes from the current clock instead of catching up on the frames that were held back
+        self.sync.update(|state| {
+            state.frozen = freeze;
+            state.next_sync_beat = state.beat;
+            state.next_sync_time = state.time;
+        });
+
+        let state = if freeze || !self.sync.is_playing() || self.is_remapped() {
+            State::Paused
+        } else {
+            State::Playing
+        };
+
+        if let Err(e) = self.pipeline.set_state(state) {
+            provider_log!(error, self, "Failed to toggle video freeze: {:?}", e);
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.sync.is_frozen()
+    }
+
+    pub fn set_frame_pacing(&mut self, frame_pacing: FramePacing) {
+        self.frame_queue
+            .set_hold_until_consumed(frame_pacing == FramePacing::WaitForConsumer);
+    }
+
+    pub fn frame_pacing(&self) -> FramePacing {
+        if self.frame_queue.holds_until_consumed() {
+            FramePacing::WaitForConsumer
+        } else {
+            FramePacing::DropToLatest
+        }
+    }
+
+    fn current_state(&self) -> State {
+        let (_, current, pending) = self.pipeline.get_state(gst::ClockTime::from_seconds(0));
+
+        if pending == State::VoidPending {
+            current
+        } else {
+            pending
+        }
+    }
+
+    fn is_remapped(&self) -> bool {
+        self.sync.lock().map(|state| state.speed.is_remap()).unwrap_or(false)
+    }
+
+    // Maps a position of the master clock onto the loop region, following the loop mode
+    fn remapped_position(&self, offset: f64) -> Option<f64> {
+        let start = self.loop_start.unwrap_or(0.0);
+        let end = self.loop_end.or_else(|| self.duration())?;
+        let length = end - start;
+
+        if length <= 0.0 {
+            return Some(start);
+        }
+
+        let position = match self.loop_mode {
+            LoopMode::Loop => offset.rem_euclid(length),
+            LoopMode::PingPong => {
+                let phase = offset.rem_euclid(2.0 * length);
+                if phase < length {
+                    phase
+                } else {
+                    2.0 * length - phase
+                }
+            }
+            LoopMode::Once | LoopMode::Hold => offset.max(0.0).min(length),
+        };
+
+        Some(start + position)
+    }
+
+    // Seeks to the position the master clock maps to, waiting for its frame when synchronizing
+    fn remap(&mut self, clock: f64, scale: f64, sync: bool) {
+        let position = if let Some(position) = self.remapped_position(clock * scale) {
+            position
+        } else {
+            return;
+        };
+
+        // Positions within the same frame do not need a new seek
+        let frame_duration = 1.0 / self.native_framerate().unwrap_or(30.0);
+        if let Some(remap_position) = self.remap_position {
+            if (remap_position - position).abs() < frame_duration / 2.0 {
+                return;
+            }
+        }
+
+        if let Err(e) = self.seek(position) {
+            provider_log!(error, self, "{:?}", e);
+            return;
+        }
+        self.remap_position = Some(position);
+
+        if sync {
+            let timeout = self.max_sync_wait.unwrap_or(Duration::from_secs(5));
+            let (result, _, _) = self
+                .pipeline
+                .get_state(gst::ClockTime::from_nseconds(timeout.as_nanos() as u64));
+
+            if let Err(e) = result {
+                provider_log!(error, self, "Failed to preroll remapped video frame: {:?}", e);
+            }
+        }
+    }
+
+    // Clips stretched over a number of beats are locked to the beat grid: frame gating keeps them close to it,
+    // any remaining drift is corrected by seeking to where the clip should be at this beat
+    fn follow_beat_grid(&mut self, beat: f64) {
+        let (beats, native_fps, clip_duration) = match self.sync.lock() {
+            Some(state) => match (state.speed, state.native_fps, state.clip_duration) {
+                (PlaybackSpeed::BeatsTotal(beats), Some(native_fps), Some(clip_duration)) if beats > 0.0 && clip_duration > 0.0 => (beats, native_fps, clip_duration),
+                _ => return,
+            },
+            None => return,
+        };
+
+        if self.loop_mode != LoopMode::Loop || self.rate <= 0.0 || !self.sync.is_playing() {
+            return;
+        }
+
+        if let Some(last_grid_correction) = self.last_grid_correction {
+            if last_grid_correction.elapsed() < BEAT_GRID_CORRECTION_INTERVAL {
+                return;
+            }
+        }
+
+        let position = if let Some(position) = self.position() {
+            position
+        } else {
+            return;
+        };
+
+        // The beats are spread over the loop region, `clip_duration` is its length
+        let expected_position = self.loop_start.unwrap_or(0.0) + (beat - self.beat_origin).rem_euclid(beats) / beats * clip_duration;
+
+        // Positions on either side of the loop point are close to each other
+        let drift = (position - expected_position).abs();
+        let drift = drift.min(clip_duration - drift);
+
+        if drift * native_fps > BEAT_GRID_TOLERANCE {
+            self.last_grid_correction = Some(Instant::now());
+            if let Err(e) = self.seek(expected_position) {
+                provider_log!(error, self, "{:?}", e);
+            }
+        }
+    }
+
+    // Restarts the clip on the next multiple of `quantum` beats, e.g. 1 for the next beat or 4 for the next bar
+    pub fn retrigger(&mut self, quantum: f64) {
+        let beat = self.sync.lock().map(|state| state.beat).unwrap_or(0.0);
+
+        if quantum <= 0.0 {
+            self.pending_retrigger = Some(beat);
+        } else {
+            self.pending_retrigger = Some(((beat / quantum).floor() + 1.0) * quantum);
+        }
+    }
+
+    fn apply_retrigger(&mut self, beat: f64) {
+        let retrigger_beat = match self.pending_retrigger {
+            Some(retrigger_beat) if beat >= retrigger_beat => retrigger_beat,
+            _ => return,
+        };
+        self.pending_retrigger = None;
+
+        // Beat locked modes count from the retrigger point from now on
+        self.beat_origin = retrigger_beat;
+        self.remap_position = None;
+        self.ended = false;
+
+        let restart_position = self.restart_position();
+        if let Err(e) = self.seek(restart_position) {
+            provider_log!(error, self, "{:?}", e);
+        }
+    }
+
+    pub fn set_random_slice(&mut self, random_slice: Option<RandomSlice>) {
+        self.random_slice = random_slice.filter(|random_slice| random_slice.every > 0.0);
+        self.slice_index = None;
+    }
+
+    // Jumps to a random position of the loop region whenever a new slice starts
+    fn apply_random_slice(&mut self, beat: f64) {
+        let random_slice = if let Some(random_slice) = self.random_slice {
+            random_slice
+        } else {
+            return;
+        };
+
+        let slice_index = ((beat - self.beat_origin) / random_slice.every).floor() as i64;
+        if self.slice_index == Some(slice_index) {
+            return;
+        }
+        self.slice_index = Some(slice_index);
+
+        let start = self.loop_start.unwrap_or(0.0);
+        let end = if let Some(end) = self.loop_end.or_else(|| self.duration()) {
+            end
+        } else {
+            return;
+        };
+
+        let position = start + (end - start).max(0.0) * random_unit(random_slice.seed, slice_index as u64);
+        if let Err(e) = self.seek(position) {
+            provider_log!(error, self, "{:?}", e);
+        }
+    }
 
-    beat: Arc<Mutex<f64>>,
-    next_sync_beat: Arc<Mutex<f64>>,
+    // Bounds the time set_beat / set_time may block the render thread, None waits until the decoder catches up
+    pub fn set_max_sync_wait(&mut self, max_sync_wait: Option<Duration>) {
+        self.max_sync_wait = max_sync_wait;
+    }
 
-    time: Arc<Mutex<f64>>,
-    next_sync_time: Arc<Mutex<f64>>,
+    pub fn set_late_policy(&mut self, late_policy: LatePolicy) {
+        self.late_policy = late_policy;
+    }
 
-    speed: Arc<Mutex<Speed>>,
-}
+    pub fn is_playing(&self) -> bool {
+        self.sync
+            .lock()
+            .map(|state| state.playing && !state.stopped)
+            .unwrap_or(false)
+    }
 
-impl VideoProvider {
-    pub fn new(path: &str, name: String, resolution: (usize, usize), speed: Speed, start_beat: f64, start_time: f64, start_playing: bool) -> Result<Self> {
-       
-       
-        gst::init().expect("Failed to initialize the gstreamer library");
-        let path = if path.starts_with("http") {
-            path.to_owned()
-        } else {
-            let path = if cfg!(target_os = "windows") {
-                path.replace('\\', "/")
-            } else {
-                path.to_owned()
-            };
+    pub fn is_stopped(&self) -> bool {
+        self.sync.lock().map(|state| state.stopped).unwrap_or(true)
+    }
 
-            Url::from_file_path(path).unwrap().as_str().to_string()
-        };
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
 
+    pub fn loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
 
+    // Timestamp and index in the source of the last frame provided through get()
+    pub fn frame_pts(&self) -> Option<f64> {
+        self.frame_pts
+    }
 
-        let video_buffer = Arc::new(Mutex::new(Buffer {
-            dimensions: vec![resolution.0, resolution.1, 3],
-            data: None,
-        }));
+    pub fn frame_index(&self) -> Option<u64> {
+        self.frame_index
+    }
 
+    pub fn decoded_frame_count(&self) -> u64 {
+        self.frame_queue.pushed_count()
+    }
 
-        let speed = Arc::new(Mutex::new(speed));
-        
-        let playing_lock = Arc::new(AtomicBool::new(start_playing));
-        let stop_lock = Arc::new(AtomicBool::new(false));
+    // Frames that were decoded but replaced by a newer one before being provided
+    pub fn dropped_frame_count(&self) -> u64 {
+        self.frame_queue.overwritten_count()
+    }
 
-        let beat = Arc::new(Mutex::new(start_beat));
-        let next_sync_beat = Arc::new(Mutex::new(start_beat));
+    pub fn stats(&self) -> DecodeStats {
+        self.frame_queue.stats()
+    }
 
-        let time = Arc::new(Mutex::new(start_time));
-        let next_sync_time = Arc::new(Mutex::new(start_time));
+    // Provides the statistics as the <name>_stats uniform, see DecodeStats::to_vec for its layout
+    pub fn set_stats_uniform(&mut self, enabled: bool) {
+        self.stats_uniform = enabled;
+    }
 
-        let pipeline_string = format!(
-            "uridecodebin uri={} ! videoconvert ! videoscale ! video/x-raw,format=RGB,format=RGBA,format=BGR,format=BGRA,width={:},height={:} ! videoflip method=vertical-flip ! appsink name=appsink async=false sync=false",
-            path, resolution.0, resolution.1,
-        );
+    pub fn is_eos(&self) -> bool {
+        self.ended
+    }
 
-        let pipeline =
-            gst::parse_launch(&pipeline_string).context("Failed to build gstreamer pipeline")?;
+    pub fn is_buffering(&self) -> bool {
+        self.buffering_percent < 100
+    }
 
-        let sink = pipeline
-            .clone()
-            .dynamic_cast::<gst::Bin>()
-            .expect("Failed to cast the gstreamer pipeline as a gst::Bin element")
-            .get_by_name("appsink")
-            .expect("Failed to retrieve sink from gstreamer pipeline.");
+    fn on_buffering(&mut self, percent: i32) {
+        let was_buffering = self.is_buffering();
+        self.buffering_percent = percent;
 
-        let appsink = sink
-            .dynamic_cast::<gst_app::AppSink>()
-            .expect("The sink defined in the pipeline is not an appsink");
+        if !self.sync.is_playing() {
+            return;
+        }
 
-        {
-            let speed_mutex = speed.clone();
-            let stop_lock = stop_lock.clone();
-            let playing_lock = playing_lock.clone();
-
-            let beat = beat.clone();
-            let next_sync_beat = next_sync_beat.clone();
-
-            let time = time.clone();
-            let next_sync_time = next_sync_time.clone();
-
-            let video_buffer = video_buffer.clone();
-            appsink.set_callbacks(
-                gst_app::AppSinkCallbacks::builder()
-                    .new_sample(move |appsink| {
-                        loop {
-                            if stop_lock.load(Ordering::Relaxed) {
-                                    break;
-                                }
-                                if !playing_lock.load(Ordering::Relaxed) {
-                                        break;
-                                    }
-                            let speed;
-                            if let Ok(speed_mutex) = speed_mutex.lock() {
-                                speed = speed_mutex.to_owned();
-                            } else {
-                                // The main thread most likely crashed
-                                return Err(gst::FlowError::Eos);
-                            }
+        // The pipeline is held paused while buffering so that playback does not stutter
+        if self.is_buffering() && !was_buffering {
+            if let Err(e) = self.pipeline.set_state(State::Paused) {
+                provider_log!(error, self, "Failed to pause video stream while buffering: {:?}", e);
+            }
+        } else if !self.is_buffering() && was_buffering {
+            if let Err(e) = self.pipeline.set_state(State::Playing) {
+                provider_log!(error, self, "Failed to resume video stream after buffering: {:?}", e);
+            }
 
-                            match speed {
-                                Speed::Fpb(frames_per_beat) => {
-                                    if let Ok(beat) = beat.lock() {
-                                        if let Ok(mut next_sync_beat) = next_sync_beat.lock() {
-                                            if *beat > *next_sync_beat {
-                                                *next_sync_beat += 1.0 / (frames_per_beat as f64);
-                                                break;
-                                            }
-                                        } else {
-                                            // The main thread most likely crashed
-                                            return Err(gst::FlowError::Eos);
-                                        }
-                                    } else {
-                                        // The main thread most likely crashed
-                                        return Err(gst::FlowError::Eos);
-                                    }
-                                }
-                                Speed::Fps(frame_rate) => {
-                                    if let Ok(time) = time.lock() {
-                                        if let Ok(mut next_sync_time) = next_sync_time.lock() {
-                                            if *time > *next_sync_time {
-                                                *next_sync_time += 1.0 / (frame_rate as f64);
-                                                break;
-                                            } 
-                                        } else {
-                                            // The main thread most likely crashed
-                                            return Err(gst::FlowError::Error);
-                                        }
-                                    } else {
-                                        // The main thread most likely crashed
-                                        return Err(gst::FlowError::Error);
-                                    }
-                                }
-                            }
-                            thread::sleep(Duration::from_micros(50))
-                        }
-                        
+            if self.follow_live_edge {
+                if let Err(e) = self.seek_live_edge() {
+                    provider_log!(error, self, "{:?}", e);
+                }
+            }
+        }
+    }
 
-                        let sample = match appsink.pull_sample() {
-                            Err(e) => {
-                                eprintln!("{:}", e);
-                                return Err(gst::FlowError::Eos);
-                            }
-                            Ok(sample) => sample,
-                        };
-
-                        let sample_caps = if let Some(sample_caps) = sample.get_caps() {
-                            sample_caps
-                        } else {
-                            
-                            return Err(gst::FlowError::Error);
-                        };
-
-                        let video_info = if let Ok(video_info) = gst_video::VideoInfo::from_caps(sample_caps) {
-                            video_info
-                        } else {
-                            
-                            return Err(gst::FlowError::Error);
-                        };
-
-                        let buffer = if let Some(buffer) = sample.get_buffer() {
-                            buffer
-                        } else {
-                            
-                            return Err(gst::FlowError::Error);
-                        };
-
-                        let map = if let Ok(map) = buffer.map_readable() {
-                            map
-                        } else {
-                            
-                            return Err(gst::FlowError::Error);
-                        };
-
-                        let samples = map.as_slice().to_vec();
-                        let format = match video_info.format() {
-                            gst_video::VideoFormat::Rgb => TextureFormat::RGBU8,
-                            gst_video::VideoFormat::Rgba => TextureFormat::RGBAU8,
-                            gst_video::VideoFormat::Bgr => TextureFormat::BGRU8,
-                            gst_video::VideoFormat::Bgra => TextureFormat::BGRAU8,
-                            //gst_video::VideoFormat::Gray16Le => TextureFormat::RF16,
-                            unsupported_format => {
-                                eprintln!("Unsupported gstreamer format '{:?}'", unsupported_format);
-                                return Err(gst::FlowError::Error);
-                            }
-                        };
+    pub fn seek_live_edge(&mut self) -> Result<()> {
+        let mut query = gst::query::Seeking::new(gst::Format::Time);
+        if !self.pipeline.query(&mut query) {
+            return Err(anyhow::anyhow!("Failed to query seekable range of video stream"));
+        }
 
-                        let image_buffer = match format {
-                            TextureFormat::RGBU8 => DynamicImage::ImageRgb8(image::RgbImage::from_raw(video_info.width(), video_info.height(), samples).unwrap()).into_rgb8(),
-                            TextureFormat::RGBAU8 => DynamicImage::ImageRgba8(image::RgbaImage::from_raw(video_info.width(), video_info.height(), samples).unwrap()).into_rgb8(),
-                            TextureFormat::BGRU8 => DynamicImage::ImageBgr8(BgrImage::from_raw(video_info.width(), video_info.height(), samples).unwrap()).into_rgb8(),
-                            TextureFormat::BGRAU8 => DynamicImage::ImageBgra8(BgraImage::from_raw(video_info.width(), video_info.height(), samples).unwrap()).into_rgb8(),
-                        };
+        let (seekable, _, end) = query.get_result();
+        if !seekable {
+            return Ok(());
+        }
 
-                        let image_buffer = image_buffer.into_vec();
+        if let gst::GenericFormattedValue::Time(end) = end {
+            if let Some(end) = end.nseconds() {
+                self.seek((end as f64 / 1_000_000_000.0 - LIVE_EDGE_OFFSET).max(0.0))?;
+            }
+        }
 
-                        match video_buffer.lock() {
-                            Ok(mut video_buffer) => {
-                                video_buffer.data = Some(image_buffer);
-                                video_buffer.dimensions = vec![video_info.width() as usize, video_info.height() as usize, 3];
-                            }
-                            Err(e) => {
-                                eprintln!("Could not lock video buffer, did the main thread panic? \n{:?}", e);
-                                return Err(FlowError::Error);
-                            }
-                        }
+        Ok(())
+    }
+
+    fn on_eos(&mut self, segment_done: bool) {
+        // A segment seek can be chained without flushing, making the loop seamless
+        let flags = if segment_done {
+            gst::SeekFlags::ACCURATE
+        } else {
+            gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT
+        };
 
+        match self.loop_mode {
+            LoopMode::Loop => {
+                let restart_position = self.restart_position();
+                self.seek_segment(restart_position, flags).ok();
+                self.events.emit(ProviderEvent::Looped);
+            }
+            LoopMode::PingPong => {
+                self.rate = -self.rate;
 
-                        Ok(gst::FlowSuccess::Ok)
-                    })
-                    .build(),
-            );
+                let restart_position = self.restart_position();
+                self.seek_segment(restart_position, flags).ok();
+                self.events.emit(ProviderEvent::Looped);
+            }
+            LoopMode::Once => {
+                // Rewind to the start of the clip and wait for the next call to play()
+                self.sync.update(|state| state.playing = false);
+                if let Err(e) = self.pipeline.set_state(State::Ready) {
+                    provider_log!(error, self, "Failed to rewind video after playing it once: {:?}", e);
+                }
+                self.pending_seek = self.region_start_seek();
+                self.ended = true;
+                self.events.emit(ProviderEvent::Eos);
+            }
+            LoopMode::Hold => {
+                // Nothing to do, the last decoded frame stays on screen
+                self.ended = true;
+                self.events.emit(ProviderEvent::Eos);
+            }
         }
+    }
 
-        if start_playing {
-            pipeline.set_state(State::Playing).context(format!(
-                "Failed to start gstreamer pipeline for video {:?}",
-                path
-            ))?;
+    fn restart_position(&self) -> f64 {
+        if self.rate < 0.0 {
+            self.loop_end.or_else(|| self.duration()).unwrap_or(0.0)
+        } else {
+            self.loop_start.unwrap_or(0.0)
+        }
+    }
+
+    pub fn set_loop_mode(&mut self, loop_mode: LoopMode) {
+        self.loop_mode = loop_mode;
+    }
+
+    pub fn seek(&mut self, position: f64) -> Result<()> {
+        self.seek_segment(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE)
+            .context("Failed to seek in video")
+    }
+
+    // Negative rates play backwards. The pipeline plays at the rate itself with a native speed,
+    // the other speeds gate frames that much faster or slower
+    pub fn set_rate(&mut self, rate: f64) -> Result<()> {
+        if rate == 0.0 || !rate.is_finite() {
+            return Err(anyhow::anyhow!("Playback rate has to be a non-zero number, got {}", rate));
         }
 
-        Ok(Self {
-            name,
-            video_buffer,
-            pipeline,
-            time,
-            stop_lock,
-            playing_lock,
-            next_sync_time,
-            beat,
-            next_sync_beat,
-            speed,
+        let position = self.position().unwrap_or(0.0);
+
+        self.rate = rate;
+        self.sync.update(|state| state.rate = rate.abs());
+        self.seek_segment(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE)
+            .context("Failed to change video playback rate")
+    }
+
+    pub fn position(&self) -> Option<f64> {
+        self.pipeline
+            .query_position::<gst::ClockTime>()
+            .and_then(|position| position.nseconds())
+            .map(|position| position as f64 / 1_000_000_000.0)
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.pipeline
+            .query_duration::<gst::ClockTime>()
+            .and_then(|duration| duration.nseconds())
+            .map(|duration| duration as f64 / 1_000_000_000.0)
+    }
+
+    pub fn step_frame(&mut self) -> Result<()> {
+        if self.sync.is_playing() {
+            return Err(anyhow::anyhow!("Frame stepping is only available while the video is paused"));
+        }
+
+        let step = gst::event::Step::new(gst::format::Buffers(Some(1)), self.rate.abs(), true, false);
+        if !self.pipeline.send_event(step) {
+            return Err(anyhow::anyhow!("Failed to step to the next video frame"));
+        }
+
+        Ok(())
+    }
+
+    pub fn set_loop_region(&mut self, loop_start: Option<f64>, loop_end: Option<f64>) -> Result<()> {
+        self.loop_start = loop_start;
+        self.loop_end = loop_end;
+
+        // Clips stretched over a number of beats are stretched over the new region instead
+        let clip_duration = self.looped_duration();
+        self.sync.update(|state| state.clip_duration = clip_duration);
+
+        let position = self.position().unwrap_or(0.0);
+        let position = match (self.loop_start, self.loop_end) {
+            (Some(loop_start), _) if position < loop_start => loop_start,
+            (_, Some(loop_end)) if position > loop_end => self.loop_start.unwrap_or(0.0),
+            _ => position,
+        };
+
+        self.seek_segment(position, gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE)
+            .context("Failed to update video loop region")
+    }
+
+    fn seek_segment(&mut self, position: f64, flags: gst::SeekFlags) -> Result<()> {
+        let to_clock_time = |position: f64| gst::ClockTime::from_nseconds((position.max(0.0) * 1_000_000_000.0) as u64);
+
+        // Segment seeks post SegmentDone at the loop boundaries instead of Eos
+        let flags = if self.loop_start.is_some() || self.loop_end.is_some() {
+            flags | gst::SeekFlags::SEGMENT
+        } else {
+            flags
+        };
+
+        let pipeline = &self.pipeline;
+        let rate = self.rate;
+        let (loop_start, loop_end) = (self.loop_start, self.loop_end);
+
+        let result = self.sync.interrupt_waits(|| {
+            // Reverse playback runs from the requested position back to the start of the loop region
+            if rate < 0.0 {
+                pipeline.seek(
+                    rate,
+                    flags,
+                    gst::SeekType::Set,
+                    to_clock_time(loop_start.unwrap_or(0.0)),
+                    gst::SeekType::Set,
+                    to_clock_time(position),
+                )
+            } else {
+                let (stop_type, stop) = match loop_end {
+                    Some(loop_end) => (gst::SeekType::Set, to_clock_time(loop_end)),
+                    None => (gst::SeekType::None, gst::ClockTime::none()),
+                };
+
+                pipeline.seek(
+                    rate,
+                    flags,
+                    gst::SeekType::Set,
+                    to_clock_time(position),
+                    stop_type,
+                    stop,
+                )
+            }
+        });
+
+        result.context("Failed to perform seek on the gstreamer pipeline")?;
+        self.ended = false;
+
+        Ok(())
+    }
+}
+
+// Square pixels are enforced unless stretching, as videoscale would otherwise change the pixel aspect ratio to fill the frame
+pub(crate) fn scale_caps(output_format: OutputFormat, resolution: Option<(usize, usize)>, fit_mode: FitMode) -> String {
+    let resolution = if let Some(resolution) = resolution {
+        resolution
+    } else {
+        // Left unconstrained videoscale passes frames through at their native size
+        return format!("video/x-raw,{}", output_format.caps_format());
+    };
+
+    let caps = format!(
+        "video/x-raw,{},width={},height={}",
+        output_format.caps_format(),
+        resolution.0,
+        resolution.1
+    );
+
+    match fit_mode {
+        FitMode::Stretch => caps,
+        _ => format!("{},pixel-aspect-ratio=1/1", caps),
+    }
+}
+
+// Keeps decodebin from picking decoders running on a GPU or a dedicated chip, software ones are tried instead
+pub(crate) fn skip_hardware_decoders(decoder: &gst::Element) -> Result<()> {
+    decoder
+        .connect("autoplug-select", false, |args| {
+            let is_hardware = args
+                .get(3)
+                .and_then(|factory| factory.get::<gst::ElementFactory>().ok().flatten())
+                .and_then(|factory| factory.get_metadata(&gst::ELEMENT_METADATA_KLASS).map(|klass| klass.contains("Hardware")))
+                .unwrap_or(false);
+
+            // GST_AUTOPLUG_SELECT_TRY and GST_AUTOPLUG_SELECT_SKIP
+            let result = if is_hardware { 2 } else { 0 };
+
+            glib::Type::from_name("GstAutoplugSelectResult")
+                .and_then(glib::EnumClass::new)
+                .and_then(|enum_class| enum_class.to_value(result))
         })
+        .context("Failed to select the decoders of the video pipeline")?;
+
+    Ok(())
+}
+
+// Local paths are made absolute and percent-encoded, so that spaces, '#', '%' or non-ASCII characters survive in the URI
+pub(crate) fn path_to_uri(path: &str) -> Result<String> {
+    if path.contains("://") {
+        return Ok(path.to_owned());
     }
 
-    pub fn check_loop(&mut self) {
-        if let Some(view) = self
-            .pipeline
-            .get_bus()
-            .expect("Failed to find bus for video playback pipeline")
-            .timed_pop(gst::ClockTime::from_seconds(0))
-        {
-            if let gst::MessageView::Eos(_) = view.view() {
-                self.pipeline
-                    .seek_simple(
-                        gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
-                        gst::ClockTime::from_seconds(0),
-                    )
-                    .ok();
+    if !Path::new(path).is_file() {
+        bail!("Video file {:?} does not exist", path);
+    }
+
+    let absolute_path = std::env::current_dir()
+        .context("Failed to resolve the current directory")?
+        .join(path);
+
+    Ok(Url::from_file_path(&absolute_path)
+        .map_err(|_| anyhow!("Failed to build an URI for video file {:?}", path))?
+        .as_str()
+        .to_string())
+}
+
+// Values are quoted in launch strings as characters like '!' are not percent-encoded in URIs
+pub(crate) fn quote_launch_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Position of a decoder pad among the pads of the same media type, in the order the decoder exposed them
+pub(crate) fn stream_index(decoder: &gst::Element, pad: &gst::Pad, media_type: &str) -> Option<usize> {
+    let is_media_type = |pad: &gst::Pad| {
+        pad.get_current_caps()
+            .unwrap_or_else(|| pad.query_caps(None))
+            .get_structure(0)
+            .map(|structure| structure.get_name().starts_with(media_type))
+            .unwrap_or(false)
+    };
+
+    if !is_media_type(pad) {
+        return None;
+    }
+
+    decoder
+        .get_src_pads()
+        .into_iter()
+        .filter(|src_pad| is_media_type(src_pad))
+        .position(|src_pad| src_pad == *pad)
+}
+
+// Links the selected video stream to the rest of the pipeline. The links made by gst::parse_launch only happen once,
+// this also restores them whenever a restarted pipeline exposes its streams again.
+fn watch_video_pads(pipeline: &gst::Element, decoder: &gst::Element, video_track: Arc<AtomicUsize>) {
+    let pipeline = pipeline.downgrade();
+
+    decoder.connect_pad_added(move |decoder, pad| {
+        if stream_index(decoder, pad, "video/") != Some(video_track.load(Ordering::SeqCst)) {
+            return;
+        }
+
+        let sink_pad = pipeline
+            .upgrade()
+            .and_then(|pipeline| pipeline.dynamic_cast::<gst::Bin>().ok())
+            .and_then(|bin| bin.get_by_name("deinterlace"))
+            .and_then(|deinterlace| deinterlace.get_static_pad("sink"));
+
+        let sink_pad = if let Some(sink_pad) = sink_pad {
+            sink_pad
+        } else {
+            return;
+        };
+
+        if let Some(peer) = sink_pad.get_peer() {
+            if peer == *pad {
+                return;
+            }
+            if let Err(e) = peer.unlink(&sink_pad) {
+                error!("Failed to unlink the previous video stream: {:?}", e);
             }
         }
+
+        if let Err(e) = pad.link(&sink_pad) {
+            error!("Failed to link the selected video stream: {:?}", e);
+        }
+    });
+}
+
+// Maps a seed and an index to a number in [0, 1), always the same for the same inputs (splitmix64)
+fn random_unit(seed: u64, index: u64) -> f64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+// Catches unsupported schemes, unreadable files and missing decoders before the pipeline is built,
+// as they would otherwise only surface later on the bus. Remote sources are not probed to keep construction fast.
+fn validate_uri(uri: &str, probe: bool) -> Result<()> {
+    crate::init()?;
+
+    if let Err(e) = gst::Element::make_from_uri(gst::URIType::Src, uri, None) {
+        let scheme = uri.split("://").next().unwrap_or(uri);
+        bail!("No installed gstreamer plugin can read {:?} URIs like {:?}: {}", scheme, uri, e);
+    }
+
+    if probe {
+        probe_uri(uri)?;
+    }
+
+    Ok(())
+}
+
+// Blocks until the file is discovered
+fn probe_uri(uri: &str) -> Result<()> {
+    if !uri.starts_with("file://") {
+        return Ok(());
     }
 
+    let info = discover(uri)?;
+
+    if info.get_video_streams().is_empty() {
+        bail!("{:?} does not contain any video stream", uri);
+    }
+
+    Ok(())
+}
+
+fn is_adaptive_stream(path: &str) -> bool {
+    let path = path.split(|c| c == '?' || c == '#').next().unwrap_or(path).to_lowercase();
+
+    path.starts_with("http") && (path.ends_with(".m3u8") || path.ends_with(".mpd"))
 }
 
 impl Drop for VideoProvider {
     fn drop(&mut self) {
         if let Err(e) = self.stop() {
-            eprintln!("{:?}", e);
+            provider_log!(error, self, "{:?}", e);
         }
     }
 }
@@ -298,18 +2391,178 @@ impl InputProvider for VideoProvider {
     }
 
     fn provides(&self) -> Vec<String> {
-        vec![self.name.clone()]
+        let mut uniforms = vec![
+            self.name.clone(),
+            format!("{}_position", self.name),
+            format!("{}_duration", self.name),
+            format!("{}_progress", self.name),
+            format!("{}_buffering", self.name),
+            format!("{}_hdr_transfer", self.name),
+            format!("{}_ready", self.name),
+            format!("{}_error", self.name),
+            format!("{}_fft", self.name),
+            format!("{}_rms", self.name),
+            format!("{}_subtitle", self.name),
+            format!("{}_pts", self.name),
+            format!("{}_frame", self.name),
+            format!("{}_dropped_frames", self.name),
+        ];
+
+        if self.stats_uniform {
+            uniforms.push(format!("{}_stats", self.name));
+        }
+
+        uniforms
     }
     
     fn set_property(&mut self, property: &str, value: &DataHolder) {
         match (property, value) {
-            ("speed_fpb", DataHolder::Float(new_speed)) => if let Ok(mut speed) = self.speed.lock() {
-                *speed = Speed::Fpb(*new_speed);
+            ("speed_fpb", DataHolder::Float(new_speed)) => self.set_speed(PlaybackSpeed::Fpb(*new_speed)),
+            ("speed_fps", DataHolder::Float(new_speed)) => self.set_speed(PlaybackSpeed::Fps(*new_speed)),
+            ("speed_factor", DataHolder::Float(factor)) => self.set_speed(PlaybackSpeed::Factor(*factor as f64)),
+            ("speed_native", DataHolder::Bool(true)) => self.set_speed(PlaybackSpeed::Native),
+            ("speed_beats_total", DataHolder::Float(beats)) => self.set_speed(PlaybackSpeed::BeatsTotal(*beats as f64)),
+            ("remap_time", DataHolder::Float(scale)) => self.set_speed(PlaybackSpeed::RemapTime(*scale as f64)),
+            ("remap_beats", DataHolder::Float(seconds_per_beat)) => self.set_speed(PlaybackSpeed::RemapBeats(*seconds_per_beat as f64)),
+            ("seek", DataHolder::Float(position)) => if let Err(e) = self.seek(*position as f64) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("random_slice", DataHolder::Float(every)) => {
+                let seed = self.random_slice.map(|random_slice| random_slice.seed).unwrap_or_else(random_seed);
+                self.set_random_slice(Some(RandomSlice { every: *every as f64, seed }));
+            }
+            ("random_slice_seed", DataHolder::Int(seed)) => {
+                if let Some(random_slice) = self.random_slice {
+                    self.set_random_slice(Some(RandomSlice { seed: *seed as u64, ..random_slice }));
+                }
+            }
+            ("watch_file", DataHolder::Bool(watch)) => if let Err(e) = self.set_watch_file(*watch) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("path", DataHolder::String(path)) => if let Err(e) = self.set_path(path) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("retrigger", DataHolder::Float(quantum)) => self.retrigger(*quantum as f64),
+            ("rate", DataHolder::Float(rate)) => if let Err(e) = self.set_rate(*rate as f64) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("max_sync_wait", DataHolder::Float(max_sync_wait)) => {
+                let max_sync_wait = if *max_sync_wait < 0.0 {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(*max_sync_wait as f64))
+                };
+                self.set_max_sync_wait(max_sync_wait);
+            }
+            ("stall_timeout", DataHolder::Float(stall_timeout)) => {
+                let stall_timeout = if *stall_timeout <= 0.0 {
+                    None
+                } else {
+                    Some(Duration::from_secs_f64(*stall_timeout as f64))
+                };
+                self.set_stall_timeout(stall_timeout);
+            }
+            ("wait_for_consumer", DataHolder::Bool(wait)) => {
+                self.set_frame_pacing(if *wait { FramePacing::WaitForConsumer } else { FramePacing::DropToLatest });
+            }
+            ("skip_late_frames", DataHolder::Bool(skip)) => {
+                self.set_late_policy(if *skip { LatePolicy::Skip } else { LatePolicy::CatchUp });
+            }
+            ("freeze", DataHolder::Bool(freeze)) => self.set_freeze(*freeze),
+            ("stats", DataHolder::Bool(enabled)) => self.set_stats_uniform(*enabled),
+            ("paused", DataHolder::Bool(paused)) => {
+                let result = if *paused { self.pause() } else { self.play() };
+                if let Err(e) = result {
+                    provider_log!(error, self, "{:?}", e);
+                }
+            }
+            ("auto_restart", DataHolder::Bool(auto_restart)) => {
+                self.set_restart_policy(if *auto_restart { Some(ReconnectPolicy::default()) } else { None });
+            }
+            ("audio_analysis", DataHolder::Bool(enabled)) => self.set_audio_analysis(*enabled),
+            ("audio_output", DataHolder::Bool(enabled)) => if let Err(e) = self.set_audio_output(*enabled) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("audio_track", DataHolder::Int(track)) => if let Err(e) = self.set_audio_track((*track).max(0) as usize) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("video_track", DataHolder::Int(track)) => if let Err(e) = self.set_video_track((*track).max(0) as usize) {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("volume", DataHolder::Float(volume)) => self.set_volume(*volume as f64),
+            ("mute", DataHolder::Bool(mute)) => self.set_mute(*mute),
+            ("step_frame", DataHolder::Bool(true)) => if let Err(e) = self.step_frame() {
+                provider_log!(error, self, "{:?}", e);
+            }
+            ("loop_start", DataHolder::Float(loop_start)) => {
+                let loop_start = if *loop_start < 0.0 { None } else { Some(*loop_start as f64) };
+                if let Err(e) = self.set_loop_region(loop_start, self.loop_end) {
+                    provider_log!(error, self, "{:?}", e);
+                }
             }
-            ("speed_fps", DataHolder::Float(new_speed)) => if let Ok(mut speed) = self.speed.lock() {
-                *speed = Speed::Fps(*new_speed);
+            ("loop_end", DataHolder::Float(loop_end)) => {
+                let loop_end = if *loop_end < 0.0 { None } else { Some(*loop_end as f64) };
+                if let Err(e) = self.set_loop_region(self.loop_start, loop_end) {
+                    provider_log!(error, self, "{:?}", e);
+                }
+            }
+            ("crop_x", DataHolder::Float(_)) | ("crop_y", DataHolder::Float(_)) | ("crop_width", DataHolder::Float(_)) | ("crop_height", DataHolder::Float(_)) => {
+                let (mut x, mut y, mut width, mut height) = match self.crop {
+                    Some(CropRegion::Normalized(x, y, width, height)) => (x, y, width, height),
+                    _ => (0.0, 0.0, 1.0, 1.0),
+                };
+
+                if let DataHolder::Float(value) = value {
+                    let value = *value as f64;
+                    match property {
+                        "crop_x" => x = value,
+                        "crop_y" => y = value,
+                        "crop_width" => width = value,
+                        _ => height = value,
+                    }
+                }
+
+                // A region covering the whole frame disables cropping
+                let crop = if x <= 0.0 && y <= 0.0 && width >= 1.0 && height >= 1.0 {
+                    None
+                } else {
+                    Some(CropRegion::Normalized(x, y, width, height))
+                };
+
+                if let Err(e) = self.set_crop(crop) {
+                    provider_log!(error, self, "{:?}", e);
+                }
+            }
+            ("deinterlace", DataHolder::String(method)) => {
+                let method = match method.to_lowercase().as_str() {
+                    "none" | "off" | "disabled" => Ok(None),
+                    method => DeinterlaceMethod::from_str(method).map(Some),
+                };
+
+                match method {
+                    Ok(method) => if let Err(e) = self.set_deinterlace(method) {
+                        provider_log!(error, self, "{:?}", e);
+                    }
+                    Err(e) => provider_log!(error, self, "{:?}", e),
+                }
             }
-            _ => eprintln!("Set_property unimplemented for {:}", property),
+            ("fit_mode", DataHolder::String(fit_mode)) => match FitMode::from_str(fit_mode) {
+                Ok(fit_mode) => if let Err(e) = self.set_fit_mode(fit_mode) {
+                    provider_log!(error, self, "{:?}", e);
+                }
+                Err(e) => provider_log!(error, self, "{:?}", e),
+            }
+            ("orientation", DataHolder::String(orientation)) => match Orientation::from_str(orientation) {
+                Ok(orientation) => if let Err(e) = self.set_orientation(orientation) {
+                    provider_log!(error, self, "{:?}", e);
+                }
+                Err(e) => provider_log!(error, self, "{:?}", e),
+            }
+            ("loop_mode", DataHolder::String(loop_mode)) => match LoopMode::from_str(loop_mode) {
+                Ok(loop_mode) => self.set_loop_mode(loop_mode),
+                Err(e) => provider_log!(error, self, "{:?}", e),
+            }
+            _ => provider_log!(warn, self, "Set_property unimplemented for {:}", property),
         }
     }
 
@@ -317,113 +2570,110 @@ impl InputProvider for VideoProvider {
         if uniform_name == self.name {
             self.check_loop();
 
-            if let Ok(mut video_buffer) = self.video_buffer.lock() {
-                let result = video_buffer.data.as_ref().map(|data| {
-                    DataHolder::Texture((
-                        (
-                            video_buffer.dimensions[0] as u32,
-                            video_buffer.dimensions[1] as u32,
-                        ),
-                        data.to_vec(),
-                    ))});
-
-                if invalidate {
-                    video_buffer.data = None;
+            // Textures are taken with take_gl_texture, they can not be handed over as bytes
+            #[cfg(feature = "gl")]
+            {
+                if self.gl_context.is_some() {
+                    return None;
                 }
+            }
 
-                result
+            // An invalidated frame is handed over as is, it is only copied when it has to stay available
+            let frame = if self.sync.is_frozen() {
+                None
+            } else if invalidate {
+                self.frame_queue.take()
             } else {
+                self.frame_queue.peek()
+            };
+
+            frame.map(|frame| {
+                self.frame_pts = frame.pts;
+                self.frame_index = Some(frame.index);
+
+                DataHolder::Texture((
+                    (frame.dimensions[0] as u32, frame.dimensions[1] as u32),
+                    frame.data,
+                ))
+            })
+        } else if uniform_name == format!("{}_position", self.name) {
+            self.position().map(|position| DataHolder::Float(position as f32))
+        } else if uniform_name == format!("{}_duration", self.name) {
+            self.duration().map(|duration| DataHolder::Float(duration as f32))
+        } else if uniform_name == format!("{}_progress", self.name) {
+            match (self.position(), self.duration()) {
+                (Some(position), Some(duration)) if duration > 0.0 => Some(DataHolder::Float((position / duration) as f32)),
+                _ => None,
+            }
+        } else if uniform_name == format!("{}_buffering", self.name) {
+            Some(DataHolder::Float(self.buffering_percent as f32 / 100.0))
+        } else if uniform_name == format!("{}_hdr_transfer", self.name) {
+            let hdr_transfer = match self.hdr_transfer() {
+                HdrTransfer::Sdr => 0.0,
+                HdrTransfer::Pq => 1.0,
+                HdrTransfer::Hlg => 2.0,
+            };
+            Some(DataHolder::Float(hdr_transfer))
+        } else if uniform_name == format!("{}_ready", self.name) {
+            Some(DataHolder::Bool(self.is_ready()))
+        } else if uniform_name == format!("{}_error", self.name) {
+            self.last_error().map(DataHolder::String)
+        } else if uniform_name == format!("{}_fft", self.name) {
+            if self.audio_spectrum.is_empty() {
                 None
+            } else {
+                Some(DataHolder::FloatArray(self.audio_spectrum.clone()))
             }
+        } else if uniform_name == format!("{}_rms", self.name) {
+            Some(DataHolder::Float(self.audio_rms))
+        } else if uniform_name == format!("{}_subtitle", self.name) {
+            // An empty text clears the caption between two cues
+            Some(DataHolder::String(self.subtitle().unwrap_or_default()))
+        } else if uniform_name == format!("{}_pts", self.name) {
+            self.frame_pts.map(|pts| DataHolder::Float(pts as f32))
+        } else if uniform_name == format!("{}_frame", self.name) {
+            self.frame_index.map(|index| DataHolder::Int(index as i32))
+        } else if uniform_name == format!("{}_dropped_frames", self.name) {
+            Some(DataHolder::Int(self.dropped_frame_count() as i32))
+        } else if self.stats_uniform && uniform_name == format!("{}_stats", self.name) {
+            Some(DataHolder::FloatArray(self.stats().to_vec()))
         } else {
             None
         }
     }
 
     fn set_beat(&mut self, beat: f64, sync: bool) {
-        if let Ok(mut own_beat) = self.beat.lock() {
-            // Succesful locking of the Mutex is only checked here as use of the other mutexes depend on this one
-            *own_beat = beat;
-        } else {
+        self.sync.update(|state| state.beat = beat);
+        self.apply_retrigger(beat);
+        self.apply_random_slice(beat);
+        self.follow_beat_grid(beat);
+
+        if let Some(PlaybackSpeed::RemapBeats(seconds_per_beat)) = self.sync.lock().map(|state| state.speed) {
+            self.remap(beat - self.beat_origin, seconds_per_beat, sync);
             return;
         }
 
         if sync {
-            let speed;
-            if let Ok(speed_mutex) = self.speed.lock() {
-                speed = speed_mutex.to_owned();
-            } else {
-                return;
-            }
-
-            if let Speed::Fpb(_) = speed {
-                let wait_for_sync = if let Ok(next_sync_beat) = self.next_sync_beat.lock() {
-                    beat > *next_sync_beat
-                } else {
-                    // The video reading thread has most probably crashed
-                    return;
-                };
-
-                if wait_for_sync {
-                    loop {
-                        if let Ok(next_sync_beat) = self.next_sync_beat.lock() {
-                            if beat <= *next_sync_beat {
-                                break;
-                            }
-                        } else {
-                            // The video reading thread has most probably crashed
-                            return;
-                        };
-                        self.check_loop();
-                    }
-                }
-            }
+            self.wait_for_sync(move |state| !state.speed.is_beat_based() || beat <= state.next_sync_beat);
         }
     }
 
     fn set_time(&mut self, time: f64, sync: bool) {
-        if let Ok(mut own_time) = self.time.lock() {
-            *own_time = time;
-        } else {
-            // The video reading thread has most probably crashed
+        self.sync.update(|state| state.time = time);
+
+        if let Some(PlaybackSpeed::RemapTime(scale)) = self.sync.lock().map(|state| state.speed) {
+            self.remap(time, scale, sync);
             return;
         }
 
         if sync {
-            let speed;
-            if let Ok(speed_mutex) = self.speed.lock() {
-                speed = speed_mutex.to_owned();
-            } else {
-                return;
-            }
-
-            if let Speed::Fps(_) = speed {
-                let wait_for_sync = if let Ok(next_sync_time) = self.next_sync_time.lock() {
-                    time > *next_sync_time
-                } else {
-                    // The video reading thread has most probably crashed
-                    return;
-                };
-
-                if wait_for_sync {
-                    loop {
-                        if let Ok(next_sync_time) = self.next_sync_time.lock() {
-                            if time <= *next_sync_time {
-                                break;
-                            }
-                        } else {
-                            // The video reading thread has most probably crashed
-                            return;
-                        };
-                        self.check_loop();
-                    }
-                }
-            }
+            self.wait_for_sync(move |state| !state.speed.is_time_based() || time <= state.next_sync_time);
         }
     }
 
     fn stop(&mut self) -> Result<()>{
-        self.stop_lock.store(true, Ordering::Relaxed);
+        // Wakes up the streaming thread so that it does not hold the pipeline while shutting down
+        self.sync.update(|state| state.stopped = true);
         
         self.pipeline.set_state(State::Null).context("Failed to stop video playback")?;
          
@@ -431,23 +2681,27 @@ impl InputProvider for VideoProvider {
         Ok(())
     }
     fn play(&mut self) -> Result<()>{
-        self.playing_lock.store(true, Ordering::Relaxed);
-
-        if let (Ok(mut next_sync_beat), Ok(beat)) = (self.next_sync_beat.lock(), self.beat.lock()) {
-            *next_sync_beat = *beat;
-        }
-        
-        if let (Ok(mut next_sync_time), Ok(time)) = (self.next_sync_time.lock(), self.time.lock()) {
-            *next_sync_time = *time;
+        self.sync.update(|state| {
+            state.playing = true;
+            state.next_sync_beat = state.beat;
+            state.next_sync_time = state.time;
+        });
+        if self.loop_mode == LoopMode::Once {
+            self.ended = false;
         }
-        
-        self.pipeline.set_state(State::Playing).context("Failed to resume video playback")?;
+
+        let state = if self.is_remapped() || self.sync.is_frozen() {
+            State::Paused
+        } else {
+            State::Playing
+        };
+        self.pipeline.set_state(state).context("Failed to resume video playback")?;
          
         Ok(())
 
     }
     fn pause(&mut self) -> Result<()>{
-        self.playing_lock.store(false, Ordering::Relaxed);
+        self.sync.update(|state| state.playing = false);
         
         self.pipeline.set_state(State::Paused).context("Failed to pause video playback")?;
          
@@ -0,0 +1,56 @@
+use anyhow::{bail, Result};
+
+use crate::config::EncoderConfig;
+use crate::encoder::{EncodeError, VideoEncoder};
+use crate::stats::EncodeStats;
+use crate::video::Orientation;
+
+// Writes rendered frames to a v4l2loopback device, which other applications then open as a webcam.
+// The device is created beforehand, e.g. `modprobe v4l2loopback devices=1 exclusive_caps=1`.
+// Same as a VideoEncoder with a v4l2:// output
+pub struct VirtualCameraOutput {
+    encoder: VideoEncoder,
+}
+
+impl VirtualCameraOutput {
+    pub fn new(device: &str, width: usize, height: usize, framerate: f64) -> Result<Self> {
+        Self::from_config(&EncoderConfig::new(&format!("v4l2://{}", device), width, height, framerate))
+    }
+
+    // The path of the config is the v4l2:// URL of the device, the codec settings do not apply
+    pub fn from_config(config: &EncoderConfig) -> Result<Self> {
+        if !cfg!(target_os = "linux") {
+            bail!("Virtual camera output is only available on Linux, through v4l2loopback");
+        }
+
+        Ok(Self {
+            encoder: VideoEncoder::from_config(config)?,
+        })
+    }
+
+    pub fn send_frame(&mut self, time: f64, frame: &[u8]) -> Result<(), EncodeError> {
+        self.encoder.encode_frame(time, frame)
+    }
+
+    pub fn send_frame_with_stride(&mut self, time: f64, frame: &[u8], stride: usize) -> Result<(), EncodeError> {
+        self.encoder.encode_frame_with_stride(time, frame, stride)
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.encoder.set_orientation(orientation)
+    }
+
+    pub fn stats(&self) -> EncodeStats {
+        self.encoder.stats()
+    }
+
+    pub fn stop(&mut self) {
+        self.encoder.stop()
+    }
+}
+
+impl Drop for VirtualCameraOutput {
+    fn drop(&mut self) {
+        self.encoder.stop();
+    }
+}
@@ -0,0 +1,278 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use log::error;
+
+use gst::prelude::*;
+use gst::{Fraction, State};
+use gst_video::VideoInfo;
+
+use crate::encode_queue::FrameLayout;
+use crate::encoder::{EncodeError, PixelFormat};
+use crate::video::quote_launch_value;
+use crate::video::Orientation;
+
+const WEBRTC_NAME: &str = "webrtc";
+const FLIP_NAME: &str = "flip";
+
+// Messages to forward to the remote peer through whatever signalling channel the host uses
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebRtcSignal {
+    Offer(String),
+    IceCandidate { mline_index: u32, candidate: String },
+}
+
+// Sends rendered frames to a single browser peer as VP8 over WebRTC.
+// The offer and ICE candidates come out of signals(), the answer and the candidates of the peer go in
+// through set_remote_answer and add_ice_candidate
+pub struct WebRtcOutput {
+    pipeline: gst::Element,
+    app_src: gst_app::AppSrc,
+    videoflip: gst::Element,
+    webrtc: gst::Element,
+    signals: Arc<Mutex<Option<Sender<WebRtcSignal>>>>,
+    layout: FrameLayout,
+    error: Option<String>,
+}
+
+impl WebRtcOutput {
+    // `stun_server` looks like "stun://stun.l.google.com:19302", peers on the same network do not need one
+    pub fn new(width: usize, height: usize, framerate: f64, bitrate: u32, stun_server: Option<&str>) -> Result<Self> {
+        Self::with_pixel_format(width, height, framerate, PixelFormat::default(), bitrate, stun_server)
+    }
+
+    // Frames are given in `pixel_format`, with tightly packed rows unless sent with send_frame_with_stride
+    pub fn with_pixel_format(
+        width: usize,
+        height: usize,
+        framerate: f64,
+        pixel_format: PixelFormat,
+        bitrate: u32,
+        stun_server: Option<&str>,
+    ) -> Result<Self> {
+        crate::init()?;
+
+        let caps = VideoInfo::builder(pixel_format.video_format(), width as u32, height as u32)
+            .fps(Fraction::new((framerate * 1000.0) as i32, 1000))
+            .build()
+            .context("Failed to describe the frames sent over WebRTC")?
+            .to_caps()
+            .context("Failed to describe the frames sent over WebRTC")?;
+
+        let mut webrtc_properties = String::from("bundle-policy=max-bundle");
+        if let Some(stun_server) = stun_server {
+            webrtc_properties.push_str(&format!(" stun-server=\"{}\"", quote_launch_value(stun_server)));
+        }
+
+        // Rendered frames are bottom-up, the peer gets them in the usual top-down order unless told otherwise.
+        // Float frames come as ARGB64, videoflip does not take it
+        let pipeline_string = format!(
+            "appsrc name=appsrc format=time is-live=true ! videoconvert ! videoflip name={} method={} ! videoconvert ! queue leaky=downstream ! vp8enc deadline=1 target-bitrate={} ! rtpvp8pay ! application/x-rtp,media=video,encoding-name=VP8,payload=96 ! webrtcbin name={} {}",
+            FLIP_NAME,
+            Orientation::default().videoflip_method(),
+            bitrate.saturating_mul(1000),
+            WEBRTC_NAME,
+            webrtc_properties,
+        );
+
+        let pipeline =
+            gst::parse_launch(&pipeline_string).context("Failed to build gstreamer pipeline")?;
+
+        let bin = pipeline
+            .clone()
+            .dynamic_cast::<gst::Bin>()
+            .expect("Failed to cast the gstreamer pipeline as a gst::Bin element");
+
+        let app_src = bin
+            .get_by_name("appsrc")
+            .expect("Failed to retrieve source from gstreamer pipeline.")
+            .dynamic_cast::<gst_app::AppSrc>()
+            .expect("The source defined in the pipeline is not an appsrc");
+        app_src.set_caps(Some(&caps));
+
+        let videoflip = bin
+            .get_by_name(FLIP_NAME)
+            .expect("Failed to retrieve videoflip from gstreamer pipeline.");
+
+        let webrtc = bin
+            .get_by_name(WEBRTC_NAME)
+            .expect("Failed to retrieve webrtcbin from gstreamer pipeline.");
+
+        let signals = Arc::new(Mutex::new(None));
+        watch_negotiation(&webrtc, signals.clone())?;
+
+        pipeline
+            .set_state(State::Playing)
+            .context("Failed to start WebRTC output")?;
+
+        Ok(Self {
+            pipeline,
+            app_src,
+            videoflip,
+            webrtc,
+            signals,
+            layout: FrameLayout {
+                format: pixel_format,
+                width,
+                height,
+            },
+            error: None,
+        })
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.videoflip.set_property_from_str("method", orientation.videoflip_method());
+    }
+
+    // Only the last receiver gets the signals, the offer is sent again by restarting the negotiation
+    pub fn signals(&mut self) -> Receiver<WebRtcSignal> {
+        let (sender, receiver) = channel();
+        if let Ok(mut signals) = self.signals.lock() {
+            *signals = Some(sender);
+        }
+
+        receiver
+    }
+
+    pub fn set_remote_answer(&mut self, sdp: &str) -> Result<()> {
+        let sdp = gst_sdp::SDPMessage::parse_buffer(sdp.as_bytes())
+            .map_err(|_| anyhow!("Failed to parse the SDP answer of the peer"))?;
+        let answer = gst_webrtc::WebRTCSessionDescription::new(gst_webrtc::WebRTCSDPType::Answer, sdp);
+
+        self.webrtc
+            .emit("set-remote-description", &[&answer, &None::<gst::Promise>])
+            .context("Failed to set the answer of the peer")?;
+
+        Ok(())
+    }
+
+    pub fn add_ice_candidate(&mut self, mline_index: u32, candidate: &str) -> Result<()> {
+        self.webrtc
+            .emit("add-ice-candidate", &[&mline_index, &candidate])
+            .context("Failed to add the ICE candidate of the peer")?;
+
+        Ok(())
+    }
+
+    pub fn send_frame(&mut self, time: f64, frame: &[u8]) -> Result<(), EncodeError> {
+        self.send_frame_with_stride(time, frame, self.layout.row_size())
+    }
+
+    // `stride` is the number of bytes from the start of a row to the start of the next one, padding included
+    pub fn send_frame_with_stride(&mut self, time: f64, frame: &[u8], stride: usize) -> Result<(), EncodeError> {
+        self.check_bus()?;
+
+        let size = self.layout.frame_size(stride)?;
+        if frame.len() < size {
+            return Err(EncodeError::InvalidFrame(format!(
+                "{} bytes were given where {} are needed",
+                frame.len(),
+                size
+            )));
+        }
+
+        let mut buffer = self.layout.pixels_buffer(frame[..size].to_vec(), stride);
+        buffer.make_mut().set_pts((time * 1_000.0) as u64 * gst::MSECOND);
+
+        self.app_src
+            .push_buffer(buffer)
+            .map(|_| ())
+            .map_err(|e| EncodeError::Pipeline(format!("Failed to send frame over WebRTC: {:?}", e)))
+    }
+
+    // The pipeline stays failed once an error was posted, e.g. when the encoder could not be negotiated
+    fn check_bus(&mut self) -> Result<(), EncodeError> {
+        if let Some(bus) = self.pipeline.get_bus() {
+            while let Some(message) = bus.timed_pop_filtered(gst::ClockTime::from_seconds(0), &[gst::MessageType::Error]) {
+                if let gst::MessageView::Error(err) = message.view() {
+                    let description = format!("{} ({})", err.get_error(), err.get_debug().unwrap_or_default());
+                    error!("WebRTC output error: {}", description);
+                    self.error.get_or_insert(description);
+                }
+            }
+        }
+
+        match &self.error {
+            Some(error) => Err(EncodeError::Pipeline(error.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn emit_signal(signals: &Mutex<Option<Sender<WebRtcSignal>>>, signal: WebRtcSignal) {
+    if let Ok(signals) = signals.lock() {
+        if let Some(sender) = signals.as_ref() {
+            if sender.send(signal).is_err() {
+                error!("WebRTC signalling receiver was dropped");
+            }
+        }
+    }
+}
+
+fn watch_negotiation(webrtc: &gst::Element, signals: Arc<Mutex<Option<Sender<WebRtcSignal>>>>) -> Result<()> {
+    {
+        let signals = signals.clone();
+        webrtc
+            .connect("on-negotiation-needed", false, move |values| {
+                let webrtc = values[0].get::<gst::Element>().ok().flatten()?;
+                let signals = signals.clone();
+                let offer_webrtc = webrtc.clone();
+
+                let promise = gst::Promise::new_with_change_func(move |promise| {
+                    let offer = promise
+                        .get_reply()
+                        .and_then(|reply| reply.get_value("offer").ok())
+                        .and_then(|offer| offer.get::<gst_webrtc::WebRTCSessionDescription>().ok().flatten());
+
+                    let offer = match offer {
+                        Some(offer) => offer,
+                        None => {
+                            error!("webrtcbin did not create an offer");
+                            return;
+                        }
+                    };
+
+                    if let Err(e) = offer_webrtc.emit("set-local-description", &[&offer, &None::<gst::Promise>]) {
+                        error!("Failed to set the local WebRTC description: {:?}", e);
+                        return;
+                    }
+
+                    match offer.get_sdp().as_text() {
+                        Ok(sdp) => emit_signal(&signals, WebRtcSignal::Offer(sdp)),
+                        Err(e) => error!("Failed to serialize the WebRTC offer: {:?}", e),
+                    }
+                });
+
+                if let Err(e) = webrtc.emit("create-offer", &[&None::<gst::Structure>, &promise]) {
+                    error!("Failed to create a WebRTC offer: {:?}", e);
+                }
+
+                None
+            })
+            .context("Failed to watch WebRTC negotiation")?;
+    }
+
+    webrtc
+        .connect("on-ice-candidate", false, move |values| {
+            let mline_index = values[1].get::<u32>().ok().flatten()?;
+            let candidate = values[2].get::<String>().ok().flatten()?;
+            emit_signal(&signals, WebRtcSignal::IceCandidate { mline_index, candidate });
+
+            None
+        })
+        .context("Failed to watch WebRTC ICE candidates")?;
+
+    Ok(())
+}
+
+impl Drop for WebRtcOutput {
+    fn drop(&mut self) {
+        if let Err(e) = self.app_src.end_of_stream() {
+            error!("Failed to end stream: {:?}", e);
+        }
+        if let Err(e) = self.pipeline.set_state(State::Null) {
+            error!("Failed to stop WebRTC output: {:?}", e);
+        }
+    }
+}